@@ -0,0 +1,479 @@
+// ! EtherNet/IP 封装和 CIP 报文
+//
+// * 封装头：命令(2) + 长度(2) + 会话句柄(4) + 状态(4) + 发送方上下文(8) + 选项(4)，小端
+// * 未连接报文使用 SendRRData，连接报文(Forward Open 后)使用 SendUnitData
+// * CIP 请求：服务(1) + 路径字数(1) + 路径 + 数据；回复：服务|0x80 + 保留 + 通用状态 + 扩展状态字数 + 扩展状态 + 数据
+
+use crate::prelude::*;
+
+/// 封装头长度
+pub const ENCAP_HEADER_LEN: usize = 24;
+/// 注册会话
+pub const REGISTER_SESSION: u16 = 0x0065;
+/// 注销会话
+pub const UNREGISTER_SESSION: u16 = 0x0066;
+/// 发送未连接报文
+pub const SEND_RR_DATA: u16 = 0x006F;
+/// 发送连接报文
+pub const SEND_UNIT_DATA: u16 = 0x0070;
+
+/// 读取标签
+pub const READ_TAG: u8 = 0x4C;
+/// 写入标签
+pub const WRITE_TAG: u8 = 0x4D;
+/// 分段读取标签
+pub const READ_TAG_FRAGMENTED: u8 = 0x52;
+/// 分段写入标签
+pub const WRITE_TAG_FRAGMENTED: u8 = 0x53;
+/// 多服务请求
+pub const MULTIPLE_SERVICE: u8 = 0x0A;
+/// 打开连接
+pub const FORWARD_OPEN: u8 = 0x54;
+/// 关闭连接
+pub const FORWARD_CLOSE: u8 = 0x4E;
+/// 未连接发送(与分段读取服务码相同，但是发往连接管理器)
+const UNCONNECTED_SEND: u8 = 0x52;
+
+/// 通用状态：部分数据，分段读取时还有剩余数据
+pub const STATUS_PARTIAL: u8 = 0x06;
+/// 通用状态：多服务请求中有服务出错
+pub const STATUS_EMBEDDED: u8 = 0x1E;
+
+/// 单个请求中数据的最大字节数，超过时分段写入或者拆分多服务请求
+pub const MAX_DATA_BYTES: usize = 400;
+
+/// 消息路由器(类 0x02 实例 1)
+const MESSAGE_ROUTER: [u8; 4] = [0x20, 0x02, 0x24, 0x01];
+/// 连接管理器(类 0x06 实例 1)
+const CONNECTION_MANAGER: [u8; 4] = [0x20, 0x06, 0x24, 0x01];
+/// 打开连接时使用的厂商编号和序列号
+const VENDOR_ID: u16 = 0x1337;
+const ORIGINATOR_SERIAL: u32 = 0x4B49_4D00;
+/// 连接的请求包间隔(微秒)
+const RPI: u32 = 2_000_000;
+/// 连接参数：点对点、低优先级、可变长度、最大 500 字节
+const CONNECTION_PARAMS: u16 = 0x43F4;
+
+/// 标签名称转换为符号路径
+///
+/// 例如 `Program:Main.Counter[3]`、`Axis[1,2].Pos`，成员以 `.` 分隔，数组下标以 `,` 分隔
+pub fn encode_tag_path(tag: &str) -> Result<Vec<u8>, PlcError> {
+    let invalid = || PlcError::Addr(format!("CIP 无效的标签名称\t标签={}", tag));
+    let mut path = Vec::with_capacity(tag.len() + 8);
+    for member in tag.trim().split('.') {
+        let (name, indexes) = match member.split_once('[') {
+            Some((name, rest)) => (name, Some(rest.strip_suffix(']').ok_or_else(invalid)?)),
+            None => (member, None),
+        };
+        let valid = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b':';
+        if name.is_empty() || name.len() > 255 || !name.bytes().all(valid) {
+            return Err(invalid());
+        }
+        path.extend([0x91, name.len() as u8]);
+        path.extend_from_slice(name.as_bytes());
+        if name.len() % 2 == 1 {
+            path.push(0);
+        }
+        for index in indexes.into_iter().flat_map(|indexes| indexes.split(',')) {
+            let index: u32 = index.trim().parse().map_err(|_| invalid())?;
+            match index {
+                0..=0xFF => path.extend([0x28, index as u8]),
+                0x100..=0xFFFF => {
+                    path.extend([0x29, 0]);
+                    path.extend((index as u16).to_le_bytes());
+                }
+                _ => {
+                    path.extend([0x2A, 0]);
+                    path.extend(index.to_le_bytes());
+                }
+            }
+        }
+    }
+    Ok(path)
+}
+
+/// CIP 请求
+pub fn cip_request(service: u8, path: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut request = Vec::with_capacity(2 + path.len() + data.len());
+    request.extend([service, (path.len() / 2) as u8]);
+    request.extend_from_slice(path);
+    request.extend_from_slice(data);
+    request
+}
+
+/// 读取标签请求
+pub fn read_tag_request(path: &[u8], count: u16) -> Vec<u8> {
+    cip_request(READ_TAG, path, &count.to_le_bytes())
+}
+
+/// 分段读取标签请求
+pub fn read_fragmented_request(path: &[u8], count: u16, offset: u32) -> Vec<u8> {
+    let mut data = count.to_le_bytes().to_vec();
+    data.extend(offset.to_le_bytes());
+    cip_request(READ_TAG_FRAGMENTED, path, &data)
+}
+
+/// 写入标签请求
+pub fn write_tag_request(path: &[u8], type_code: u16, count: u16, bytes: &[u8]) -> Vec<u8> {
+    let mut data = type_code.to_le_bytes().to_vec();
+    data.extend(count.to_le_bytes());
+    data.extend_from_slice(bytes);
+    cip_request(WRITE_TAG, path, &data)
+}
+
+/// 分段写入标签请求
+pub fn write_fragmented_request(
+    path: &[u8],
+    type_code: u16,
+    count: u16,
+    offset: u32,
+    bytes: &[u8],
+) -> Vec<u8> {
+    let mut data = type_code.to_le_bytes().to_vec();
+    data.extend(count.to_le_bytes());
+    data.extend(offset.to_le_bytes());
+    data.extend_from_slice(bytes);
+    cip_request(WRITE_TAG_FRAGMENTED, path, &data)
+}
+
+/// 多服务请求，将多个请求合并为一个发往消息路由器
+pub fn multiple_service_request(requests: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = (requests.len() as u16).to_le_bytes().to_vec();
+    // 偏移量从服务数量开始计算
+    let mut offset = 2 + 2 * requests.len();
+    for request in requests {
+        data.extend((offset as u16).to_le_bytes());
+        offset += request.len();
+    }
+    for request in requests {
+        data.extend_from_slice(request);
+    }
+    cip_request(MULTIPLE_SERVICE, &MESSAGE_ROUTER, &data)
+}
+
+/// 拆分多服务回复的数据，返回每个服务的回复
+pub fn parse_multiple_service_data(data: &[u8]) -> Result<Vec<&[u8]>, PlcError> {
+    let invalid = || PlcError::Comm(format!("CIP 多服务回复解析失败\t数据={:02X?}", data));
+    let count = u16::from_le_bytes(data.get(..2).ok_or_else(invalid)?.try_into().unwrap());
+    let offsets = data.get(2..2 + 2 * count as usize).ok_or_else(invalid)?;
+    let offsets: Vec<usize> = offsets
+        .chunks_exact(2)
+        .map(|offset| u16::from_le_bytes([offset[0], offset[1]]) as usize)
+        .chain([data.len()])
+        .collect();
+    offsets
+        .windows(2)
+        .map(|range| data.get(range[0]..range[1]).ok_or_else(invalid))
+        .collect()
+}
+
+/// CIP 回复
+pub struct CipReply<'a> {
+    /// 通用状态
+    pub status: u8,
+    /// 第一个扩展状态，没有时为0
+    pub ext_status: u16,
+    /// 回复数据
+    pub data: &'a [u8],
+}
+
+impl CipReply<'_> {
+    /// 检查通用状态，部分数据(0x06)由调用者处理
+    pub fn check(&self) -> PlcResult {
+        match self.status {
+            0x00 | STATUS_PARTIAL => Ok(()),
+            status => Err(status_error(status, self.ext_status)),
+        }
+    }
+}
+
+/// 解析 CIP 回复
+pub fn parse_reply(reply: &[u8], service: u8) -> Result<CipReply<'_>, PlcError> {
+    let invalid = || PlcError::Comm(format!("CIP 回复解析失败\t数据={:02X?}", reply));
+    if reply.len() < 4 || reply[0] != service | 0x80 {
+        return Err(invalid());
+    }
+    let start = 4 + reply[3] as usize * 2;
+    if reply.len() < start {
+        return Err(invalid());
+    }
+    let ext_status = match reply[3] {
+        0 => 0,
+        _ => u16::from_le_bytes([reply[4], reply[5]]),
+    };
+    Ok(CipReply {
+        status: reply[2],
+        ext_status,
+        data: &reply[start..],
+    })
+}
+
+/// 通用状态转换为错误
+fn status_error(status: u8, ext_status: u16) -> PlcError {
+    let desc = match (status, ext_status) {
+        (0x01, _) => "连接失败",
+        (0x04, _) => "路径错误",
+        (0x05, _) => "路径目标不存在",
+        (0x08, _) => "不支持的服务",
+        (0x0F, _) => "权限不足",
+        (0x13, _) => "数据不足",
+        (0x15, _) => "数据过多",
+        (0x1E, _) => "多服务请求中有服务出错",
+        (0xFF, 0x2105) => "超出范围",
+        (0xFF, 0x2107) => "数据类型不一致",
+        _ => "未知错误",
+    };
+    let msg = format!(
+        "CIP 错误：{}\t状态=0x{:02X}\t扩展状态=0x{:04X}",
+        desc, status, ext_status
+    );
+    match (status, ext_status) {
+        (0x04 | 0x05, _) | (0xFF, 0x2105) => PlcError::Addr(msg),
+        (0x08 | 0x13 | 0x15, _) | (0xFF, 0x2107) => PlcError::Param(msg),
+        _ => PlcError::Comm(msg),
+    }
+}
+
+/// 基本数据类型每个元素的字节数，结构体等不支持的类型返回 None
+pub fn type_size(type_code: u16) -> Option<usize> {
+    match type_code {
+        // BOOL SINT USINT BYTE
+        0xC1 | 0xC2 | 0xC6 | 0xD1 => Some(1),
+        // INT UINT WORD
+        0xC3 | 0xC7 | 0xD2 => Some(2),
+        // DINT UDINT REAL DWORD
+        0xC4 | 0xC8 | 0xCA | 0xD3 => Some(4),
+        // LINT ULINT LREAL LWORD
+        0xC5 | 0xC9 | 0xCB | 0xD4 => Some(8),
+        _ => None,
+    }
+}
+
+/// 每个元素占用的字数，单字节类型占用一个字
+pub fn element_words(size: usize) -> usize {
+    size.div_ceil(2)
+}
+
+/// 元素数据转换为字，多字类型低字在前
+pub fn bytes_to_words(size: usize, bytes: &[u8]) -> Vec<u16> {
+    match size {
+        1 => bytes.iter().map(|b| *b as u16).collect(),
+        _ => bytes
+            .chunks_exact(2)
+            .map(|word| u16::from_le_bytes([word[0], word[1]]))
+            .collect(),
+    }
+}
+
+/// 字转换为元素数据，单字节类型取低字节
+pub fn words_to_bytes(size: usize, words: &[u16]) -> Vec<u8> {
+    match size {
+        1 => words.iter().map(|word| *word as u8).collect(),
+        _ => words.iter().flat_map(|word| word.to_le_bytes()).collect(),
+    }
+}
+
+/// 未连接发送，通过背板等路由将请求转发到指定槽位的控制器
+///
+/// 成功时回复为内部请求的回复；失败时回复服务为 0xD2
+pub fn unconnected_send(request: &[u8], route: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x0A, 0x0E];
+    data.extend((request.len() as u16).to_le_bytes());
+    data.extend_from_slice(request);
+    if request.len() % 2 == 1 {
+        data.push(0);
+    }
+    data.extend([(route.len() / 2) as u8, 0]);
+    data.extend_from_slice(route);
+    cip_request(UNCONNECTED_SEND, &CONNECTION_MANAGER, &data)
+}
+
+/// 连接路径：路由 + 消息路由器
+pub fn connection_path(route: &[u8]) -> Vec<u8> {
+    [route, &MESSAGE_ROUTER].concat()
+}
+
+/// 打开连接请求
+///
+/// # Param
+/// * `t_o_id` - 目标到本机的连接ID
+/// * `serial` - 连接序列号
+/// * `path` - 连接路径
+pub fn forward_open_request(t_o_id: u32, serial: u16, path: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x0A, 0x0E];
+    // 本机到目标的连接ID由目标分配
+    data.extend(0u32.to_le_bytes());
+    data.extend(t_o_id.to_le_bytes());
+    data.extend(serial.to_le_bytes());
+    data.extend(VENDOR_ID.to_le_bytes());
+    data.extend(ORIGINATOR_SERIAL.to_le_bytes());
+    // 超时倍数 + 3个保留字节
+    data.extend([0x01, 0, 0, 0]);
+    for _ in 0..2 {
+        data.extend(RPI.to_le_bytes());
+        data.extend(CONNECTION_PARAMS.to_le_bytes());
+    }
+    // 类3 应用触发的服务器连接
+    data.extend([0xA3, (path.len() / 2) as u8]);
+    data.extend_from_slice(path);
+    cip_request(FORWARD_OPEN, &CONNECTION_MANAGER, &data)
+}
+
+/// 解析打开连接回复，返回本机到目标的连接ID
+pub fn parse_forward_open_reply(reply: &[u8]) -> Result<u32, PlcError> {
+    let reply = parse_reply(reply, FORWARD_OPEN)?;
+    reply.check()?;
+    match reply.data.get(..4) {
+        Some(id) => Ok(u32::from_le_bytes(id.try_into().unwrap())),
+        None => Err(PlcError::Comm(format!(
+            "CIP 打开连接回复解析失败\t数据={:02X?}",
+            reply.data
+        ))),
+    }
+}
+
+/// 关闭连接请求
+pub fn forward_close_request(serial: u16, path: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x0A, 0x0E];
+    data.extend(serial.to_le_bytes());
+    data.extend(VENDOR_ID.to_le_bytes());
+    data.extend(ORIGINATOR_SERIAL.to_le_bytes());
+    data.extend([(path.len() / 2) as u8, 0]);
+    data.extend_from_slice(path);
+    cip_request(FORWARD_CLOSE, &CONNECTION_MANAGER, &data)
+}
+
+/// 封装报文
+pub fn create_encap_frame(command: u16, session: u32, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(ENCAP_HEADER_LEN + data.len());
+    frame.extend(command.to_le_bytes());
+    frame.extend((data.len() as u16).to_le_bytes());
+    frame.extend(session.to_le_bytes());
+    // 状态 + 发送方上下文 + 选项
+    frame.extend([0u8; 16]);
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// 检查封装报文是否完整
+pub fn check_encap_frame(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    if buf.len() < ENCAP_HEADER_LEN {
+        return Ok(Err("数据长度不足"));
+    }
+    let len = ENCAP_HEADER_LEN + u16::from_le_bytes([buf[2], buf[3]]) as usize;
+    match buf.get(..len) {
+        Some(frame) => Ok(Ok(frame)),
+        None => Ok(Err("数据长度不足")),
+    }
+}
+
+/// 解析封装报文，返回会话句柄和数据
+pub fn parse_encap_frame(frame: &[u8], command: u16) -> Result<(u32, &[u8]), PlcError> {
+    let reply_command = u16::from_le_bytes([frame[0], frame[1]]);
+    if reply_command != command {
+        return Err(PlcError::Comm(format!(
+            "EtherNet/IP 回复命令错误\t命令=0x{:04X}\t需要=0x{:04X}",
+            reply_command, command
+        )));
+    }
+    let session = u32::from_le_bytes(frame[4..8].try_into().unwrap());
+    let status = u32::from_le_bytes(frame[8..12].try_into().unwrap());
+    let desc = match status {
+        0x0000 => return Ok((session, &frame[ENCAP_HEADER_LEN..])),
+        0x0001 => "无效的命令",
+        0x0002 => "内存不足",
+        0x0003 => "数据格式错误",
+        0x0064 => "无效的会话句柄",
+        0x0065 => "无效的长度",
+        0x0069 => "不支持的协议版本",
+        _ => "未知错误",
+    };
+    Err(PlcError::Comm(format!(
+        "EtherNet/IP 错误：{}\t状态=0x{:04X}",
+        desc, status
+    )))
+}
+
+/// 通用数据包格式(CPF)：接口句柄 + 超时 + 数据项
+pub fn create_cpf(items: &[(u16, &[u8])]) -> Vec<u8> {
+    let mut data = vec![0u8; 6];
+    data.extend((items.len() as u16).to_le_bytes());
+    for (type_id, item) in items {
+        data.extend(type_id.to_le_bytes());
+        data.extend((item.len() as u16).to_le_bytes());
+        data.extend_from_slice(item);
+    }
+    data
+}
+
+/// 从通用数据包中获取指定类型的数据项
+pub fn cpf_item(data: &[u8], type_id: u16) -> Result<&[u8], PlcError> {
+    let invalid = || PlcError::Comm(format!("EtherNet/IP 数据项解析失败\t数据={:02X?}", data));
+    let count = u16::from_le_bytes(data.get(6..8).ok_or_else(invalid)?.try_into().unwrap());
+    let mut offset = 8;
+    for _ in 0..count {
+        let header = data.get(offset..offset + 4).ok_or_else(invalid)?;
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let item = data.get(offset + 4..offset + 4 + len).ok_or_else(invalid)?;
+        if u16::from_le_bytes([header[0], header[1]]) == type_id {
+            return Ok(item);
+        }
+        offset += 4 + len;
+    }
+    Err(invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_path() {
+        let path = encode_tag_path("Program:Main.Counter[3]").unwrap();
+        let mut expect = vec![0x91, 12];
+        expect.extend(b"Program:Main");
+        expect.extend([0x91, 7]);
+        expect.extend(b"Counter\0");
+        expect.extend([0x28, 3]);
+        assert_eq!(path, expect);
+        let path = encode_tag_path("A[1,300,70000]").unwrap();
+        assert_eq!(
+            path,
+            [0x91, 1, b'A', 0, 0x28, 1, 0x29, 0, 0x2C, 0x01, 0x2A, 0, 0x70, 0x11, 0x01, 0x00]
+        );
+        for tag in ["", "A.", "A[1", "A[x]", "A[1]B", "A-B"] {
+            assert!(encode_tag_path(tag).is_err(), "{}", tag);
+        }
+    }
+
+    #[test]
+    fn test_frame() {
+        let requests = vec![read_tag_request(&[0x91, 1, b'A', 0], 1); 2];
+        let request = multiple_service_request(&requests);
+        assert_eq!(&request[..6], [0x0A, 2, 0x20, 0x02, 0x24, 0x01]);
+        assert_eq!(&request[6..12], [2, 0, 6, 0, 14, 0]);
+        assert_eq!(
+            parse_multiple_service_data(&request[6..]).unwrap(),
+            requests
+        );
+
+        let reply = [0xCC, 0, 0xFF, 1, 0x05, 0x21];
+        let r = parse_reply(&reply, READ_TAG).unwrap().check();
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+        let reply = [0xD2, 0, 0x06, 0, 0xC4, 0x00, 1, 0, 0, 0];
+        let r = parse_reply(&reply, READ_TAG_FRAGMENTED).unwrap();
+        assert_eq!((r.status, r.data), (STATUS_PARTIAL, &reply[4..]));
+        assert!(r.check().is_ok());
+
+        assert_eq!(bytes_to_words(4, &[1, 0, 2, 0]), [1, 2]);
+        assert_eq!(bytes_to_words(1, &[0xFF, 1]), [0xFF, 1]);
+        assert_eq!(words_to_bytes(1, &[0x1FF]), [0xFF]);
+
+        let frame = create_encap_frame(SEND_RR_DATA, 7, &create_cpf(&[(0, &[]), (0xB2, &[1])]));
+        assert!(check_encap_frame(&frame[..30]).unwrap().is_err());
+        let frame = check_encap_frame(&frame).unwrap().unwrap();
+        let (session, data) = parse_encap_frame(frame, SEND_RR_DATA).unwrap();
+        assert_eq!((session, cpf_item(data, 0xB2).unwrap()), (7, &[1u8][..]));
+        assert!(cpf_item(data, 0xB1).is_err());
+    }
+}
@@ -0,0 +1,689 @@
+// ! 罗克韦尔 EtherNet/IP 显式报文(CIP 标签读写)
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::cip;
+use crate::core::Channel;
+use crate::prelude::*;
+
+/// Forward Open 建立的连接
+struct CipConnection {
+    /// 本机到目标的连接ID
+    o_t_id: u32,
+    /// 连接序列号
+    serial: u16,
+    /// 报文序号
+    sequence: u16,
+}
+
+/// 会话和连接，连接时建立，克隆的客户端共用
+#[derive(Default)]
+struct EipSession {
+    /// 会话句柄
+    session: u32,
+    /// 连接，未使用 Forward Open 时为 None
+    connection: Option<CipConnection>,
+}
+
+/// 罗克韦尔 CompactLogix/ControlLogix 网口PLC EtherNet/IP 协议，端口一般为 44818
+///
+/// 使用标签名称读写，例如 `Program:Main.Counter[3]`。
+/// 默认直接发往以太网口所在的控制器，ControlLogix 通过 [`EipTcpPlc::slot`] 指定背板槽位；
+/// 可以通过 [`EipTcpPlc::forward_open`] 使用连接报文
+pub struct EipTcpPlc {
+    /// 通讯通道
+    channel: Channel,
+    /// 会话和连接
+    session: Arc<Mutex<EipSession>>,
+    /// 路由路径
+    route: Vec<u8>,
+    /// 是否使用 Forward Open 建立连接
+    forward_open: bool,
+    /// 标签的数据类型
+    tag_types: Arc<Mutex<HashMap<String, u16>>>,
+}
+
+impl Clone for EipTcpPlc {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            session: self.session.clone(),
+            route: self.route.clone(),
+            forward_open: self.forward_open,
+            tag_types: self.tag_types.clone(),
+        }
+    }
+}
+
+impl EipTcpPlc {
+    /// 设置控制器所在的背板槽位(ControlLogix)，需要在连接前设置
+    pub fn slot(mut self, slot: u8) -> Self {
+        self.route = vec![0x01, slot];
+        self
+    }
+
+    /// 设置是否使用 Forward Open 建立连接，默认不使用，需要在连接前设置
+    pub fn forward_open(mut self, enable: bool) -> Self {
+        self.forward_open = enable;
+        self
+    }
+
+    /// 发送封装报文并返回回复的数据
+    async fn exchange(&self, command: u16, data: &[u8]) -> Result<Vec<u8>, PlcError> {
+        let current = self.session.lock().unwrap().session;
+        let frame = cip::create_encap_frame(command, current, data);
+        let reply = self
+            .channel
+            .send_and_receive(&frame, cip::check_encap_frame)
+            .await?;
+        let (session, data) = cip::parse_encap_frame(&reply, command)?;
+        match command {
+            cip::REGISTER_SESSION => self.session.lock().unwrap().session = session,
+            _ if session != current => {
+                return Err(PlcError::Comm(format!(
+                    "EtherNet/IP 会话句柄错误\t句柄=0x{:08X}",
+                    session
+                )))
+            }
+            _ => {}
+        }
+        Ok(data.to_vec())
+    }
+
+    /// 发送未连接的 CIP 请求
+    async fn send_unconnected(&self, request: &[u8]) -> Result<Vec<u8>, PlcError> {
+        let data = cip::create_cpf(&[(0x0000, &[]), (0x00B2, request)]);
+        let reply = self.exchange(cip::SEND_RR_DATA, &data).await?;
+        Ok(cip::cpf_item(&reply, 0x00B2)?.to_vec())
+    }
+
+    /// 发送 CIP 请求并返回回复
+    async fn send_and_receive(&self, request: &[u8]) -> Result<Vec<u8>, PlcError> {
+        let connected = self
+            .session
+            .lock()
+            .unwrap()
+            .connection
+            .as_mut()
+            .map(|connection| {
+                connection.sequence = connection.sequence.wrapping_add(1);
+                (connection.o_t_id, connection.sequence.to_le_bytes())
+            });
+        match connected {
+            Some((o_t_id, sequence)) => {
+                let data = cip::create_cpf(&[
+                    (0x00A1, &o_t_id.to_le_bytes()),
+                    (0x00B1, &[&sequence, request].concat()),
+                ]);
+                let reply = self.exchange(cip::SEND_UNIT_DATA, &data).await?;
+                match cip::cpf_item(&reply, 0x00B1)? {
+                    [s0, s1, reply @ ..] if [*s0, *s1] == sequence => Ok(reply.to_vec()),
+                    item => Err(PlcError::Comm(format!(
+                        "EtherNet/IP 连接报文序号错误\t数据={:02X?}",
+                        item
+                    ))),
+                }
+            }
+            None if self.route.is_empty() => self.send_unconnected(request).await,
+            None => {
+                let request = cip::unconnected_send(request, &self.route);
+                self.send_unconnected(&request).await
+            }
+        }
+    }
+
+    /// 获取标签的数据类型，没有缓存时读取一个元素
+    async fn tag_type(&self, tag: &str, path: &[u8]) -> Result<u16, PlcError> {
+        if let Some(type_code) = self.tag_types.lock().unwrap().get(tag) {
+            return Ok(*type_code);
+        }
+        let reply = self
+            .send_and_receive(&cip::read_tag_request(path, 1))
+            .await?;
+        let reply = cip::parse_reply(&reply, cip::READ_TAG)?;
+        reply.check()?;
+        let type_code = reply_type(reply.data)?;
+        self.cache_type(tag, type_code);
+        Ok(type_code)
+    }
+
+    fn cache_type(&self, tag: &str, type_code: u16) {
+        self.tag_types
+            .lock()
+            .unwrap()
+            .insert(tag.to_string(), type_code);
+    }
+
+    /// 分段读取标签，返回数据类型和数据
+    async fn read_fragmented(&self, path: &[u8], count: u16) -> Result<(u16, Vec<u8>), PlcError> {
+        let mut bytes = Vec::new();
+        loop {
+            let request = cip::read_fragmented_request(path, count, bytes.len() as u32);
+            let reply = self.send_and_receive(&request).await?;
+            let reply = cip::parse_reply(&reply, cip::READ_TAG_FRAGMENTED)?;
+            reply.check()?;
+            let type_code = reply_type(reply.data)?;
+            if reply.status != cip::STATUS_PARTIAL {
+                bytes.extend_from_slice(&reply.data[2..]);
+                return Ok((type_code, bytes));
+            }
+            // 部分数据的回复没有数据时偏移不变，继续请求会无限循环
+            if reply.data.len() == 2 {
+                return Err(PlcError::Comm(format!(
+                    "CIP 分段读取回复没有数据\t偏移={}",
+                    bytes.len()
+                )));
+            }
+            bytes.extend_from_slice(&reply.data[2..]);
+        }
+    }
+
+    /// 使用多服务请求读取多个标签，每个标签读取一个元素
+    ///
+    /// 返回每个标签的数据，多字类型低字在前
+    pub async fn read_multiple(&self, tags: &[&str]) -> Result<Vec<Vec<u16>>, PlcError> {
+        let mut groups: Vec<Vec<Vec<u8>>> = vec![];
+        let mut size = 0;
+        for tag in tags {
+            let request = cip::read_tag_request(&cip::encode_tag_path(tag)?, 1);
+            size += request.len() + 2;
+            match groups.last_mut() {
+                Some(group) if size <= cip::MAX_DATA_BYTES => group.push(request),
+                _ => {
+                    size = request.len() + 2;
+                    groups.push(vec![request]);
+                }
+            }
+        }
+        let mut datas = Vec::with_capacity(tags.len());
+        for group in groups {
+            let request = cip::multiple_service_request(&group);
+            let reply = self.send_and_receive(&request).await?;
+            let reply = cip::parse_reply(&reply, cip::MULTIPLE_SERVICE)?;
+            if reply.status != cip::STATUS_EMBEDDED {
+                reply.check()?;
+            }
+            let replies = cip::parse_multiple_service_data(reply.data)?;
+            if replies.len() != group.len() {
+                return Err(PlcError::Comm(format!(
+                    "CIP 多服务回复数量错误\t数量={}\t需要={}",
+                    replies.len(),
+                    group.len()
+                )));
+            }
+            for reply in replies {
+                let tag = tags[datas.len()];
+                let reply = cip::parse_reply(reply, cip::READ_TAG)?;
+                reply.check()?;
+                let type_code = reply_type(reply.data)?;
+                let size = element_size(type_code)?;
+                let bytes = &reply.data[2..];
+                if bytes.len() != size {
+                    return Err(PlcError::Comm(format!(
+                        "CIP 读取回复的数据长度错误\t标签={}\t长度={}",
+                        tag,
+                        bytes.len()
+                    )));
+                }
+                self.cache_type(tag, type_code);
+                datas.push(cip::bytes_to_words(size, bytes));
+            }
+        }
+        Ok(datas)
+    }
+
+    /// 注册会话，设置了 Forward Open 时打开连接
+    async fn register(&self) -> PlcResult {
+        *self.session.lock().unwrap() = EipSession::default();
+        // 协议版本1
+        let data = [0x01, 0x00, 0x00, 0x00];
+        self.exchange(cip::REGISTER_SESSION, &data).await?;
+        if self.forward_open {
+            self.open_connection().await?;
+        }
+        Ok(())
+    }
+
+    /// 关闭连接并注销会话，PLC 不回复注销会话
+    async fn unregister(&self) {
+        let connection = self.session.lock().unwrap().connection.take();
+        if let Some(connection) = connection {
+            let path = cip::connection_path(&self.route);
+            let request = cip::forward_close_request(connection.serial, &path);
+            let _ = self.send_unconnected(&request).await;
+        }
+        let session = self.session.lock().unwrap().session;
+        let frame = cip::create_encap_frame(cip::UNREGISTER_SESSION, session, &[]);
+        let _ = self.channel.send(&frame).await;
+    }
+
+    /// 打开连接
+    async fn open_connection(&self) -> PlcResult {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+        let serial = nanos as u16;
+        let path = cip::connection_path(&self.route);
+        let request = cip::forward_open_request(nanos, serial, &path);
+        let reply = self.send_unconnected(&request).await?;
+        self.session.lock().unwrap().connection = Some(CipConnection {
+            o_t_id: cip::parse_forward_open_reply(&reply)?,
+            serial,
+            sequence: 0,
+        });
+        Ok(())
+    }
+}
+
+/// 回复数据开头的数据类型
+fn reply_type(data: &[u8]) -> Result<u16, PlcError> {
+    match data {
+        [t0, t1, ..] => Ok(u16::from_le_bytes([*t0, *t1])),
+        _ => Err(PlcError::Comm("CIP 读取回复缺少数据类型".into())),
+    }
+}
+
+/// 数据类型的元素字节数
+fn element_size(type_code: u16) -> Result<usize, PlcError> {
+    cip::type_size(type_code).ok_or_else(|| {
+        PlcError::Param(format!(
+            "CIP 不支持的数据类型(结构体等)\t类型=0x{:04X}",
+            type_code
+        ))
+    })
+}
+
+/// 检查数据长度是否为元素字数的整数倍，返回元素数量
+fn element_count(len: usize, words: usize) -> Result<u16, PlcError> {
+    if !len.is_multiple_of(words) {
+        return Err(PlcError::Param(format!(
+            "CIP 长度必须是每个元素字数的倍数\t长度={}\t字数={}",
+            len, words
+        )));
+    }
+    u16::try_from(len / words).map_err(|_| PlcError::Param(format!("CIP 长度过大\t长度={}", len)))
+}
+
+unsafe impl Send for EipTcpPlc {}
+
+unsafe impl Sync for EipTcpPlc {}
+
+impl IPlc for EipTcpPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        EipTcpPlc {
+            channel: Channel::new(conn, timeout),
+            session: Arc::new(Mutex::new(EipSession::default())),
+            route: vec![],
+            forward_open: false,
+            tag_types: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    async fn connect(&mut self) -> PlcResult {
+        self.channel.conn().network()?;
+        self.channel.connect().await?;
+        let r = self.register().await;
+        // 注册会话失败时关闭连接
+        if r.is_err() {
+            let _ = self.channel.close().await;
+        }
+        r
+    }
+
+    /// 断开连接，其他共用此连接的客户端不受影响；最后一个客户端断开时关闭连接并注销会话
+    async fn disconnect(&mut self) -> PlcResult {
+        if self.channel.is_connect() && !self.channel.is_shared() {
+            self.unregister().await;
+            let _ = self.channel.close().await;
+        }
+        self.channel.disconnect().await
+    }
+
+    /// 读取数据
+    ///
+    /// # Param
+    /// * `address_name` - 标签名称，例如 `Counter`、`Program:Main.Counter[3]`、`Axis[1,2].Pos`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 每个元素一个数据，非0为1
+    ///     * Word 按字读取，DINT/REAL 等多字类型低字在前，SINT 等单字节类型每个元素一个字
+    /// * `len` - 数据长度(位数或者字数)，按字读取时必须是每个元素字数的倍数
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        if len == 0 {
+            return Err(PlcError::Param("读取长度不能为0".into()));
+        }
+        let tag = address_name.into();
+        let path = cip::encode_tag_path(&tag)?;
+        let count = match data_type {
+            DataType::Bit => len,
+            DataType::Word => {
+                let size = element_size(self.tag_type(&tag, &path).await?)?;
+                element_count(len as usize, cip::element_words(size))?
+            }
+        };
+        let (type_code, bytes) = self.read_fragmented(&path, count).await?;
+        let size = element_size(type_code)?;
+        if bytes.len() != count as usize * size {
+            // 标签类型可能已经改变
+            self.tag_types.lock().unwrap().remove(&tag);
+            return Err(PlcError::Comm(format!(
+                "CIP 读取回复的数据长度错误\t标签={}\t长度={}",
+                tag,
+                bytes.len()
+            )));
+        }
+        Ok(match data_type {
+            DataType::Bit => bytes
+                .chunks_exact(size)
+                .map(|element| element.iter().any(|b| *b != 0) as u16)
+                .collect(),
+            DataType::Word => cip::bytes_to_words(size, &bytes),
+        })
+    }
+
+    /// 写入数据
+    ///
+    /// # Param
+    /// * `address_name` - 标签名称，例如 `Counter`、`Program:Main.Counter[3]`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 每个元素一个数据，0：关闭；1：打开
+    ///     * Word 按字写入，DINT/REAL 等多字类型低字在前，SINT 等单字节类型每个元素一个字
+    /// * `datas` - 需要写入的数据，超过单个请求的长度时分段写入
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        if datas.is_empty() {
+            return Err(PlcError::Param("写入数据不能为空".into()));
+        }
+        let tag = address_name.into();
+        let path = cip::encode_tag_path(&tag)?;
+        let type_code = self.tag_type(&tag, &path).await?;
+        let size = element_size(type_code)?;
+        let (count, bytes) = match data_type {
+            DataType::Bit => {
+                if datas.iter().any(|state| *state > 1) {
+                    return Err(PlcError::Param("位写入数据只能是 1 或者 0".into()));
+                }
+                let bytes = datas
+                    .iter()
+                    .flat_map(|state| {
+                        let mut element = vec![0u8; size];
+                        element[0] = *state as u8;
+                        element
+                    })
+                    .collect();
+                (element_count(datas.len(), 1)?, bytes)
+            }
+            DataType::Word => (
+                element_count(datas.len(), cip::element_words(size))?,
+                cip::words_to_bytes(size, datas),
+            ),
+        };
+        if bytes.len() <= cip::MAX_DATA_BYTES {
+            let request = cip::write_tag_request(&path, type_code, count, &bytes);
+            let reply = self.send_and_receive(&request).await?;
+            return cip::parse_reply(&reply, cip::WRITE_TAG)?.check();
+        }
+        // 分段写入，每段为整数个元素
+        let fragment = cip::MAX_DATA_BYTES / size * size;
+        for (i, chunk) in bytes.chunks(fragment).enumerate() {
+            let offset = (i * fragment) as u32;
+            let request = cip::write_fragmented_request(&path, type_code, count, offset, chunk);
+            let reply = self.send_and_receive(&request).await?;
+            cip::parse_reply(&reply, cip::WRITE_TAG_FRAGMENTED)?.check()?;
+        }
+        Ok(())
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 每次分段读取回复的最大字节数
+    const FRAGMENT: usize = 200;
+    const O_T_ID: u32 = 0x1122_3344;
+
+    /// 模拟的标签：数据类型 + 数据
+    type Tags = HashMap<String, (u16, Vec<u8>)>;
+
+    /// 解析符号路径，返回大写的标签名称和元素下标
+    fn decode_path(path: &[u8]) -> (String, usize) {
+        let mut names = vec![];
+        let mut index = 0;
+        let mut i = 0;
+        while i < path.len() {
+            match path[i] {
+                0x91 => {
+                    let len = path[i + 1] as usize;
+                    names.push(String::from_utf8(path[i + 2..i + 2 + len].to_vec()).unwrap());
+                    i += 2 + len + len % 2;
+                }
+                0x28 => {
+                    index = path[i + 1] as usize;
+                    i += 2;
+                }
+                _ => {
+                    index = u16::from_le_bytes([path[i + 2], path[i + 3]]) as usize;
+                    i += 4;
+                }
+            }
+        }
+        (names.join(".").to_uppercase(), index)
+    }
+
+    fn handle_cip(tags: &mut Tags, request: &[u8]) -> Vec<u8> {
+        let service = request[0];
+        let path = &request[2..2 + request[1] as usize * 2];
+        let data = &request[2 + path.len()..];
+        let reply = |status: u8, data: &[u8]| [&[service | 0x80, 0, status, 0], data].concat();
+        match (service, path) {
+            (0x0A, _) => {
+                let replies: Vec<Vec<u8>> = cip::parse_multiple_service_data(data)
+                    .unwrap()
+                    .into_iter()
+                    .map(|request| handle_cip(tags, request))
+                    .collect();
+                let status = match replies.iter().all(|reply| reply[2] == 0) {
+                    true => 0,
+                    false => cip::STATUS_EMBEDDED,
+                };
+                let mut r = cip::multiple_service_request(&replies)[6..].to_vec();
+                r.splice(0..0, [0x8A, 0, status, 0]);
+                r
+            }
+            (0x52, [0x20, 0x06, 0x24, 0x01]) => {
+                let len = u16::from_le_bytes([data[2], data[3]]) as usize;
+                let route = &data[4 + len + len % 2..];
+                assert_eq!(route, [1, 0, 0x01, 0x02]);
+                handle_cip(tags, &data[4..4 + len])
+            }
+            (0x54, _) => reply(0, &[&O_T_ID.to_le_bytes(), &data[6..10]].concat()),
+            (0x4E, _) => reply(0, &data[2..10]),
+            _ => {
+                let (name, index) = decode_path(path);
+                // 部分数据的回复没有数据
+                if name == "STALL" {
+                    return reply(cip::STATUS_PARTIAL, &0xC4u16.to_le_bytes());
+                }
+                let Some((type_code, bytes)) = tags.get_mut(&name) else {
+                    return reply(0x05, &[]);
+                };
+                let size = cip::type_size(*type_code).unwrap_or(bytes.len());
+                let start = index * size;
+                match service {
+                    0x4C | 0x52 => {
+                        let count = u16::from_le_bytes([data[0], data[1]]) as usize;
+                        let offset = match service {
+                            0x52 => u32::from_le_bytes(data[2..6].try_into().unwrap()) as usize,
+                            _ => 0,
+                        };
+                        let end = start + count * size;
+                        if end > bytes.len() {
+                            let mut r = reply(0xFF, &[0x05, 0x21]);
+                            r[3] = 1;
+                            return r;
+                        }
+                        let from = start + offset;
+                        let to = end.min(from + FRAGMENT);
+                        let status = if to < end { cip::STATUS_PARTIAL } else { 0 };
+                        let data = [&type_code.to_le_bytes(), &bytes[from..to]].concat();
+                        reply(status, &data)
+                    }
+                    _ => {
+                        if u16::from_le_bytes([data[0], data[1]]) != *type_code {
+                            let mut r = reply(0xFF, &[0x07, 0x21]);
+                            r[3] = 1;
+                            return r;
+                        }
+                        let (offset, values) = match service {
+                            0x53 => (
+                                u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize,
+                                &data[8..],
+                            ),
+                            _ => (0, &data[4..]),
+                        };
+                        let from = start + offset;
+                        bytes[from..from + values.len()].copy_from_slice(values);
+                        reply(0, &[])
+                    }
+                }
+            }
+        }
+    }
+
+    async fn mock_plc(listener: TcpListener) {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut tags = Tags::new();
+        let counter = (0..600u32).flat_map(|i| i.to_le_bytes()).collect();
+        tags.insert("PROGRAM:MAIN.COUNTER".into(), (0xC4, counter));
+        tags.insert("FLAG".into(), (0xC1, vec![0]));
+        tags.insert("SPEED".into(), (0xCA, vec![0; 4]));
+        tags.insert("UDT".into(), (0x02A0, vec![0; 4]));
+        loop {
+            let mut header = [0u8; 24];
+            if socket.read_exact(&mut header).await.is_err() {
+                return;
+            }
+            let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+            let mut data = vec![0u8; len];
+            socket.read_exact(&mut data).await.unwrap();
+            let command = u16::from_le_bytes([header[0], header[1]]);
+            let reply = match command {
+                0x0065 => data,
+                0x006F => {
+                    let request = cip::cpf_item(&data, 0xB2).unwrap();
+                    let reply = handle_cip(&mut tags, request);
+                    cip::create_cpf(&[(0, &[]), (0xB2, &reply)])
+                }
+                0x0070 => {
+                    assert_eq!(cip::cpf_item(&data, 0xA1).unwrap(), O_T_ID.to_le_bytes());
+                    let item = cip::cpf_item(&data, 0xB1).unwrap();
+                    let reply = [&item[..2], &handle_cip(&mut tags, &item[2..])].concat();
+                    cip::create_cpf(&[(0xA1, &[0; 4]), (0xB1, &reply)])
+                }
+                _ => continue,
+            };
+            let frame = cip::create_encap_frame(command, 0x1234_5678, &reply);
+            socket.write_all(&frame).await.unwrap();
+        }
+    }
+
+    async fn check_read_write(plc: &EipTcpPlc) {
+        let r = plc.read("Program:Main.Counter[3]", DataType::Word, 4).await;
+        assert_eq!(r.unwrap(), [3, 0, 4, 0]);
+        // 大数组分段读取
+        let r = plc
+            .read("Program:Main.Counter[0]", DataType::Word, 1200)
+            .await;
+        assert_eq!(r.unwrap()[1198], 599);
+        let r = plc
+            .read("Program:Main.Counter[599]", DataType::Word, 4)
+            .await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+        let r = plc.read("Program:Main.Counter", DataType::Word, 3).await;
+        assert!(matches!(r, Err(PlcError::Param(_))));
+
+        // 大数组分段写入
+        let datas: Vec<u16> = (0..300).collect();
+        plc.write("Program:Main.Counter[10]", DataType::Word, &datas)
+            .await
+            .unwrap();
+        let r = plc
+            .read("Program:Main.Counter[10]", DataType::Word, 300)
+            .await;
+        assert_eq!(r.unwrap(), datas);
+
+        plc.write("Speed", DataType::Word, &[0x0000, 0x3F80])
+            .await
+            .unwrap();
+        plc.write("Flag", DataType::Bit, &[1]).await.unwrap();
+        assert_eq!(plc.read("Flag", DataType::Bit, 1).await.unwrap(), [1]);
+        let r = plc
+            .read_multiple(&["Flag", "Speed", "Program:Main.Counter[2]"])
+            .await
+            .unwrap();
+        assert_eq!(r, [vec![1], vec![0, 0x3F80], vec![2, 0]]);
+
+        let r = plc.read("Missing", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+        let r = plc.read_multiple(&["Flag", "Missing"]).await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+        let r = plc.write("Udt", DataType::Word, &[1, 2]).await;
+        assert!(matches!(r, Err(PlcError::Param(_))));
+        let r = plc.read("Stall", DataType::Bit, 1).await;
+        assert!(matches!(r, Err(PlcError::Comm(_))));
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(mock_plc(listener));
+        let mut plc = EipTcpPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        )
+        .slot(2);
+        plc.connect().await.unwrap();
+        check_read_write(&plc).await;
+        plc.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_forward_open() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(mock_plc(listener));
+        let mut plc = EipTcpPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        )
+        .forward_open(true);
+        plc.connect().await.unwrap();
+        check_read_write(&plc).await;
+        // 断开只影响当前客户端，克隆的客户端继续使用会话和连接
+        let mut other = plc.clone();
+        plc.disconnect().await.unwrap();
+        assert!(!plc.is_connect());
+        assert!(other.is_connect());
+        assert_eq!(other.read("Flag", DataType::Bit, 1).await.unwrap(), [1]);
+        other.disconnect().await.unwrap();
+        assert!(!other.is_connect());
+    }
+}
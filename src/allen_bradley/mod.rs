@@ -0,0 +1,13 @@
+// ! 罗克韦尔(AB) PLC
+
+mod cip;
+mod eip_tcp;
+
+pub use self::eip_tcp::EipTcpPlc;
+use crate::{core::PlcConnector, IPlc};
+use std::time::Duration;
+
+/// 创建一个 罗克韦尔 网口PLC (EtherNet/IP 协议，端口一般为44818)
+pub fn new_eip_tcp_plc(conn: PlcConnector, timeout: Duration) -> EipTcpPlc {
+    EipTcpPlc::new(conn, timeout)
+}
@@ -0,0 +1,417 @@
+// ! 倍福 ADS/AMS 协议
+//
+// * AMS/TCP 头：保留(2) + 长度(4)
+// * AMS 头：目标 Net ID(6) + 目标端口(2) + 源 Net ID(6) + 源端口(2) + 命令(2) + 状态标志(2)
+//   + 数据长度(4) + 错误码(4) + 调用编号(4)，小端
+// * 请求的状态标志为 0x0004，回复为 0x0005；设备通知由 PLC 主动发送，不需要回复
+
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::prelude::*;
+
+/// AMS/TCP 头长度
+pub const AMS_TCP_HEADER_LEN: usize = 6;
+/// AMS 头长度
+pub const AMS_HEADER_LEN: usize = 32;
+/// 读取
+pub const ADS_READ: u16 = 0x0002;
+/// 写入
+pub const ADS_WRITE: u16 = 0x0003;
+/// 添加设备通知
+pub const ADS_ADD_NOTIFICATION: u16 = 0x0006;
+/// 删除设备通知
+pub const ADS_DELETE_NOTIFICATION: u16 = 0x0007;
+/// 设备通知
+pub const ADS_NOTIFICATION: u16 = 0x0008;
+/// 读写
+pub const ADS_READ_WRITE: u16 = 0x0009;
+
+/// 通过变量名称获取句柄
+pub const SYM_HANDLE_BY_NAME: u32 = 0xF003;
+/// 通过句柄读写变量
+pub const SYM_VALUE_BY_HANDLE: u32 = 0xF005;
+/// 释放句柄
+pub const SYM_RELEASE_HANDLE: u32 = 0xF006;
+
+/// 状态标志：ADS 命令
+const STATE_COMMAND: u16 = 0x0004;
+/// 状态标志：回复
+pub const STATE_RESPONSE: u16 = 0x0001;
+/// 单个报文的最大长度
+const MAX_FRAME_LEN: usize = 0x10_0000;
+
+/// AMS Net ID，例如 `5.12.82.130.1.1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmsNetId(pub [u8; 6]);
+
+impl AmsNetId {
+    /// 使用 IP 地址 + `.1.1` 作为 Net ID，这也是 TwinCAT 的默认设置
+    pub fn from_ip(ip: IpAddr) -> Self {
+        let [a, b, c, d] = match ip {
+            IpAddr::V4(ip) => ip.octets(),
+            IpAddr::V6(ip) => ip.octets()[12..].try_into().unwrap(),
+        };
+        AmsNetId([a, b, c, d, 1, 1])
+    }
+}
+
+impl FromStr for AmsNetId {
+    type Err = PlcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || PlcError::Param(format!("ADS 无效的 AMS Net ID\tNetId={}", s));
+        let parts: Vec<u8> = s
+            .trim()
+            .split('.')
+            .map(|part| part.parse::<u8>().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        Ok(AmsNetId(parts.try_into().map_err(|_| invalid())?))
+    }
+}
+
+impl Display for AmsNetId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{}.{}.{}.{}.{}.{}", a, b, c, d, e, g)
+    }
+}
+
+/// AMS 地址：Net ID + 端口
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmsAddr {
+    pub net_id: AmsNetId,
+    pub port: u16,
+}
+
+/// ADS 变量地址
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdsAddress {
+    /// 索引组和索引偏移，例如 `0x4020:100`、`16416:100`
+    Index { group: u32, offset: u32 },
+    /// 变量名称，例如 `MAIN.counter`、`GVL.values[3]`
+    Symbol(String),
+}
+
+impl AdsAddress {
+    /// 解析变量地址，包含 `:` 时为 `索引组:索引偏移`，否则为变量名称
+    pub fn new(address_name: impl Into<String>) -> Result<Self, PlcError> {
+        let address_name = address_name.into();
+        let name = address_name.trim();
+        let invalid = || PlcError::Addr(format!("ADS 无效的变量地址\t地址={}", name));
+        if let Some((group, offset)) = name.split_once(':') {
+            return Ok(AdsAddress::Index {
+                group: parse_number(group).ok_or_else(invalid)?,
+                offset: parse_number(offset).ok_or_else(invalid)?,
+            });
+        }
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+        Ok(AdsAddress::Symbol(name.to_string()))
+    }
+}
+
+/// 解析十进制或者 `0x` 开头的十六进制数字
+fn parse_number(s: &str) -> Option<u32> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// AMS 头
+#[derive(Debug)]
+pub struct AmsHeader {
+    pub command: u16,
+    pub state_flags: u16,
+    pub error: u32,
+    pub invoke_id: u32,
+}
+
+/// AMS/TCP 报文
+pub fn create_ams_frame(
+    target: &AmsAddr,
+    source: &AmsAddr,
+    command: u16,
+    invoke_id: u32,
+    data: &[u8],
+) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(AMS_TCP_HEADER_LEN + AMS_HEADER_LEN + data.len());
+    frame.extend([0, 0]);
+    frame.extend(((AMS_HEADER_LEN + data.len()) as u32).to_le_bytes());
+    frame.extend(target.net_id.0);
+    frame.extend(target.port.to_le_bytes());
+    frame.extend(source.net_id.0);
+    frame.extend(source.port.to_le_bytes());
+    frame.extend(command.to_le_bytes());
+    frame.extend(STATE_COMMAND.to_le_bytes());
+    frame.extend((data.len() as u32).to_le_bytes());
+    frame.extend(0u32.to_le_bytes());
+    frame.extend(invoke_id.to_le_bytes());
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// 检查 AMS/TCP 报文是否完整
+pub fn check_ams_frame(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    if buf.len() < AMS_TCP_HEADER_LEN {
+        return Ok(Err("数据长度不足"));
+    }
+    let len = AMS_TCP_HEADER_LEN + u32::from_le_bytes(buf[2..6].try_into().unwrap()) as usize;
+    if !(AMS_TCP_HEADER_LEN + AMS_HEADER_LEN..=MAX_FRAME_LEN).contains(&len) {
+        return Err(PlcError::Comm(format!(
+            "ADS 报文长度错误\t数据={:02X?}",
+            &buf[..AMS_TCP_HEADER_LEN]
+        )));
+    }
+    match buf.get(..len) {
+        Some(frame) => Ok(Ok(frame)),
+        None => Ok(Err("数据长度不足")),
+    }
+}
+
+/// 解析 AMS/TCP 报文，返回 AMS 头和数据
+pub fn parse_ams_frame(frame: &[u8]) -> Result<(AmsHeader, &[u8]), PlcError> {
+    let ams = &frame[AMS_TCP_HEADER_LEN..];
+    let u16_at = |i: usize| u16::from_le_bytes([ams[i], ams[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes(ams[i..i + 4].try_into().unwrap());
+    let header = AmsHeader {
+        command: u16_at(16),
+        state_flags: u16_at(18),
+        error: u32_at(24),
+        invoke_id: u32_at(28),
+    };
+    match ams.get(AMS_HEADER_LEN..AMS_HEADER_LEN + u32_at(20) as usize) {
+        Some(data) => Ok((header, data)),
+        None => Err(PlcError::Comm(format!(
+            "ADS 数据长度错误\t数据={:02X?}",
+            frame
+        ))),
+    }
+}
+
+/// 读取请求
+pub fn read_request(group: u32, offset: u32, len: u32) -> Vec<u8> {
+    [group, offset, len]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+/// 写入请求
+pub fn write_request(group: u32, offset: u32, data: &[u8]) -> Vec<u8> {
+    let mut request = read_request(group, offset, data.len() as u32);
+    request.extend_from_slice(data);
+    request
+}
+
+/// 读写请求
+pub fn read_write_request(group: u32, offset: u32, read_len: u32, data: &[u8]) -> Vec<u8> {
+    let mut request = read_request(group, offset, read_len);
+    request.extend((data.len() as u32).to_le_bytes());
+    request.extend_from_slice(data);
+    request
+}
+
+/// 添加设备通知请求
+///
+/// # Param
+/// * `mode` - 传输方式：3 周期发送；4 数据变化时发送
+/// * `max_delay` - 最大延时(100ns)
+/// * `cycle` - 检查周期(100ns)
+pub fn add_notification_request(
+    group: u32,
+    offset: u32,
+    len: u32,
+    mode: u32,
+    max_delay: u32,
+    cycle: u32,
+) -> Vec<u8> {
+    let mut request = read_request(group, offset, len);
+    for value in [mode, max_delay, cycle] {
+        request.extend(value.to_le_bytes());
+    }
+    // 保留
+    request.extend([0u8; 16]);
+    request
+}
+
+/// 检查回复开头的结果码，返回其后的数据
+pub fn parse_result(data: &[u8]) -> Result<&[u8], PlcError> {
+    match data {
+        [r0, r1, r2, r3, rest @ ..] => match u32::from_le_bytes([*r0, *r1, *r2, *r3]) {
+            0 => Ok(rest),
+            code => Err(ads_error(code)),
+        },
+        _ => Err(PlcError::Comm(format!(
+            "ADS 回复解析失败\t数据={:02X?}",
+            data
+        ))),
+    }
+}
+
+/// 解析读取和读写回复：结果码 + 长度 + 数据
+pub fn parse_read_reply(data: &[u8]) -> Result<&[u8], PlcError> {
+    let rest = parse_result(data)?;
+    let invalid = || PlcError::Comm(format!("ADS 读取回复解析失败\t数据={:02X?}", data));
+    let len = u32::from_le_bytes(rest.get(..4).ok_or_else(invalid)?.try_into().unwrap());
+    rest.get(4..4 + len as usize).ok_or_else(invalid)
+}
+
+/// 设备通知中的数据：(通知句柄, 时间戳, 数据)
+///
+/// 时间戳为 Windows FILETIME(1601-01-01 起的 100ns)
+pub type NotificationSample<'a> = (u32, u64, &'a [u8]);
+
+/// 解析设备通知
+pub fn parse_notification(data: &[u8]) -> Result<Vec<NotificationSample<'_>>, PlcError> {
+    let invalid = || PlcError::Comm(format!("ADS 设备通知解析失败\t数据={:02X?}", data));
+    let u32_at = |i: usize| -> Result<u32, PlcError> {
+        let bytes = data.get(i..i + 4).ok_or_else(invalid)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let mut samples = vec![];
+    let stamps = u32_at(4)?;
+    let mut offset = 8;
+    for _ in 0..stamps {
+        let timestamp = data.get(offset..offset + 8).ok_or_else(invalid)?;
+        let timestamp = u64::from_le_bytes(timestamp.try_into().unwrap());
+        let count = u32_at(offset + 8)?;
+        offset += 12;
+        for _ in 0..count {
+            let handle = u32_at(offset)?;
+            let size = u32_at(offset + 4)? as usize;
+            let sample = data
+                .get(offset + 8..offset + 8 + size)
+                .ok_or_else(invalid)?;
+            samples.push((handle, timestamp, sample));
+            offset += 8 + size;
+        }
+    }
+    Ok(samples)
+}
+
+/// 数据转换为读取结果：按位时每个字节一个数据，非0为1；按字时小端
+pub fn bytes_to_values(data_type: &DataType, bytes: &[u8]) -> Vec<u16> {
+    match data_type {
+        DataType::Bit => bytes.iter().map(|b| (*b != 0) as u16).collect(),
+        DataType::Word => bytes
+            .chunks(2)
+            .map(|word| u16::from_le_bytes([word[0], *word.get(1).unwrap_or(&0)]))
+            .collect(),
+    }
+}
+
+/// ADS 错误码转换为错误
+pub fn ads_error(code: u32) -> PlcError {
+    let desc = match code {
+        0x0006 => "目标端口不存在",
+        0x0007 => "目标设备不存在",
+        0x0701 => "不支持的服务",
+        0x0702 => "无效的索引组",
+        0x0703 => "无效的索引偏移",
+        0x0704 => "不允许读写",
+        0x0705 => "数据长度错误",
+        0x0706 => "无效的数据",
+        0x0710 => "变量不存在",
+        0x0711 => "变量版本无效(在线修改后句柄失效)",
+        0x0745 => "超时",
+        0x0751 => "无效的通知句柄",
+        _ => "未知错误",
+    };
+    let msg = format!("ADS 错误：{}\t错误码=0x{:04X}", desc, code);
+    match code {
+        0x0702 | 0x0703 | 0x0710 => PlcError::Addr(msg),
+        0x0705 | 0x0706 | 0x0751 => PlcError::Param(msg),
+        0x0745 => PlcError::Timeout,
+        _ => PlcError::Comm(msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let net_id: AmsNetId = "5.12.82.130.1.1".parse().unwrap();
+        assert_eq!(net_id.0, [5, 12, 82, 130, 1, 1]);
+        assert_eq!(net_id.to_string(), "5.12.82.130.1.1");
+        assert!("5.12.82.130.1".parse::<AmsNetId>().is_err());
+        assert!("5.12.82.300.1.1".parse::<AmsNetId>().is_err());
+        let ip = "192.168.1.10".parse().unwrap();
+        assert_eq!(AmsNetId::from_ip(ip).0, [192, 168, 1, 10, 1, 1]);
+
+        let cases = [
+            (
+                "0x4020:100",
+                AdsAddress::Index {
+                    group: 0x4020,
+                    offset: 100,
+                },
+            ),
+            (
+                "16416:0x10",
+                AdsAddress::Index {
+                    group: 0x4020,
+                    offset: 16,
+                },
+            ),
+            ("MAIN.counter", AdsAddress::Symbol("MAIN.counter".into())),
+            (
+                " GVL.values[3] ",
+                AdsAddress::Symbol("GVL.values[3]".into()),
+            ),
+        ];
+        for (name, address) in cases {
+            assert_eq!(AdsAddress::new(name).unwrap(), address);
+        }
+        for name in ["", "0x4020:", "MAIN:x", "MAIN. x"] {
+            assert!(AdsAddress::new(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_frame() {
+        let target = AmsAddr {
+            net_id: AmsNetId([1, 2, 3, 4, 1, 1]),
+            port: 851,
+        };
+        let source = AmsAddr {
+            net_id: AmsNetId([5, 6, 7, 8, 1, 1]),
+            port: 32905,
+        };
+        let frame = create_ams_frame(&target, &source, ADS_READ, 9, &read_request(0x4020, 0, 2));
+        assert_eq!(&frame[..6], [0, 0, 44, 0, 0, 0]);
+        assert_eq!(&frame[6..14], [1, 2, 3, 4, 1, 1, 0x53, 0x03]);
+        assert!(check_ams_frame(&frame[..40]).unwrap().is_err());
+        let (header, data) = parse_ams_frame(check_ams_frame(&frame).unwrap().unwrap()).unwrap();
+        assert_eq!((header.command, header.invoke_id), (ADS_READ, 9));
+        assert_eq!(data, [0x20, 0x40, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0]);
+
+        let reply = [0, 0, 0, 0, 2, 0, 0, 0, 0x34, 0x12];
+        assert_eq!(parse_read_reply(&reply).unwrap(), [0x34, 0x12]);
+        assert!(parse_read_reply(&reply[..9]).is_err());
+        let reply = [0x10, 0x07, 0, 0];
+        assert!(matches!(parse_result(&reply), Err(PlcError::Addr(_))));
+
+        let mut notification = vec![0u8; 4];
+        notification.extend(1u32.to_le_bytes());
+        notification.extend(7u64.to_le_bytes());
+        notification.extend(2u32.to_le_bytes());
+        for (handle, data) in [(1u32, &[1u8, 0][..]), (2, &[5])] {
+            notification.extend(handle.to_le_bytes());
+            notification.extend((data.len() as u32).to_le_bytes());
+            notification.extend(data);
+        }
+        let samples = parse_notification(&notification).unwrap();
+        assert_eq!(samples, [(1, 7, &[1u8, 0][..]), (2, 7, &[5][..])]);
+        assert!(parse_notification(&notification[..notification.len() - 1]).is_err());
+
+        assert_eq!(bytes_to_values(&DataType::Word, &[1, 0, 2]), [1, 2]);
+        assert_eq!(bytes_to_values(&DataType::Bit, &[0, 3]), [0, 1]);
+    }
+}
@@ -0,0 +1,791 @@
+// ! 倍福 ADS 协议(TCP)
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{event, Level};
+
+use super::ads::{self, AdsAddress, AmsAddr, AmsNetId};
+use crate::core::{open_transport, Transport};
+use crate::prelude::*;
+
+/// 设备通知的传输方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdsTransMode {
+    /// 按周期发送
+    Cyclic,
+    /// 数据变化时发送
+    OnChange,
+}
+
+/// 设备通知
+#[derive(Debug, Clone)]
+pub struct AdsNotification {
+    /// 通知句柄
+    pub handle: u32,
+    /// 时间戳，Windows FILETIME(1601-01-01 起的 100ns)
+    pub timestamp: u64,
+    /// 数据，格式与 [`IPlc::read`] 相同
+    pub datas: Vec<u16>,
+}
+
+/// 通知句柄对应的数据类型和发送通道
+type NotifySender = (DataType, mpsc::UnboundedSender<AdsNotification>);
+
+/// 等待回复的请求
+struct Pending {
+    reply: oneshot::Sender<Result<Vec<u8>, PlcError>>,
+    /// 添加设备通知时，收到回复后立即登记通知句柄，避免丢失第一个通知
+    notify: Option<NotifySender>,
+}
+
+/// 客户端连接，回复和设备通知由接收任务分发
+struct AdsClient {
+    writer: Mutex<WriteHalf<Box<dyn Transport>>>,
+    target: AmsAddr,
+    source: AmsAddr,
+    pending: Arc<std::sync::Mutex<HashMap<u32, Pending>>>,
+    notifications: Arc<std::sync::Mutex<HashMap<u32, NotifySender>>>,
+    /// 接收任务退出(连接断开)时清除
+    connected: Arc<AtomicBool>,
+    receiver: JoinHandle<()>,
+}
+
+impl Drop for AdsClient {
+    fn drop(&mut self) {
+        self.receiver.abort();
+    }
+}
+
+/// 倍福 TwinCAT 网口PLC ADS 协议，端口一般为 48898
+///
+/// 目标 Net ID 默认为 PLC IP 地址 + `.1.1`，端口默认为 851(TwinCAT 3 第一个 PLC 运行时)；
+/// 源 Net ID 默认为本机 IP 地址 + `.1.1`，需要在 PLC 的路由表中添加。
+/// 按变量名称读写时会缓存变量句柄
+pub struct AdsTcpPlc {
+    /// 连接参数
+    conn: PlcConnector,
+    /// 客户端连接
+    client: Option<Arc<AdsClient>>,
+    /// 超时时间
+    timeout: Duration,
+    /// 指定的目标 Net ID
+    target_net_id: Option<AmsNetId>,
+    /// 目标端口
+    target_port: u16,
+    /// 指定的源 Net ID
+    source_net_id: Option<AmsNetId>,
+    /// 源端口
+    source_port: u16,
+    /// 调用编号
+    invoke_id: Arc<AtomicU32>,
+    /// 变量名称对应的句柄
+    handles: Arc<std::sync::Mutex<HashMap<String, u32>>>,
+}
+
+impl Clone for AdsTcpPlc {
+    fn clone(&self) -> Self {
+        Self {
+            conn: self.conn.clone(),
+            client: self.client.clone(),
+            timeout: self.timeout,
+            target_net_id: self.target_net_id,
+            target_port: self.target_port,
+            source_net_id: self.source_net_id,
+            source_port: self.source_port,
+            invoke_id: self.invoke_id.clone(),
+            handles: self.handles.clone(),
+        }
+    }
+}
+
+impl AdsTcpPlc {
+    /// 设置目标 AMS 地址，需要在连接前设置
+    ///
+    /// 通过路由器(例如 EtherCAT 耦合器后的设备)访问时 Net ID 与 IP 地址不一致
+    pub fn target(mut self, net_id: AmsNetId, port: u16) -> Self {
+        self.target_net_id = Some(net_id);
+        self.target_port = port;
+        self
+    }
+
+    /// 设置源 AMS 地址，需要在连接前设置
+    pub fn source(mut self, net_id: AmsNetId, port: u16) -> Self {
+        self.source_net_id = Some(net_id);
+        self.source_port = port;
+        self
+    }
+
+    /// 发送 ADS 命令并返回回复的数据
+    async fn send_and_receive(
+        &self,
+        command: u16,
+        data: &[u8],
+        notify: Option<NotifySender>,
+    ) -> Result<Vec<u8>, PlcError> {
+        // 获取发送客户端
+        let Some(client) = self.client.as_ref() else {
+            return Err(PlcError::NotConnect);
+        };
+        if !client.connected.load(Ordering::Acquire) {
+            return Err(PlcError::NotConnect);
+        }
+        let invoke_id = self.invoke_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        client
+            .pending
+            .lock()
+            .unwrap()
+            .insert(invoke_id, Pending { reply: tx, notify });
+        let frame = ads::create_ams_frame(&client.target, &client.source, command, invoke_id, data);
+        let r = timeout(self.timeout, async {
+            client.writer.lock().await.write_all(&frame).await
+        })
+        .await;
+        let r = match r {
+            Ok(Ok(())) => timeout(self.timeout, rx).await,
+            Ok(Err(err)) => {
+                client.pending.lock().unwrap().remove(&invoke_id);
+                return Err(PlcError::Io(err));
+            }
+            Err(elapsed) => Err(elapsed),
+        };
+        match r {
+            Ok(Ok(reply)) => reply,
+            // 接收任务已经退出
+            Ok(Err(_)) => Err(PlcError::NotConnect),
+            Err(_) => {
+                client.pending.lock().unwrap().remove(&invoke_id);
+                Err(PlcError::Timeout)
+            }
+        }
+    }
+
+    /// 按索引组和索引偏移读取
+    async fn read_index(&self, group: u32, offset: u32, len: u32) -> Result<Vec<u8>, PlcError> {
+        let request = ads::read_request(group, offset, len);
+        let reply = self.send_and_receive(ads::ADS_READ, &request, None).await?;
+        let data = ads::parse_read_reply(&reply)?;
+        if data.len() != len as usize {
+            return Err(PlcError::Comm(format!(
+                "ADS 读取回复的数据长度错误\t长度={}\t需要={}",
+                data.len(),
+                len
+            )));
+        }
+        Ok(data.to_vec())
+    }
+
+    /// 按索引组和索引偏移写入
+    async fn write_index(&self, group: u32, offset: u32, data: &[u8]) -> PlcResult {
+        let request = ads::write_request(group, offset, data);
+        let reply = self
+            .send_and_receive(ads::ADS_WRITE, &request, None)
+            .await?;
+        ads::parse_result(&reply)?;
+        Ok(())
+    }
+
+    /// 获取变量句柄，返回句柄和是否来自缓存
+    async fn symbol_handle(&self, name: &str) -> Result<(u32, bool), PlcError> {
+        if let Some(handle) = self.handles.lock().unwrap().get(name) {
+            return Ok((*handle, true));
+        }
+        let request = ads::read_write_request(ads::SYM_HANDLE_BY_NAME, 0, 4, name.as_bytes());
+        let reply = self
+            .send_and_receive(ads::ADS_READ_WRITE, &request, None)
+            .await?;
+        let handle = match ads::parse_read_reply(&reply)? {
+            [h0, h1, h2, h3] => u32::from_le_bytes([*h0, *h1, *h2, *h3]),
+            data => {
+                return Err(PlcError::Comm(format!(
+                    "ADS 变量句柄解析失败\t数据={:02X?}",
+                    data
+                )))
+            }
+        };
+        self.handles
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), handle);
+        Ok((handle, false))
+    }
+
+    /// 使用地址对应的索引组和索引偏移执行操作
+    ///
+    /// 缓存的变量句柄失效时(例如 PLC 在线修改后)重新获取句柄再执行一次
+    async fn with_index<T, F, Fut>(&self, address: &AdsAddress, op: F) -> Result<T, PlcError>
+    where
+        F: Fn(u32, u32) -> Fut,
+        Fut: Future<Output = Result<T, PlcError>>,
+    {
+        let name = match address {
+            AdsAddress::Index { group, offset } => return op(*group, *offset).await,
+            AdsAddress::Symbol(name) => name,
+        };
+        let (handle, cached) = self.symbol_handle(name).await?;
+        match op(ads::SYM_VALUE_BY_HANDLE, handle).await {
+            Err(PlcError::Addr(_) | PlcError::Comm(_)) if cached => {
+                event!(Level::WARN, "变量句柄失效，重新获取\t变量={}", name);
+                self.handles.lock().unwrap().remove(name);
+                let (handle, _) = self.symbol_handle(name).await?;
+                op(ads::SYM_VALUE_BY_HANDLE, handle).await
+            }
+            r => r,
+        }
+    }
+
+    /// 添加设备通知，返回通知句柄和接收通道
+    ///
+    /// # Param
+    /// * `address_name` - 变量地址，与 [`IPlc::read`] 相同
+    /// * `data_type` - 数据类型，与 [`IPlc::read`] 相同
+    /// * `len` - 数据长度，与 [`IPlc::read`] 相同
+    /// * `mode` - 传输方式
+    /// * `cycle` - 检查(发送)周期
+    pub async fn add_notification(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+        mode: AdsTransMode,
+        cycle: Duration,
+    ) -> Result<(u32, mpsc::UnboundedReceiver<AdsNotification>), PlcError> {
+        let address = AdsAddress::new(address_name)?;
+        let length = byte_length(&data_type, len)?;
+        let mode = match mode {
+            AdsTransMode::Cyclic => 3,
+            AdsTransMode::OnChange => 4,
+        };
+        // 单位为 100ns
+        let cycle = u32::try_from(cycle.as_nanos() / 100).unwrap_or(u32::MAX);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let handle = self
+            .with_index(&address, |group, offset| {
+                let request = ads::add_notification_request(group, offset, length, mode, 0, cycle);
+                let notify = Some((data_type.clone(), tx.clone()));
+                async move {
+                    let reply = self
+                        .send_and_receive(ads::ADS_ADD_NOTIFICATION, &request, notify)
+                        .await?;
+                    notification_handle(&reply)
+                }
+            })
+            .await?;
+        Ok((handle, rx))
+    }
+
+    /// 删除设备通知，对应的接收通道随之关闭
+    pub async fn delete_notification(&self, handle: u32) -> PlcResult {
+        if let Some(client) = self.client.as_ref() {
+            client.notifications.lock().unwrap().remove(&handle);
+        }
+        let reply = self
+            .send_and_receive(ads::ADS_DELETE_NOTIFICATION, &handle.to_le_bytes(), None)
+            .await?;
+        ads::parse_result(&reply)?;
+        Ok(())
+    }
+}
+
+/// 添加设备通知回复中的通知句柄
+fn notification_handle(reply: &[u8]) -> Result<u32, PlcError> {
+    match ads::parse_result(reply)? {
+        [h0, h1, h2, h3, ..] => Ok(u32::from_le_bytes([*h0, *h1, *h2, *h3])),
+        data => Err(PlcError::Comm(format!(
+            "ADS 通知句柄解析失败\t数据={:02X?}",
+            data
+        ))),
+    }
+}
+
+/// 读写的字节数：按位时每个位一个字节(BOOL)，按字时每个字两个字节
+fn byte_length(data_type: &DataType, len: u16) -> Result<u32, PlcError> {
+    match (data_type, len) {
+        (_, 0) => Err(PlcError::Param("读取长度不能为0".into())),
+        (DataType::Bit, len) => Ok(len as u32),
+        (DataType::Word, len) => Ok(len as u32 * 2),
+    }
+}
+
+/// 接收任务：分发回复和设备通知，连接断开时结束
+async fn receive_loop(
+    mut reader: ReadHalf<Box<dyn Transport>>,
+    pending: Arc<std::sync::Mutex<HashMap<u32, Pending>>>,
+    notifications: Arc<std::sync::Mutex<HashMap<u32, NotifySender>>>,
+    connected: Arc<AtomicBool>,
+) {
+    let mut buf = Vec::with_capacity(4096);
+    let mut chunk = [0u8; 4096];
+    'read: loop {
+        loop {
+            let len = match ads::check_ams_frame(&buf) {
+                Ok(Ok(frame)) => frame.len(),
+                Ok(Err(_)) => break,
+                Err(err) => {
+                    event!(Level::ERROR, "\t{}", err);
+                    break 'read;
+                }
+            };
+            dispatch(&buf[..len], &pending, &notifications);
+            buf.drain(..len);
+        }
+        match reader.read(&mut chunk).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+    // 连接断开，等待中的请求返回错误
+    connected.store(false, Ordering::Release);
+    pending.lock().unwrap().clear();
+    notifications.lock().unwrap().clear();
+}
+
+/// 分发一个 AMS 报文
+fn dispatch(
+    frame: &[u8],
+    pending: &std::sync::Mutex<HashMap<u32, Pending>>,
+    notifications: &std::sync::Mutex<HashMap<u32, NotifySender>>,
+) {
+    let (header, data) = match ads::parse_ams_frame(frame) {
+        Ok(r) => r,
+        Err(err) => {
+            event!(Level::WARN, "\t{}", err);
+            return;
+        }
+    };
+    if header.command == ads::ADS_NOTIFICATION && header.state_flags & ads::STATE_RESPONSE == 0 {
+        match ads::parse_notification(data) {
+            Ok(samples) => {
+                let notifications = notifications.lock().unwrap();
+                for (handle, timestamp, bytes) in samples {
+                    if let Some((data_type, tx)) = notifications.get(&handle) {
+                        let datas = ads::bytes_to_values(data_type, bytes);
+                        let _ = tx.send(AdsNotification {
+                            handle,
+                            timestamp,
+                            datas,
+                        });
+                    }
+                }
+            }
+            Err(err) => event!(Level::WARN, "\t{}", err),
+        }
+        return;
+    }
+    let Some(request) = pending.lock().unwrap().remove(&header.invoke_id) else {
+        event!(
+            Level::WARN,
+            "丢弃未知请求的回复\t调用编号={}",
+            header.invoke_id
+        );
+        return;
+    };
+    let reply = match header.error {
+        0 => Ok(data.to_vec()),
+        code => Err(ads::ads_error(code)),
+    };
+    if let (Some(notify), Ok(data)) = (request.notify, &reply) {
+        if let Ok(handle) = notification_handle(data) {
+            notifications.lock().unwrap().insert(handle, notify);
+        }
+    }
+    let _ = request.reply.send(reply);
+}
+
+unsafe impl Send for AdsTcpPlc {}
+
+unsafe impl Sync for AdsTcpPlc {}
+
+impl IPlc for AdsTcpPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        AdsTcpPlc {
+            conn,
+            client: None,
+            timeout,
+            target_net_id: None,
+            target_port: 851,
+            source_net_id: None,
+            source_port: 32905,
+            invoke_id: Arc::new(AtomicU32::new(1)),
+            handles: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        }
+    }
+
+    async fn connect(&mut self) -> PlcResult {
+        self.conn.network()?;
+        let stream = open_transport(&self.conn, self.timeout).await?;
+        // 未设置 AMS Net ID 时使用 IP 地址 + ".1.1"
+        let net_id =
+            |net_id: Option<AmsNetId>, addr: Option<std::net::SocketAddr>, name: &str| match (
+                net_id, addr,
+            ) {
+                (Some(net_id), _) => Ok(net_id),
+                (None, Some(addr)) => Ok(AmsNetId::from_ip(addr.ip())),
+                (None, None) => Err(PlcError::Param(format!("需要设置{} AMS Net ID", name))),
+            };
+        let target = AmsAddr {
+            net_id: net_id(self.target_net_id, stream.peer_addr(), "目标")?,
+            port: self.target_port,
+        };
+        let source = AmsAddr {
+            net_id: net_id(self.source_net_id, stream.local_addr(), "本机")?,
+            port: self.source_port,
+        };
+        // 重新连接后 PLC 中的句柄不再有效
+        self.handles.lock().unwrap().clear();
+        let (reader, writer) = tokio::io::split(stream);
+        let pending = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let notifications = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let connected = Arc::new(AtomicBool::new(true));
+        let receiver = tokio::spawn(receive_loop(
+            reader,
+            pending.clone(),
+            notifications.clone(),
+            connected.clone(),
+        ));
+        self.client = Some(Arc::new(AdsClient {
+            writer: Mutex::new(writer),
+            target,
+            source,
+            pending,
+            notifications,
+            connected,
+            receiver,
+        }));
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        // 释放变量句柄
+        let handles: Vec<u32> = self.handles.lock().unwrap().drain().map(|h| h.1).collect();
+        for handle in handles {
+            let _ = self
+                .write_index(ads::SYM_RELEASE_HANDLE, 0, &handle.to_le_bytes())
+                .await;
+        }
+        if let Some(client) = self.client.take() {
+            let _ = client.writer.lock().await.shutdown().await;
+        }
+        Ok(())
+    }
+
+    /// 读取数据
+    ///
+    /// # Param
+    /// * `address_name` - 变量地址：
+    ///     * 变量名称，例如 `MAIN.counter`、`GVL.values[3]`
+    ///     * 索引组:索引偏移，例如 `0x4020:100`(%MB100)、`0xF020:0`(%IB0)
+    /// * `data_type` - 数据类型：
+    ///     * Bit 每个位一个字节(BOOL)，非0为1
+    ///     * Word 按字读取，小端
+    /// * `len` - 数据长度
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        let address = AdsAddress::new(address_name)?;
+        let length = byte_length(&data_type, len)?;
+        let bytes = self
+            .with_index(&address, |group, offset| {
+                self.read_index(group, offset, length)
+            })
+            .await?;
+        Ok(ads::bytes_to_values(&data_type, &bytes))
+    }
+
+    /// 写入数据
+    ///
+    /// # Param
+    /// * `address_name` - 变量地址，例如 `MAIN.counter`、`0x4020:100`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 每个位一个字节(BOOL)，0：关闭；1：打开
+    ///     * Word 按字写入，小端
+    /// * `datas` - 需要写入的数据
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        if datas.is_empty() {
+            return Err(PlcError::Param("写入数据不能为空".into()));
+        }
+        let address = AdsAddress::new(address_name)?;
+        let bytes: Vec<u8> = match data_type {
+            DataType::Bit => {
+                if datas.iter().any(|state| *state > 1) {
+                    return Err(PlcError::Param("位写入数据只能是 1 或者 0".into()));
+                }
+                datas.iter().map(|state| *state as u8).collect()
+            }
+            DataType::Word => datas.iter().flat_map(|word| word.to_le_bytes()).collect(),
+        };
+        self.with_index(&address, |group, offset| {
+            self.write_index(group, offset, &bytes)
+        })
+        .await
+    }
+
+    fn is_connect(&self) -> bool {
+        self.client
+            .as_ref()
+            .is_some_and(|client| client.connected.load(Ordering::Acquire))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// 写入此索引组时模拟 PLC 在线修改，已分配的句柄失效
+    const ONLINE_CHANGE: u32 = 0x9999;
+
+    /// 模拟 PLC
+    #[derive(Default)]
+    struct MockAds {
+        /// %M 区
+        memory: Vec<u8>,
+        /// 变量名称和数据
+        symbols: HashMap<String, Vec<u8>>,
+        /// 有效的句柄
+        handles: HashMap<u32, String>,
+        next_handle: u32,
+        /// 通知句柄对应的索引组、索引偏移、长度
+        notifications: HashMap<u32, (u32, u32, usize)>,
+    }
+
+    impl MockAds {
+        fn value(&mut self, group: u32, offset: u32) -> Option<&mut [u8]> {
+            match group {
+                0x4020 => Some(&mut self.memory[offset as usize..]),
+                ads::SYM_VALUE_BY_HANDLE => {
+                    let name = self.handles.get(&offset)?;
+                    self.symbols.get_mut(name).map(|value| value.as_mut_slice())
+                }
+                _ => None,
+            }
+        }
+
+        /// 处理请求，返回回复数据
+        fn handle(&mut self, command: u16, data: &[u8]) -> Vec<u8> {
+            let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+            let error = |code: u32| code.to_le_bytes().to_vec();
+            match command {
+                ads::ADS_READ => {
+                    let (group, offset, len) = (u32_at(0), u32_at(4), u32_at(8) as usize);
+                    match self.value(group, offset) {
+                        Some(value) if value.len() >= len => {
+                            let mut reply = error(0);
+                            reply.extend((len as u32).to_le_bytes());
+                            reply.extend_from_slice(&value[..len]);
+                            reply
+                        }
+                        Some(_) => error(0x0705),
+                        None => error(0x0710),
+                    }
+                }
+                ads::ADS_WRITE => {
+                    let (group, offset) = (u32_at(0), u32_at(4));
+                    match group {
+                        ONLINE_CHANGE => self.handles.clear(),
+                        ads::SYM_RELEASE_HANDLE => {
+                            self.handles.remove(&u32_at(12));
+                        }
+                        _ => match self.value(group, offset) {
+                            Some(value) => value[..data.len() - 12].copy_from_slice(&data[12..]),
+                            None => return error(0x0710),
+                        },
+                    }
+                    error(0)
+                }
+                ads::ADS_READ_WRITE => {
+                    let name = String::from_utf8(data[16..].to_vec()).unwrap();
+                    if !self.symbols.contains_key(&name) {
+                        return error(0x0710);
+                    }
+                    self.next_handle += 1;
+                    self.handles.insert(self.next_handle, name);
+                    let mut reply = error(0);
+                    reply.extend(4u32.to_le_bytes());
+                    reply.extend(self.next_handle.to_le_bytes());
+                    reply
+                }
+                ads::ADS_ADD_NOTIFICATION => {
+                    let (group, offset, len) = (u32_at(0), u32_at(4), u32_at(8) as usize);
+                    let handle = 100 + self.notifications.len() as u32;
+                    self.notifications.insert(handle, (group, offset, len));
+                    let mut reply = error(0);
+                    reply.extend(handle.to_le_bytes());
+                    reply
+                }
+                _ => match self.notifications.remove(&u32_at(0)) {
+                    Some(_) => error(0),
+                    None => error(0x0751),
+                },
+            }
+        }
+
+        /// 所有通知的当前值
+        fn notification(&mut self) -> Vec<u8> {
+            let mut samples = vec![];
+            let notifications: Vec<_> = self.notifications.clone().into_iter().collect();
+            for (handle, (group, offset, len)) in notifications {
+                samples.extend(handle.to_le_bytes());
+                samples.extend((len as u32).to_le_bytes());
+                samples.extend_from_slice(&self.value(group, offset).unwrap()[..len]);
+            }
+            let mut data = vec![0u8; 4];
+            data.extend(1u32.to_le_bytes());
+            data.extend(0x01D9_0000_0000_0000u64.to_le_bytes());
+            data.extend((self.notifications.len() as u32).to_le_bytes());
+            data.extend(samples);
+            let len = data.len() as u32 - 4;
+            data[..4].copy_from_slice(&len.to_le_bytes());
+            data
+        }
+    }
+
+    /// 回复或者通知的 AMS 报文，交换请求中的目标和源
+    fn ams_reply(request: &[u8], command: u16, state: u16, data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0, 0];
+        frame.extend(((ads::AMS_HEADER_LEN + data.len()) as u32).to_le_bytes());
+        frame.extend_from_slice(&request[14..22]);
+        frame.extend_from_slice(&request[6..14]);
+        frame.extend(command.to_le_bytes());
+        frame.extend(state.to_le_bytes());
+        frame.extend((data.len() as u32).to_le_bytes());
+        frame.extend(0u32.to_le_bytes());
+        frame.extend_from_slice(&request[34..38]);
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    async fn mock_plc(listener: TcpListener) {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut plc = MockAds {
+            memory: (0..=255).collect(),
+            ..Default::default()
+        };
+        plc.symbols.insert("MAIN.counter".into(), vec![0; 4]);
+        plc.symbols.insert("MAIN.flag".into(), vec![0]);
+        loop {
+            let mut header = [0u8; 6];
+            if socket.read_exact(&mut header).await.is_err() {
+                return;
+            }
+            let len = u32::from_le_bytes(header[2..6].try_into().unwrap()) as usize;
+            let mut request = header.to_vec();
+            request.resize(6 + len, 0);
+            socket.read_exact(&mut request[6..]).await.unwrap();
+            // 目标为 1.2.3.4.1.1:851，源为 5.6.7.8.1.1:32905
+            assert_eq!(request[6..14], [1, 2, 3, 4, 1, 1, 0x53, 0x03]);
+            assert_eq!(request[14..22], [5, 6, 7, 8, 1, 1, 0x89, 0x80]);
+            let command = u16::from_le_bytes([request[22], request[23]]);
+            let reply = plc.handle(command, &request[38..]);
+            socket
+                .write_all(&ams_reply(&request, command, 0x0005, &reply))
+                .await
+                .unwrap();
+            // 添加通知和写入后发送通知
+            if matches!(command, ads::ADS_ADD_NOTIFICATION | ads::ADS_WRITE) {
+                let data = plc.notification();
+                let frame = ams_reply(&request, ads::ADS_NOTIFICATION, 0x0004, &data);
+                socket.write_all(&frame).await.unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(mock_plc(listener));
+        let mut plc = AdsTcpPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        )
+        .target("1.2.3.4.1.1".parse().unwrap(), 851)
+        .source("5.6.7.8.1.1".parse().unwrap(), 32905);
+        plc.connect().await.unwrap();
+
+        let r = plc.read("0x4020:2", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x0302, 0x0504]);
+        plc.write("0x4020:10", DataType::Bit, &[1, 0])
+            .await
+            .unwrap();
+        let r = plc.read("16416:10", DataType::Bit, 3).await.unwrap();
+        assert_eq!(r, [1, 0, 1]);
+
+        plc.write("MAIN.counter", DataType::Word, &[0x1234, 0x5678])
+            .await
+            .unwrap();
+        let r = plc.read("MAIN.counter", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x1234, 0x5678]);
+        // 在线修改后句柄失效，重新获取句柄
+        plc.write("0x9999:0", DataType::Word, &[0]).await.unwrap();
+        let r = plc.read("MAIN.counter", DataType::Word, 1).await.unwrap();
+        assert_eq!(r, [0x1234]);
+
+        let r = plc.read("MAIN.missing", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+        let r = plc.read("MAIN.flag", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::Param(_))));
+
+        // 设备通知：添加后收到当前值，写入后收到新值
+        let (handle, mut rx) = plc
+            .add_notification(
+                "MAIN.flag",
+                DataType::Bit,
+                1,
+                AdsTransMode::OnChange,
+                Duration::from_millis(10),
+            )
+            .await
+            .unwrap();
+        let notification = rx.recv().await.unwrap();
+        assert_eq!((notification.handle, notification.datas), (handle, vec![0]));
+        plc.write("MAIN.flag", DataType::Bit, &[1]).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().datas, [1]);
+        plc.delete_notification(handle).await.unwrap();
+        assert!(rx.recv().await.is_none());
+        let r = plc.delete_notification(handle).await;
+        assert!(matches!(r, Err(PlcError::Param(_))));
+
+        plc.disconnect().await.unwrap();
+        let r = plc.read("MAIN.counter", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::NotConnect)));
+    }
+
+    #[tokio::test]
+    async fn test_peer_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            // 接受连接后立即关闭
+            let _ = listener.accept().await.unwrap();
+        });
+        let mut plc = AdsTcpPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        plc.connect().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // 接收任务退出后不再认为已连接
+        assert!(!plc.is_connect());
+        let r = plc.read("0x4020:0", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::NotConnect)));
+
+        let mut plc = AdsTcpPlc::new(SerailPort::default().into(), Duration::from_millis(300));
+        assert!(matches!(plc.connect().await, Err(PlcError::Param(_))));
+    }
+}
@@ -0,0 +1,14 @@
+// ! 倍福PLC
+
+mod ads;
+mod ads_tcp;
+
+pub use self::ads::{AdsAddress, AmsNetId};
+pub use self::ads_tcp::{AdsNotification, AdsTcpPlc, AdsTransMode};
+use crate::{core::PlcConnector, IPlc};
+use std::time::Duration;
+
+/// 创建一个 倍福 网口PLC (ADS 协议，端口一般为48898)
+pub fn new_ads_tcp_plc(conn: PlcConnector, timeout: Duration) -> AdsTcpPlc {
+    AdsTcpPlc::new(conn, timeout)
+}
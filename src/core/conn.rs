@@ -1,5 +1,10 @@
 // ! PLC connect paramter
 
+use std::time::Duration;
+use tracing::{event, Level};
+
+use crate::error::PlcError;
+
 /// plc connect paraters
 #[derive(Debug)]
 pub enum PlcConnector {
@@ -37,6 +42,18 @@ impl PlcConnector {
             PlcConnector::Network(network) => format!("{}:{}", network.ip_address, network.ip_port),
         }
     }
+
+    /// 网络连接参数，只支持网口的协议在连接前检查，串口参数时返回参数错误
+    pub(crate) fn network(&self) -> Result<&Network, PlcError> {
+        match self {
+            PlcConnector::Network(network) => Ok(network),
+            PlcConnector::SerialPort(serial) => {
+                let err = format!("连接参数错误,此处需要Network参数\t{:?}", serial);
+                event!(Level::ERROR, "\t{}", &err);
+                Err(PlcError::Param(err))
+            }
+        }
+    }
 }
 
 impl From<Network> for PlcConnector {
@@ -52,13 +69,24 @@ impl From<SerailPort> for PlcConnector {
 }
 
 /// a serial port type connect parameter struct
+///
+/// `parity`、`stop_bits` 保存为数字以兼容旧版本，可以使用 [`Parity`]、[`StopBits`] 的构建方法设置
 #[derive(Debug)]
 pub struct SerailPort {
     pub port_name: String,
     pub baud_rate: u32,
+    /// 5~8
     pub data_bits: u8,
+    /// 1 或者 2，见 [`StopBits`]
     pub stop_bits: u8,
+    /// 0：无校验；1：奇校验；2：偶校验，见 [`Parity`]
     pub parity: u8,
+    /// 流控制
+    pub flow_control: FlowControl,
+    /// RS-485 收发方向控制，None 时不控制(RS-232 或者自动换向的 RS-485)
+    pub rs485: Option<Rs485>,
+    /// 字符间超时，接收时字符间的静默时间超过此值则丢弃不完整的报文
+    pub inter_byte_timeout: Option<Duration>,
 }
 
 impl Default for SerailPort {
@@ -69,6 +97,9 @@ impl Default for SerailPort {
             data_bits: 8,
             stop_bits: 1,
             parity: 1,
+            flow_control: FlowControl::None,
+            rs485: None,
+            inter_byte_timeout: None,
         }
     }
 }
@@ -77,10 +108,186 @@ impl Clone for SerailPort {
     fn clone(&self) -> Self {
         Self {
             port_name: self.port_name.clone(),
-            baud_rate: self.baud_rate.clone(),
-            data_bits: self.data_bits.clone(),
-            stop_bits: self.stop_bits.clone(),
-            parity: self.parity.clone(),
+            baud_rate: self.baud_rate,
+            data_bits: self.data_bits,
+            stop_bits: self.stop_bits,
+            parity: self.parity,
+            flow_control: self.flow_control,
+            rs485: self.rs485,
+            inter_byte_timeout: self.inter_byte_timeout,
+        }
+    }
+}
+
+impl SerailPort {
+    /// 创建串口参数，其他参数为默认值(8 数据位、1 停止位、奇校验)
+    /// * `port_name` - 串口名称，例如 `/dev/ttyUSB0`
+    /// * `baud_rate` - 波特率
+    pub fn new(port_name: impl Into<String>, baud_rate: u32) -> Self {
+        Self {
+            port_name: port_name.into(),
+            baud_rate,
+            ..Default::default()
+        }
+    }
+
+    /// 设置数据位 5~8
+    pub fn data_bits(mut self, data_bits: u8) -> Self {
+        self.data_bits = data_bits;
+        self
+    }
+
+    /// 设置校验方式
+    pub fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity.into();
+        self
+    }
+
+    /// 设置停止位
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits.into();
+        self
+    }
+
+    /// 设置流控制
+    pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    /// 设置 RS-485 收发方向控制
+    pub fn rs485(mut self, rs485: Rs485) -> Self {
+        self.rs485 = Some(rs485);
+        self
+    }
+
+    /// 设置字符间超时
+    pub fn inter_byte_timeout(mut self, timeout: Duration) -> Self {
+        self.inter_byte_timeout = Some(timeout);
+        self
+    }
+
+    /// 校验方式
+    pub fn parity_type(&self) -> Result<Parity, PlcError> {
+        Parity::try_from(self.parity)
+    }
+
+    /// 停止位
+    pub fn stop_bits_type(&self) -> Result<StopBits, PlcError> {
+        StopBits::try_from(self.stop_bits)
+    }
+
+    /// 传输一个字符的时间：起始位 + 数据位 + 校验位 + 停止位
+    pub fn char_time(&self) -> Duration {
+        if self.baud_rate == 0 {
+            return Duration::ZERO;
+        }
+        let bits = 1 + self.data_bits as u32 + (self.parity != 0) as u32 + self.stop_bits as u32;
+        Duration::from_secs_f64(bits as f64 / self.baud_rate as f64)
+    }
+}
+
+/// 串口校验方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    /// 无校验
+    None,
+    /// 奇校验
+    Odd,
+    /// 偶校验
+    Even,
+}
+
+impl TryFrom<u8> for Parity {
+    type Error = PlcError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Parity::None),
+            1 => Ok(Parity::Odd),
+            2 => Ok(Parity::Even),
+            _ => Err(PlcError::Param(format!("串口参数错误\tparity={}", value))),
+        }
+    }
+}
+
+impl From<Parity> for u8 {
+    fn from(value: Parity) -> Self {
+        match value {
+            Parity::None => 0,
+            Parity::Odd => 1,
+            Parity::Even => 2,
+        }
+    }
+}
+
+/// 串口停止位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+impl TryFrom<u8> for StopBits {
+    type Error = PlcError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(StopBits::One),
+            2 => Ok(StopBits::Two),
+            _ => Err(PlcError::Param(format!(
+                "串口参数错误\tstop_bits={}",
+                value
+            ))),
+        }
+    }
+}
+
+impl From<StopBits> for u8 {
+    fn from(value: StopBits) -> Self {
+        match value {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        }
+    }
+}
+
+/// 串口流控制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowControl {
+    /// 无流控制
+    None,
+    /// 软件流控制(XON/XOFF)
+    Software,
+    /// 硬件流控制(RTS/CTS)
+    Hardware,
+}
+
+/// RS-485 收发方向控制
+///
+/// 串口驱动支持 RS-485 模式时由驱动控制 RTS，否则由程序在发送前后切换 RTS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rs485 {
+    /// 使用串口驱动的 RS-485 模式(Linux TIOCSRS485)
+    pub kernel: bool,
+    /// 发送时 RTS 为高电平，否则发送时为低电平
+    pub rts_on_send: bool,
+    /// RTS 切换为发送后，开始发送前的延时
+    pub delay_before_send: Duration,
+    /// 发送完成后，RTS 切换为接收前的延时
+    pub delay_after_send: Duration,
+    /// 发送时同时接收(会收到自己发送的数据)，只用于驱动的 RS-485 模式
+    pub rx_during_tx: bool,
+}
+
+impl Default for Rs485 {
+    fn default() -> Self {
+        Self {
+            kernel: true,
+            rts_on_send: true,
+            delay_before_send: Duration::ZERO,
+            delay_after_send: Duration::ZERO,
+            rx_during_tx: false,
         }
     }
 }
@@ -129,4 +336,34 @@ mod test {
         let conn_clone = conn.clone();
         assert_eq!(conn.to_string(), conn_clone.to_string())
     }
+
+    #[test]
+    fn test_serial_builder() {
+        let serial = SerailPort::new("/dev/ttyUSB0", 19200)
+            .data_bits(7)
+            .parity(Parity::Even)
+            .stop_bits(StopBits::Two)
+            .flow_control(FlowControl::Hardware)
+            .inter_byte_timeout(Duration::from_millis(5));
+        assert_eq!(serial.parity, 2);
+        assert_eq!(serial.parity_type().unwrap(), Parity::Even);
+        assert_eq!(serial.stop_bits_type().unwrap(), StopBits::Two);
+        assert_eq!(serial.clone().flow_control, FlowControl::Hardware);
+        assert!(Parity::try_from(3).is_err());
+        assert!(StopBits::try_from(0).is_err());
+    }
+
+    #[test]
+    fn test_char_time() {
+        // 8N1：10位
+        let serial = SerailPort::new("/dev/ttyS0", 9600).parity(Parity::None);
+        assert_eq!(serial.char_time().as_micros(), 1041);
+        // 7E2：11位
+        let serial = SerailPort::new("/dev/ttyS0", 11000)
+            .data_bits(7)
+            .parity(Parity::Even)
+            .stop_bits(StopBits::Two);
+        assert_eq!(serial.char_time(), Duration::from_millis(1));
+        assert_eq!(SerailPort::new("", 0).char_time(), Duration::ZERO);
+    }
 }
@@ -1,5 +1,14 @@
 mod address;
 mod conn;
+#[cfg(unix)]
+mod serial;
+mod transport;
 
 pub use address::IAddress;
-pub use conn::{Network, PlcConnector, SerailPort};
+pub use conn::{FlowControl, Network, Parity, PlcConnector, Rs485, SerailPort, StopBits};
+#[cfg(all(unix, test))]
+pub(crate) use serial::open_pty;
+#[cfg(unix)]
+pub use serial::SerialStream;
+pub(crate) use transport::{open_transport, receive, transfer, Channel};
+pub use transport::{Transport, UdpTransport};
@@ -0,0 +1,474 @@
+// ! 串口 (Linux tty)
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::prelude::*;
+
+/// 异步串口
+///
+/// 按 [`SerailPort`] 的参数以原始模式打开串口：
+/// * `data_bits` - 5~8
+/// * `stop_bits` - 1 或者 2
+/// * `parity` - 0：无校验；1：奇校验；2：偶校验
+/// * `flow_control` - 无、XON/XOFF 或者 RTS/CTS
+/// * `rs485` - 使用驱动的 RS-485 模式，或者发送前后由程序切换 RTS
+/// * `inter_byte_timeout` - 由传输层在接收时使用
+pub struct SerialStream {
+    inner: AsyncFd<File>,
+    /// 程序控制 RTS 时的 RS-485 参数，驱动的 RS-485 模式为 None
+    rs485: Option<Rs485>,
+    /// 字符间超时
+    inter_byte_timeout: Option<Duration>,
+    /// 传输一个字符的时间
+    char_time: Duration,
+}
+
+/// UART 发送 FIFO 的深度(16550)
+const UART_FIFO_DEPTH: u32 = 16;
+
+/// 内核发送缓冲区为空后，UART 发送 FIFO 和移位寄存器中剩余数据的最长发送时间
+fn fifo_drain_time(char_time: Duration) -> Duration {
+    char_time * (UART_FIFO_DEPTH + 1)
+}
+
+impl SerialStream {
+    /// 打开串口
+    pub fn open(param: &SerailPort) -> Result<Self, PlcError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(&param.port_name)
+            .map_err(PlcError::Io)?;
+        configure(&file, param)?;
+        let rs485 = match param.rs485 {
+            Some(rs485) if rs485.kernel => {
+                enable_rs485(&file, &rs485)?;
+                None
+            }
+            Some(rs485) => {
+                // 空闲时处于接收状态
+                set_rts(&file, !rs485.rts_on_send)?;
+                Some(rs485)
+            }
+            None => None,
+        };
+        Ok(Self {
+            inner: register(file)?,
+            rs485,
+            inter_byte_timeout: param.inter_byte_timeout,
+            char_time: param.char_time(),
+        })
+    }
+
+    /// 字符间超时
+    pub fn inter_byte_timeout(&self) -> Option<Duration> {
+        self.inter_byte_timeout
+    }
+
+    /// 丢弃接收缓冲区中未读取的数据
+    pub fn clear_input(&self) -> PlcResult {
+        let fd = self.inner.get_ref().as_raw_fd();
+        check_os(unsafe { libc::tcflush(fd, libc::TCIFLUSH) })
+    }
+
+    /// 发送一帧数据，程序控制 RS-485 方向时在发送前切换为发送，发送完成后切换为接收
+    pub async fn send_frame(&mut self, buf: &[u8]) -> io::Result<()> {
+        let Some(rs485) = self.rs485 else {
+            return self.write_all(buf).await;
+        };
+        set_rts(self.inner.get_ref(), rs485.rts_on_send).map_err(into_io)?;
+        if !rs485.delay_before_send.is_zero() {
+            tokio::time::sleep(rs485.delay_before_send).await;
+        }
+        let r = match self.write_all(buf).await {
+            Ok(()) => self.wait_sent().await,
+            Err(err) => Err(err),
+        };
+        if r.is_ok() && !rs485.delay_after_send.is_zero() {
+            tokio::time::sleep(rs485.delay_after_send).await;
+        }
+        // 无论发送是否成功都要切换回接收
+        set_rts(self.inner.get_ref(), !rs485.rts_on_send).map_err(into_io)?;
+        r
+    }
+
+    /// 等待数据全部发出(tcdrain 会阻塞线程，所以轮询)
+    ///
+    /// 先等待内核发送缓冲区为空(TIOCOUTQ)，再等待 UART 的 FIFO 和移位寄存器为空，
+    /// 否则切换为接收时最后几个字符会被截断
+    async fn wait_sent(&self) -> io::Result<()> {
+        let fd = self.inner.get_ref().as_raw_fd();
+        loop {
+            let mut pending: libc::c_int = 0;
+            if unsafe { libc::ioctl(fd, libc::TIOCOUTQ, &mut pending) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if pending == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        // 驱动不支持查询发送器状态时按 FIFO 深度等待
+        if !self.wait_transmitter_empty().await? {
+            tokio::time::sleep(fifo_drain_time(self.char_time)).await;
+        }
+        Ok(())
+    }
+
+    /// 轮询线路状态寄存器直到发送器为空，驱动不支持时返回 false
+    #[cfg(target_os = "linux")]
+    async fn wait_transmitter_empty(&self) -> io::Result<bool> {
+        const TIOCSER_TEMT: libc::c_uint = 1;
+        let fd = self.inner.get_ref().as_raw_fd();
+        loop {
+            let mut lsr: libc::c_uint = 0;
+            if unsafe { libc::ioctl(fd, libc::TIOCSERGETLSR, &mut lsr) } != 0 {
+                let err = io::Error::last_os_error();
+                return match err.raw_os_error() {
+                    Some(libc::ENOTTY) | Some(libc::EINVAL) => Ok(false),
+                    _ => Err(err),
+                };
+            }
+            if lsr & TIOCSER_TEMT != 0 {
+                return Ok(true);
+            }
+            tokio::time::sleep(self.char_time).await;
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn wait_transmitter_empty(&self) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+/// 注册到 tokio 的 IO 事件
+fn register(file: File) -> Result<AsyncFd<File>, PlcError> {
+    // SAFETY: `File` 拥有文件描述符，在 `AsyncFd` 释放前保持打开
+    unsafe { AsyncFd::register(file) }.map_err(|err| PlcError::Io(err.into()))
+}
+
+/// 设置 RTS 电平
+fn set_rts(file: &File, high: bool) -> PlcResult {
+    let request = if high { libc::TIOCMBIS } else { libc::TIOCMBIC };
+    let bits: libc::c_int = libc::TIOCM_RTS;
+    check_os(unsafe { libc::ioctl(file.as_raw_fd(), request, &bits) })
+}
+
+fn into_io(err: PlcError) -> io::Error {
+    match err {
+        PlcError::Io(err) => err,
+        err => io::Error::other(err.to_string()),
+    }
+}
+
+/// Linux 内核的 `struct serial_rs485`
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct SerialRs485 {
+    flags: u32,
+    /// 毫秒
+    delay_rts_before_send: u32,
+    /// 毫秒
+    delay_rts_after_send: u32,
+    padding: [u32; 5],
+}
+
+/// 开启串口驱动的 RS-485 模式，由驱动在发送时切换 RTS
+#[cfg(target_os = "linux")]
+fn enable_rs485(file: &File, rs485: &Rs485) -> PlcResult {
+    const SER_RS485_ENABLED: u32 = 1;
+    const SER_RS485_RTS_ON_SEND: u32 = 1 << 1;
+    const SER_RS485_RTS_AFTER_SEND: u32 = 1 << 2;
+    const SER_RS485_RX_DURING_TX: u32 = 1 << 4;
+    let mut flags = SER_RS485_ENABLED;
+    flags |= if rs485.rts_on_send {
+        SER_RS485_RTS_ON_SEND
+    } else {
+        SER_RS485_RTS_AFTER_SEND
+    };
+    if rs485.rx_during_tx {
+        flags |= SER_RS485_RX_DURING_TX;
+    }
+    let config = SerialRs485 {
+        flags,
+        delay_rts_before_send: rs485.delay_before_send.as_millis() as u32,
+        delay_rts_after_send: rs485.delay_after_send.as_millis() as u32,
+        padding: [0; 5],
+    };
+    check_os(unsafe { libc::ioctl(file.as_raw_fd(), libc::TIOCSRS485, &config) })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn enable_rs485(_file: &File, _rs485: &Rs485) -> PlcResult {
+    Err(PlcError::Param(
+        "当前系统不支持串口驱动的 RS-485 模式".into(),
+    ))
+}
+
+/// 设置串口参数
+fn configure(file: &File, param: &SerailPort) -> PlcResult {
+    let fd = file.as_raw_fd();
+    let invalid = |name: &str, value: u32| {
+        Err(PlcError::Param(format!(
+            "串口参数错误\t{}={}\t串口={}",
+            name, value, param.port_name
+        )))
+    };
+    let mut tio: libc::termios = unsafe { std::mem::zeroed() };
+    check_os(unsafe { libc::tcgetattr(fd, &mut tio) })?;
+    unsafe { libc::cfmakeraw(&mut tio) };
+    tio.c_cflag |= libc::CLOCAL | libc::CREAD;
+    tio.c_cflag &= !libc::CSIZE;
+    tio.c_cflag |= match param.data_bits {
+        5 => libc::CS5,
+        6 => libc::CS6,
+        7 => libc::CS7,
+        8 => libc::CS8,
+        value => return invalid("data_bits", value as u32),
+    };
+    let Ok(stop_bits) = param.stop_bits_type() else {
+        return invalid("stop_bits", param.stop_bits as u32);
+    };
+    match stop_bits {
+        StopBits::One => tio.c_cflag &= !libc::CSTOPB,
+        StopBits::Two => tio.c_cflag |= libc::CSTOPB,
+    }
+    let Ok(parity) = param.parity_type() else {
+        return invalid("parity", param.parity as u32);
+    };
+    match parity {
+        Parity::None => tio.c_cflag &= !(libc::PARENB | libc::PARODD),
+        Parity::Odd => tio.c_cflag |= libc::PARENB | libc::PARODD,
+        Parity::Even => {
+            tio.c_cflag |= libc::PARENB;
+            tio.c_cflag &= !libc::PARODD;
+        }
+    }
+    if parity != Parity::None {
+        tio.c_iflag |= libc::INPCK;
+    }
+    tio.c_cflag &= !libc::CRTSCTS;
+    tio.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY);
+    match param.flow_control {
+        FlowControl::None => {}
+        FlowControl::Software => tio.c_iflag |= libc::IXON | libc::IXOFF,
+        FlowControl::Hardware => tio.c_cflag |= libc::CRTSCTS,
+    }
+    // VMIN=0 时没有数据会返回0而不是 EAGAIN，由 tokio 等待数据
+    tio.c_cc[libc::VMIN] = 1;
+    tio.c_cc[libc::VTIME] = 0;
+    let Some(speed) = baud_speed(param.baud_rate) else {
+        return invalid("baud_rate", param.baud_rate);
+    };
+    check_os(unsafe { libc::cfsetispeed(&mut tio, speed) })?;
+    check_os(unsafe { libc::cfsetospeed(&mut tio, speed) })?;
+    check_os(unsafe { libc::tcsetattr(fd, libc::TCSANOW, &tio) })?;
+    check_os(unsafe { libc::tcflush(fd, libc::TCIOFLUSH) })
+}
+
+/// 波特率对应的 termios 常量
+fn baud_speed(baud_rate: u32) -> Option<libc::speed_t> {
+    Some(match baud_rate {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        460800 => libc::B460800,
+        921600 => libc::B921600,
+        _ => return None,
+    })
+}
+
+fn check_os(r: libc::c_int) -> PlcResult {
+    match r {
+        0 => Ok(()),
+        _ => Err(PlcError::Io(io::Error::last_os_error())),
+    }
+}
+
+impl AsyncRead for SerialStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.inner.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            match guard.try_io(|inner| inner.get_ref().read(unfilled)) {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(err)) => return Poll::Ready(Err(err)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl AsyncWrite for SerialStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.inner.poll_write_ready(cx))?;
+            match guard.try_io(|inner| inner.get_ref().write(buf)) {
+                Ok(r) => return Poll::Ready(r),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// 测试用的伪终端，返回主设备和从设备的路径
+#[cfg(test)]
+pub(crate) fn open_pty() -> (SerialStream, String) {
+    use std::os::unix::io::FromRawFd;
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK);
+        assert!(master >= 0);
+        assert_eq!(libc::grantpt(master), 0);
+        assert_eq!(libc::unlockpt(master), 0);
+        let mut name = [0 as libc::c_char; 128];
+        assert_eq!(libc::ptsname_r(master, name.as_mut_ptr(), name.len()), 0);
+        let name = std::ffi::CStr::from_ptr(name.as_ptr())
+            .to_string_lossy()
+            .into_owned();
+        let master = register(File::from_raw_fd(master)).unwrap();
+        let master = SerialStream {
+            inner: master,
+            rs485: None,
+            inter_byte_timeout: None,
+            char_time: Duration::ZERO,
+        };
+        (master, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_pty() {
+        let (mut master, name) = open_pty();
+        let param = SerailPort {
+            port_name: name,
+            parity: 0,
+            ..Default::default()
+        };
+        let mut port = SerialStream::open(&param).unwrap();
+        port.write_all(b"\x01\x02\x03").await.unwrap();
+        let mut buf = [0u8; 3];
+        master.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [1, 2, 3]);
+        master.write_all(b"\x04\x05").await.unwrap();
+        let mut buf = [0u8; 2];
+        port.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [4, 5]);
+
+        let invalid = SerailPort {
+            baud_rate: 1234,
+            ..param.clone()
+        };
+        assert!(SerialStream::open(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_fifo_drain_time() {
+        // 9600 8N1：FIFO 16 个字符 + 移位寄存器 1 个字符
+        let char_time = SerailPort::new("", 9600).parity(Parity::None).char_time();
+        let drain = fifo_drain_time(char_time);
+        assert_eq!(drain, char_time * 17);
+        assert_eq!(drain.as_micros(), 17708);
+        assert_eq!(fifo_drain_time(Duration::ZERO), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_configure() {
+        let (_master, name) = open_pty();
+        let param = SerailPort::new(name, 19200)
+            .data_bits(7)
+            .parity(Parity::Even)
+            .stop_bits(StopBits::Two)
+            .flow_control(FlowControl::Hardware);
+        let port = SerialStream::open(&param).unwrap();
+        let mut tio: libc::termios = unsafe { std::mem::zeroed() };
+        let fd = port.inner.get_ref().as_raw_fd();
+        assert_eq!(unsafe { libc::tcgetattr(fd, &mut tio) }, 0);
+        // 伪终端固定为 8 位无校验，只检查其他参数
+        assert_ne!(tio.c_cflag & libc::CSTOPB, 0);
+        assert_ne!(tio.c_iflag & libc::INPCK, 0);
+        assert_ne!(tio.c_cflag & libc::CRTSCTS, 0);
+        assert_eq!(unsafe { libc::cfgetospeed(&tio) }, libc::B19200);
+
+        let param = param.flow_control(FlowControl::Software);
+        let port = SerialStream::open(&param).unwrap();
+        let fd = port.inner.get_ref().as_raw_fd();
+        assert_eq!(unsafe { libc::tcgetattr(fd, &mut tio) }, 0);
+        assert_eq!(
+            tio.c_iflag & (libc::IXON | libc::IXOFF),
+            libc::IXON | libc::IXOFF
+        );
+        assert_eq!(tio.c_cflag & libc::CRTSCTS, 0);
+
+        // 伪终端不支持 RS-485
+        let param = param.rs485(Rs485::default());
+        assert!(matches!(SerialStream::open(&param), Err(PlcError::Io(_))));
+    }
+
+    fn check_line(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+        match buf.iter().position(|b| *b == b'\n') {
+            Some(i) => Ok(Ok(&buf[..=i])),
+            None => Ok(Err("数据不完整")),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inter_byte_timeout() {
+        let (mut master, name) = open_pty();
+        let param = SerailPort::new(name, 9600).inter_byte_timeout(Duration::from_millis(20));
+        let mut port = SerialStream::open(&param).unwrap();
+        assert_eq!(port.inter_byte_timeout(), Some(Duration::from_millis(20)));
+        let task = tokio::spawn(async move {
+            master.write_all(b"PO").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            master.write_all(b"PONG\n").await.unwrap();
+            master
+        });
+        let time = Duration::from_millis(500);
+        let reply = crate::core::receive(&mut port, check_line, time)
+            .await
+            .unwrap();
+        assert_eq!(reply, b"PONG\n");
+        drop(task.await.unwrap());
+    }
+}
@@ -0,0 +1,555 @@
+// ! 通讯传输层：协议只负责报文的编码和解析，收发由传输层完成
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf};
+use tokio::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use tokio::sync::{Mutex, OwnedMappedMutexGuard, OwnedMutexGuard};
+use tokio::time::{timeout, timeout_at, Instant};
+use tracing::{event, Level};
+
+#[cfg(unix)]
+use super::SerialStream;
+use crate::prelude::*;
+
+/// 接收缓冲区的最大长度，超出时认为回复数据错误
+const MAX_REPLY: usize = 64 * 1024;
+
+/// 传输层，任意协议都可以通过实现此接口的连接收发报文
+///
+/// 已实现：TCP、UDP([`UdpTransport`])、串口、内存管道(`tokio::io::DuplexStream`，用于测试)
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send + Sync {
+    /// 丢弃接收缓冲区中未读取的数据
+    fn clear_input(&mut self) -> PlcResult {
+        Ok(())
+    }
+
+    /// 发送一帧数据，需要在发送前后做额外处理的连接可以重写(例如 RS-485 切换方向)
+    fn send_frame<'a>(
+        &'a mut self,
+        buf: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>> {
+        Box::pin(self.write_all(buf))
+    }
+
+    /// 字符间超时，已经接收到数据后超过此时间没有新数据则丢弃不完整的报文
+    fn inter_byte_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// 远端地址，不是网络连接时为 None
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// 本机地址，不是网络连接时为 None
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+impl Transport for TcpStream {
+    fn clear_input(&mut self) -> PlcResult {
+        let mut buf = [0u8; 1024];
+        while let Ok(n) = self.try_read(&mut buf) {
+            if n == 0 {
+                return Err(PlcError::Io(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    "连接已断开",
+                )));
+            }
+            event!(Level::WARN, "丢弃残留数据\t长度={}", n);
+        }
+        Ok(())
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        TcpStream::peer_addr(self).ok()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        TcpStream::local_addr(self).ok()
+    }
+}
+
+#[cfg(unix)]
+impl Transport for SerialStream {
+    fn clear_input(&mut self) -> PlcResult {
+        SerialStream::clear_input(self)
+    }
+
+    fn send_frame<'a>(
+        &'a mut self,
+        buf: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>> {
+        Box::pin(SerialStream::send_frame(self, buf))
+    }
+
+    fn inter_byte_timeout(&self) -> Option<Duration> {
+        SerialStream::inter_byte_timeout(self)
+    }
+}
+
+impl Transport for DuplexStream {}
+
+/// UDP 传输，每次读取一个数据报，每次写入发送一个数据报
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    /// 绑定本机任意端口并连接到 `addr`
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self, PlcError> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(PlcError::Io)?;
+        if let Err(err) = socket.connect(addr).await {
+            return Err(PlcError::Comm(format!("连接错误\t{}", err)));
+        }
+        Ok(Self { socket })
+    }
+
+    /// 使用已经连接的 UDP 套接字
+    pub fn new(socket: UdpSocket) -> Self {
+        Self { socket }
+    }
+
+    /// UDP 套接字
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+}
+
+impl Transport for UdpTransport {
+    fn clear_input(&mut self) -> PlcResult {
+        let mut buf = [0u8; 2048];
+        while self.socket.try_recv(&mut buf).is_ok() {}
+        Ok(())
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.socket.peer_addr().ok()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.socket.local_addr().ok()
+    }
+}
+
+impl AsyncRead for UdpTransport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.socket.poll_recv(cx, buf)
+    }
+}
+
+impl AsyncWrite for UdpTransport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.socket.poll_send(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// 按连接参数打开传输层：Network 使用 TCP，SerialPort 使用串口
+pub(crate) async fn open_transport(
+    conn: &PlcConnector,
+    time: Duration,
+) -> Result<Box<dyn Transport>, PlcError> {
+    match conn {
+        PlcConnector::Network(value) => {
+            let addr = format!("{}:{}", value.ip_address, value.ip_port);
+            match timeout(time, TcpStream::connect(addr)).await {
+                Err(_) => {
+                    event!(Level::ERROR, "\t连接超时错误\t{}", conn.to_string());
+                    Err(PlcError::Timeout)
+                }
+                Ok(Err(err)) => {
+                    let err = format!("连接错误\t{}", err);
+                    event!(Level::ERROR, "\t{}", &err);
+                    Err(PlcError::Comm(err))
+                }
+                Ok(Ok(client)) => {
+                    let _ = client.set_nodelay(true); // ! 解决网络物理断开后重连需要50秒的问题
+                    Ok(Box::new(client))
+                }
+            }
+        }
+        #[cfg(unix)]
+        PlcConnector::SerialPort(value) => match SerialStream::open(value) {
+            Err(err) => {
+                event!(Level::ERROR, "\t打开串口错误\t{:?}", err);
+                Err(err)
+            }
+            Ok(stream) => Ok(Box::new(stream)),
+        },
+        #[cfg(not(unix))]
+        PlcConnector::SerialPort(value) => {
+            let err = format!("当前平台不支持串口\t{:?}", value);
+            event!(Level::ERROR, "\t{}", &err);
+            Err(PlcError::Param(err))
+        }
+    }
+}
+
+/// 发送请求并接收一个完整的回复
+///
+/// * `check` - 检查接收的数据是否完整，与各协议的 `check_xxx` 函数相同：
+///     * `Ok(Ok(buf))` => 数据完整，返回回复报文
+///     * `Ok(Err(err))` => 数据不完整，继续接收
+///     * `Err(err)` => 接收数据错误
+pub(crate) async fn transfer<F>(
+    stream: &mut dyn Transport,
+    request: &[u8],
+    check: F,
+    time: Duration,
+) -> Result<Vec<u8>, PlcError>
+where
+    F: for<'a> Fn(&'a [u8]) -> Result<Result<&'a [u8], &'a str>, PlcError>,
+{
+    // 丢弃上一次超时后迟到的回复
+    stream.clear_input()?;
+    let r = timeout(time, stream.send_frame(request)).await?;
+    if let Err(err) = r {
+        return Err(PlcError::Io(err));
+    }
+    receive(stream, check, time).await
+}
+
+/// 接收一个完整的回复，`check` 同 [`transfer`]
+pub(crate) async fn receive<F>(
+    stream: &mut dyn Transport,
+    check: F,
+    time: Duration,
+) -> Result<Vec<u8>, PlcError>
+where
+    F: for<'a> Fn(&'a [u8]) -> Result<Result<&'a [u8], &'a str>, PlcError>,
+{
+    // 读取返回数据,有可能接收数据不完整，所以需要循环读取
+    let mut reply = Vec::with_capacity(1024);
+    let mut buf = [0u8; 2048];
+    let inter_byte = stream.inter_byte_timeout();
+    // 整个回复共用一个超时时间，对端不停发送无效数据时也会超时
+    let deadline = Instant::now() + time;
+    loop {
+        let r = match inter_byte {
+            Some(gap) if !reply.is_empty() => {
+                let gap_deadline = deadline.min(Instant::now() + gap);
+                match timeout_at(gap_deadline, stream.read(&mut buf)).await {
+                    Ok(r) => r,
+                    Err(_) if Instant::now() >= deadline => return Err(PlcError::Timeout),
+                    Err(_) => {
+                        event!(Level::WARN, "丢弃不完整的报文\t长度={}", reply.len());
+                        reply.clear();
+                        continue;
+                    }
+                }
+            }
+            _ => timeout_at(deadline, stream.read(&mut buf)).await?,
+        };
+        match r {
+            Ok(0) => {
+                return Err(PlcError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "读取数据为空",
+                )))
+            }
+            Ok(n) => reply.extend_from_slice(&buf[..n]),
+            Err(err) => return Err(PlcError::Io(err)),
+        }
+        match check(&reply)? {
+            Ok(frame) => return Ok(frame.to_vec()),
+            Err(err) => {
+                event!(Level::DEBUG, "数据不完整,继续等待...\terr={}", err);
+                if reply.len() >= MAX_REPLY {
+                    return Err(PlcError::Comm("接收数据超出缓冲区长度".into()));
+                }
+            }
+        }
+    }
+}
+
+/// 请求/回复通讯通道，管理连接参数、超时时间和共用的连接
+///
+/// 克隆的通道共用同一个连接，请求按顺序发送
+#[derive(Clone)]
+pub(crate) struct Channel {
+    /// 连接参数
+    conn: PlcConnector,
+    /// 超时时间
+    timeout: Duration,
+    /// 客户端连接
+    client: Option<Arc<Link>>,
+}
+
+/// 独占的传输层
+pub(crate) type TransportGuard =
+    OwnedMappedMutexGuard<Option<Box<dyn Transport>>, Box<dyn Transport>>;
+
+/// 克隆的通道共用的连接
+struct Link {
+    /// 传输层，关闭后为 None
+    stream: Arc<Mutex<Option<Box<dyn Transport>>>>,
+    /// 是否已连接，`is_connect` 不需要等待锁
+    connected: AtomicBool,
+}
+
+impl Link {
+    fn new(stream: Box<dyn Transport>) -> Arc<Self> {
+        Arc::new(Self {
+            stream: Arc::new(Mutex::new(Some(stream))),
+            connected: AtomicBool::new(true),
+        })
+    }
+
+    /// 关闭传输层，保留共用的连接
+    async fn close(&self) {
+        let mut stream = self.stream.lock().await;
+        self.connected.store(false, Ordering::Release);
+        if let Some(mut stream) = stream.take() {
+            let _ = stream.shutdown().await;
+        }
+    }
+}
+
+impl Channel {
+    pub(crate) fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        Self {
+            conn,
+            timeout,
+            client: None,
+        }
+    }
+
+    /// 连接参数
+    pub(crate) fn conn(&self) -> &PlcConnector {
+        &self.conn
+    }
+
+    /// 超时时间
+    pub(crate) fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// 按连接参数打开连接
+    pub(crate) async fn connect(&mut self) -> PlcResult {
+        let stream = open_transport(&self.conn, self.timeout).await?;
+        self.client = Some(Link::new(stream));
+        Ok(())
+    }
+
+    /// 重新连接，替换共用连接中的传输层，所有共用此连接的通道都会使用新的连接
+    pub(crate) async fn reconnect(&mut self) -> PlcResult {
+        let stream = open_transport(&self.conn, self.timeout).await?;
+        match &self.client {
+            Some(client) => {
+                let mut current = client.stream.lock().await;
+                if let Some(mut old) = current.replace(stream) {
+                    let _ = old.shutdown().await;
+                }
+                client.connected.store(true, Ordering::Release);
+            }
+            None => self.client = Some(Link::new(stream)),
+        }
+        Ok(())
+    }
+
+    /// 使用已经打开的连接，例如 UDP 或者测试用的内存管道
+    pub(crate) fn attach(&mut self, transport: impl Transport + 'static) {
+        self.client = Some(Link::new(Box::new(transport)));
+    }
+
+    /// 断开此通道，其他共用此连接的通道不受影响，最后一个通道断开时关闭连接
+    ///
+    /// 需要断开所有共用此连接的通道时使用 [`Channel::close`]
+    pub(crate) async fn disconnect(&mut self) -> PlcResult {
+        self.client.take();
+        Ok(())
+    }
+
+    /// 关闭共用连接中的传输层，共用此连接的通道在 [`Channel::reconnect`] 后继续使用新的连接
+    pub(crate) async fn close(&self) -> PlcResult {
+        if let Some(client) = &self.client {
+            client.close().await;
+        }
+        Ok(())
+    }
+
+    /// 是否有其他通道共用此连接
+    pub(crate) fn is_shared(&self) -> bool {
+        self.client
+            .as_ref()
+            .is_some_and(|client| Arc::strong_count(client) > 1)
+    }
+
+    pub(crate) fn is_connect(&self) -> bool {
+        self.client
+            .as_ref()
+            .is_some_and(|client| client.connected.load(Ordering::Acquire))
+    }
+
+    /// 独占连接，用于需要连续收发多个报文的场景(握手、丢弃其他请求的回复)
+    pub(crate) async fn lock(&self) -> Result<TransportGuard, PlcError> {
+        let Some(client) = self.client.as_ref() else {
+            return Err(PlcError::NotConnect);
+        };
+        let stream = client.stream.clone().lock_owned().await;
+        OwnedMutexGuard::try_map(stream, |stream| stream.as_mut()).map_err(|_| PlcError::NotConnect)
+    }
+
+    /// 只发送请求，不等待回复(广播)
+    pub(crate) async fn send(&self, request: &[u8]) -> PlcResult {
+        let mut client = self.lock().await?;
+        let r = timeout(self.timeout, client.send_frame(request)).await?;
+        if let Err(err) = r {
+            return Err(PlcError::Io(err));
+        }
+        Ok(())
+    }
+
+    /// 发送请求并返回完整的回复，`check` 同 [`transfer`]
+    pub(crate) async fn send_and_receive<F>(
+        &self,
+        request: &[u8],
+        check: F,
+    ) -> Result<Vec<u8>, PlcError>
+    where
+        F: for<'a> Fn(&'a [u8]) -> Result<Result<&'a [u8], &'a str>, PlcError>,
+    {
+        let mut client = self.lock().await?;
+        transfer(client.as_mut(), request, check, self.timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    /// 以回车换行结束的报文
+    fn check_line(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+        match buf.windows(2).position(|w| w == b"\r\n") {
+            Some(p) => Ok(Ok(&buf[..p])),
+            None => Ok(Err("未找到结束符")),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplex() {
+        let (local, mut remote) = duplex(256);
+        let mut channel = Channel::new(Network::default().into(), Duration::from_millis(300));
+        let r = channel.send_and_receive(b"PING\r\n", check_line).await;
+        assert!(matches!(r, Err(PlcError::NotConnect)));
+        channel.attach(local);
+        tokio::spawn(async move {
+            let mut buf = [0u8; 6];
+            remote.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"PING\r\n");
+            // 分两次回复
+            remote.write_all(b"PO").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            remote.write_all(b"NG\r\n").await.unwrap();
+        });
+        let reply = channel.send_and_receive(b"PING\r\n", check_line).await;
+        assert_eq!(reply.unwrap(), b"PONG");
+        let r = channel.send_and_receive(b"PING\r\n", check_line).await;
+        assert!(r.is_err());
+        channel.disconnect().await.unwrap();
+        assert!(!channel.is_connect());
+    }
+
+    #[tokio::test]
+    async fn test_noise_timeout() {
+        let (local, mut remote) = duplex(256);
+        let mut channel = Channel::new(Network::default().into(), Duration::from_millis(100));
+        channel.attach(local);
+        tokio::spawn(async move {
+            // 不停发送没有结束符的数据
+            while remote.write_all(b"X").await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        let start = std::time::Instant::now();
+        let r = channel.send_and_receive(b"PING\r\n", check_line).await;
+        assert!(matches!(r, Err(PlcError::Timeout)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_clone() {
+        let (local, mut remote) = duplex(256);
+        let mut channel = Channel::new(Network::default().into(), Duration::from_millis(300));
+        channel.attach(local);
+        let mut clone = channel.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 6];
+            remote.read_exact(&mut buf).await.unwrap();
+            remote.write_all(b"PONG\r\n").await.unwrap();
+            remote.read_exact(&mut buf).await.unwrap();
+        });
+        // 断开克隆的通道不影响原通道
+        clone.disconnect().await.unwrap();
+        assert!(!clone.is_connect());
+        assert!(channel.is_connect());
+        let reply = channel.send_and_receive(b"PING\r\n", check_line).await;
+        assert_eq!(reply.unwrap(), b"PONG");
+        // 关闭后所有共用此连接的通道都会断开
+        let other = channel.clone();
+        channel.close().await.unwrap();
+        assert!(!other.is_connect());
+        let r = other.send_and_receive(b"PING\r\n", check_line).await;
+        assert!(matches!(r, Err(PlcError::NotConnect)));
+    }
+
+    #[tokio::test]
+    async fn test_udp() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (n, peer) = server.recv_from(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"PING\r\n");
+            server.send_to(b"PONG\r\n", peer).await.unwrap();
+        });
+        let mut channel = Channel::new(Network::default().into(), Duration::from_millis(300));
+        channel.attach(UdpTransport::connect(addr).await.unwrap());
+        let reply = channel.send_and_receive(b"PING\r\n", check_line).await;
+        assert_eq!(reply.unwrap(), b"PONG");
+    }
+
+    #[tokio::test]
+    async fn test_tcp() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 6];
+            socket.read_exact(&mut buf).await.unwrap();
+            socket.write_all(b"PONG\r\n").await.unwrap();
+        });
+        let mut channel = Channel::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        channel.connect().await.unwrap();
+        let reply = channel.send_and_receive(b"PING\r\n", check_line).await;
+        assert_eq!(reply.unwrap(), b"PONG");
+    }
+}
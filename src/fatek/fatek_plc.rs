@@ -0,0 +1,299 @@
+// ! 永宏 FBs 通讯协议(串口，或者通过串口服务器透传的 TCP)
+
+use std::time::Duration;
+
+use super::fbs::{self, FatekAddress};
+use crate::core::{Channel, Transport};
+use crate::prelude::*;
+
+/// 永宏 FBs PLC
+///
+/// 支持串口(默认 9600 7E1)和串口服务器 TCP 透传，站号默认为1
+pub struct FatekPlc {
+    /// 通讯通道，同一连接上的多个站共用
+    channel: Channel,
+    /// 站号
+    station: u8,
+}
+
+impl Clone for FatekPlc {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            station: self.station,
+        }
+    }
+}
+
+impl FatekPlc {
+    /// 设置站号(1~254)，默认为1
+    pub fn station(mut self, station: u8) -> Self {
+        self.station = station;
+        self
+    }
+
+    /// 发送命令并返回回复中错误码之后的数据
+    async fn send_and_receive(&self, command: &str, data: &str) -> Result<String, PlcError> {
+        if !(1..=254).contains(&self.station) {
+            return Err(PlcError::Param(format!(
+                "FATEK 站号只能是 1~254\t站号={}",
+                self.station
+            )));
+        }
+        let frame = fbs::create_frame(self.station, command, data);
+        let reply = self
+            .channel
+            .send_and_receive(&frame, fbs::check_frame)
+            .await?;
+        fbs::parse_frame(&reply, self.station, command).map(String::from)
+    }
+
+    /// 使用已经打开的连接代替 `connect`
+    pub fn attach(&mut self, transport: impl Transport + 'static) {
+        self.channel.attach(transport);
+    }
+
+    /// 读取系统状态(命令40)，返回状态1~3
+    ///
+    /// 状态1 bit0：1 运行；0 停止
+    pub async fn read_status(&self) -> Result<[u8; 3], PlcError> {
+        let data = self.send_and_receive(fbs::READ_STATUS, "").await?;
+        let status = (0..3)
+            .map(|i| {
+                data.get(i * 2..i * 2 + 2)
+                    .and_then(|s| u8::from_str_radix(s, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>();
+        match status {
+            Some(status) => Ok([status[0], status[1], status[2]]),
+            None => Err(PlcError::Comm(format!(
+                "FATEK 系统状态解析失败\t数据={}",
+                data
+            ))),
+        }
+    }
+}
+
+unsafe impl Send for FatekPlc {}
+
+unsafe impl Sync for FatekPlc {}
+
+impl IPlc for FatekPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        FatekPlc {
+            channel: Channel::new(conn, timeout),
+            station: 1,
+        }
+    }
+
+    async fn connect(&mut self) -> PlcResult {
+        self.channel.connect().await
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    /// 读取数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `X0`、`Y10`、`M100`、`R0`、`D100`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 读取 X/Y/M，每个点一个数据
+    ///     * Word 读取 R/D
+    /// * `len` - 数据长度
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        if len == 0 {
+            return Err(PlcError::Param("读取长度不能为0".into()));
+        }
+        let address = FatekAddress::new(address_name, data_type.clone())?;
+        address.name(len as u32 - 1)?;
+        let (command, max) = match data_type {
+            DataType::Bit => (fbs::READ_BITS, fbs::MAX_BITS),
+            DataType::Word => (fbs::READ_REGISTERS, fbs::MAX_REGISTERS),
+        };
+        let mut datas = Vec::with_capacity(len as usize);
+        while datas.len() < len as usize {
+            let count = max.min(len - datas.len() as u16);
+            let name = address.name(datas.len() as u32)?;
+            let data = self
+                .send_and_receive(command, &fbs::read_data(count, &name))
+                .await?;
+            datas.extend(match data_type {
+                DataType::Bit => fbs::parse_bits(&data, count)?,
+                DataType::Word => fbs::parse_registers(&data, count)?,
+            });
+        }
+        Ok(datas)
+    }
+
+    /// 写入数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `Y10`、`M100`、`R0`、`D100`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 写入 Y/M，0：关闭；1：打开
+    ///     * Word 写入 R/D
+    /// * `datas` - 需要写入的数据
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        if datas.is_empty() {
+            return Err(PlcError::Param("写入数据不能为空".into()));
+        }
+        let address = FatekAddress::new(address_name, data_type.clone())?;
+        address.name(datas.len() as u32 - 1)?;
+        let max = match data_type {
+            DataType::Bit => {
+                if datas.iter().any(|state| *state > 1) {
+                    return Err(PlcError::Param("位写入数据只能是 1 或者 0".into()));
+                }
+                fbs::MAX_BITS as usize
+            }
+            DataType::Word => fbs::MAX_REGISTERS as usize,
+        };
+        for (i, chunk) in datas.chunks(max).enumerate() {
+            let name = address.name((i * max) as u32)?;
+            let (command, data) = match data_type {
+                DataType::Bit => (fbs::WRITE_BITS, fbs::write_bits_data(&name, chunk)),
+                DataType::Word => (
+                    fbs::WRITE_REGISTERS,
+                    fbs::write_registers_data(&name, chunk),
+                ),
+            };
+            self.send_and_receive(command, &data).await?;
+        }
+        Ok(())
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::open_pty;
+    use std::collections::HashMap;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 模拟 PLC：寄存器初始值为编号，位初始值为编号的最低位，站号为1
+    async fn mock_plc<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, requests: usize) -> S {
+        let mut memory: HashMap<String, u16> = HashMap::new();
+        for _ in 0..requests {
+            let mut request = vec![];
+            while request.last() != Some(&fbs::ETX) {
+                let mut byte = [0u8];
+                stream.read_exact(&mut byte).await.unwrap();
+                request.push(byte[0]);
+            }
+            let text = std::str::from_utf8(&request[1..request.len() - 3]).unwrap();
+            let (station, command, data) = (&text[..2], &text[2..4], &text[4..]);
+            let reply = match (station, command) {
+                ("01", fbs::READ_STATUS) => "0010000".to_string(),
+                ("01", _) => {
+                    let count = match usize::from_str_radix(&data[..2], 16).unwrap() {
+                        0 => 256,
+                        count => count,
+                    };
+                    let kind = &data[2..3];
+                    let width = if matches!(kind, "R" | "D") { 5 } else { 4 };
+                    let start: usize = data[3..3 + width].parse().unwrap();
+                    let values = &data[3 + width..];
+                    let mut reply = "0".to_string();
+                    for i in 0..count {
+                        let key = format!("{}{}", kind, start + i);
+                        let value = memory.get(&key).copied().unwrap_or((start + i) as u16);
+                        match command {
+                            fbs::READ_BITS => reply.push_str(&(value & 1).to_string()),
+                            fbs::READ_REGISTERS => reply.push_str(&format!("{:04X}", value)),
+                            fbs::WRITE_BITS => {
+                                memory.insert(key, values[i..i + 1].parse().unwrap());
+                            }
+                            _ => {
+                                let value = u16::from_str_radix(&values[i * 4..i * 4 + 4], 16);
+                                memory.insert(key, value.unwrap());
+                            }
+                        }
+                    }
+                    reply
+                }
+                // 其他站不回复
+                _ => continue,
+            };
+            let station = u8::from_str_radix(station, 16).unwrap();
+            let frame = fbs::create_frame(station, command, &reply);
+            stream.write_all(&frame).await.unwrap();
+        }
+        stream
+    }
+
+    async fn check_read_write(plc: &FatekPlc) {
+        assert_eq!(plc.read_status().await.unwrap(), [0x01, 0x00, 0x00]);
+        let r = plc.read("R100", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [100, 101]);
+        plc.write("D10", DataType::Word, &[0x1234, 0xABCD])
+            .await
+            .unwrap();
+        let r = plc.read("D10", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x1234, 0xABCD]);
+        // 超过64个寄存器时分包
+        let r = plc.read("R0", DataType::Word, 65).await.unwrap();
+        assert_eq!(r[64], 64);
+        let r = plc.read("X1", DataType::Bit, 3).await.unwrap();
+        assert_eq!(r, [1, 0, 1]);
+        plc.write("M0", DataType::Bit, &[0, 0]).await.unwrap();
+        let r = plc.read("M0", DataType::Bit, 3).await.unwrap();
+        assert_eq!(r, [0, 0, 0]);
+        let r = plc.read("D4095", DataType::Word, 2).await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+    }
+
+    #[tokio::test]
+    async fn test_serial() {
+        let (master, name) = open_pty();
+        let task = tokio::spawn(mock_plc(master, 10));
+        let param = SerailPort {
+            port_name: name,
+            data_bits: 7,
+            parity: 2,
+            ..Default::default()
+        };
+        let mut plc = FatekPlc::new(param.into(), Duration::from_millis(200));
+        plc.connect().await.unwrap();
+        check_read_write(&plc).await;
+        // 其他站不回复
+        let r = plc.clone().station(2).read("R0", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::Timeout)));
+        let r = plc.clone().station(0).read("R0", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::Param(_))));
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            mock_plc(socket, 9).await;
+        });
+        let mut plc = FatekPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(200),
+        );
+        plc.connect().await.unwrap();
+        check_read_write(&plc).await;
+        plc.disconnect().await.unwrap();
+    }
+}
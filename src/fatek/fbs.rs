@@ -0,0 +1,328 @@
+// ! 永宏 FBs 通讯协议(ASCII)
+//
+// * 请求：STX + 站号(2) + 命令(2) + 数据 + 校验和(2) + ETX
+// * 回复：STX + 站号(2) + 命令(2) + 错误码(1) + 数据 + 校验和(2) + ETX
+// * 校验和为 STX 到数据最后一个字节的累加和(低8位)，以两位十六进制表示
+
+use crate::prelude::*;
+
+/// 起始符
+pub const STX: u8 = 0x02;
+/// 结束符
+pub const ETX: u8 = 0x03;
+/// 读取系统状态
+pub const READ_STATUS: &str = "40";
+/// 读取连续位状态
+pub const READ_BITS: &str = "44";
+/// 写入连续位状态
+pub const WRITE_BITS: &str = "45";
+/// 读取连续寄存器
+pub const READ_REGISTERS: &str = "46";
+/// 写入连续寄存器
+pub const WRITE_REGISTERS: &str = "47";
+/// 单次最多读写的位数量
+pub const MAX_BITS: u16 = 256;
+/// 单次最多读写的寄存器数量
+pub const MAX_REGISTERS: u16 = 64;
+
+/// 永宏 FBs 寄存器地址
+///
+/// * 位：`X0`(输入)、`Y0`(输出)、`M0`(内部继电器)
+/// * 字：`R0`(数据寄存器)、`D0`(数据寄存器)
+pub struct FatekAddress {
+    address_name: String,
+    data_type: DataType,
+    /// 寄存器类型
+    inner_kind: char,
+    /// 编号
+    inner_number: u32,
+}
+
+impl FatekAddress {
+    /// 实例化一个寄存器地址
+    ///
+    /// # Error
+    /// 无效的寄存器地址，或者地址与数据类型不一致
+    pub fn new(address_name: impl Into<String>, data_type: DataType) -> Result<Self, PlcError> {
+        let address_name = address_name.into().trim().to_uppercase();
+        let invalid =
+            || PlcError::Addr(format!("FATEK 无效的寄存器地址\t寄存器={}", &address_name));
+        let mut chars = address_name.chars();
+        let kind = chars.next().ok_or_else(invalid)?;
+        let number = chars.as_str();
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let number = number.parse::<u32>().map_err(|_| invalid())?;
+        match (kind, &data_type) {
+            ('X' | 'Y' | 'M', DataType::Bit) | ('R' | 'D', DataType::Word) => {}
+            ('X' | 'Y' | 'M' | 'R' | 'D', _) => {
+                return Err(PlcError::Addr(format!(
+                    "FATEK 寄存器地址与数据类型不一致\t寄存器={}",
+                    &address_name
+                )))
+            }
+            _ => return Err(invalid()),
+        }
+        if number > max_number(kind) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            address_name,
+            data_type,
+            inner_kind: kind,
+            inner_number: number,
+        })
+    }
+
+    /// 从当前地址偏移 `offset` 个点后的名称，位为4位编号(`M0010`)，寄存器为5位编号(`R00010`)
+    pub fn name(&self, offset: u32) -> Result<String, PlcError> {
+        let number = self.inner_number + offset;
+        if number > max_number(self.inner_kind) {
+            return Err(PlcError::Addr(format!(
+                "FATEK 读写范围超出地址范围\t寄存器={}",
+                self.address_name
+            )));
+        }
+        Ok(match self.data_type {
+            DataType::Bit => format!("{}{:04}", self.inner_kind, number),
+            DataType::Word => format!("{}{:05}", self.inner_kind, number),
+        })
+    }
+}
+
+/// 最大编号(FBs)
+fn max_number(kind: char) -> u32 {
+    match kind {
+        'X' | 'Y' => 255,
+        'M' => 2001,
+        'D' => 4095,
+        // R0~R3839、R3840~R3903、R4000~R4167、R5000~R8071，中间的空缺由 PLC 返回错误
+        _ => 8071,
+    }
+}
+
+impl IAddress for FatekAddress {
+    fn get_address_name(&self) -> &str {
+        &self.address_name
+    }
+
+    fn get_address_header(&self) -> &str {
+        &self.address_name[..1]
+    }
+
+    fn get_address(&self) -> u32 {
+        self.inner_number
+    }
+
+    fn get_data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// 校验和
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+/// 创建请求报文
+pub fn create_frame(station: u8, command: &str, data: &str) -> Vec<u8> {
+    let mut frame = vec![STX];
+    frame.extend(format!("{:02X}{}{}", station, command, data).bytes());
+    frame.extend(format!("{:02X}", checksum(&frame)).bytes());
+    frame.push(ETX);
+    frame
+}
+
+/// 检查回复报文是否完整
+pub fn check_frame(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    match buf.first() {
+        None => return Ok(Err("数据长度不足")),
+        Some(&STX) => {}
+        Some(_) => {
+            return Err(PlcError::Comm(format!(
+                "FATEK 回复的起始符错误\t数据={:02X?}",
+                buf
+            )))
+        }
+    }
+    match buf.iter().position(|b| *b == ETX) {
+        Some(p) => Ok(Ok(&buf[..=p])),
+        None => Ok(Err("未找到结束符 0x03")),
+    }
+}
+
+/// 解析回复报文，返回错误码之后的数据
+pub fn parse_frame<'a>(frame: &'a [u8], station: u8, command: &str) -> Result<&'a str, PlcError> {
+    let invalid = || PlcError::Comm(format!("FATEK 回复解析失败\t数据={:02X?}", frame));
+    // 按字节位置切分，非 ASCII 字符会导致切分到字符中间
+    if !frame.is_ascii() {
+        return Err(invalid());
+    }
+    let text = std::str::from_utf8(frame).map_err(|_| invalid())?;
+    if text.len() < 9 {
+        return Err(invalid());
+    }
+    let (body, tail) = text.split_at(text.len() - 3);
+    let sum = u8::from_str_radix(&tail[..2], 16).map_err(|_| invalid())?;
+    if sum != checksum(body.as_bytes()) {
+        return Err(PlcError::Comm(format!(
+            "FATEK 回复的校验和错误\t数据={:02X?}",
+            frame
+        )));
+    }
+    if body[1..3] != format!("{:02X}", station) || &body[3..5] != command {
+        return Err(invalid());
+    }
+    match body.as_bytes()[5] {
+        b'0' => Ok(&body[6..]),
+        code => Err(fatek_error(code)),
+    }
+}
+
+/// 错误码转换为错误
+fn fatek_error(code: u8) -> PlcError {
+    let desc = match code {
+        b'2' => "数值错误",
+        b'4' => "格式错误，或者命令无法执行",
+        b'5' => "无法运行(程序校验和错误)",
+        b'6' => "无法运行(PLC ID 与程序 ID 不一致)",
+        b'7' => "无法运行(程序语法错误)",
+        b'9' => "无法运行(不支持的功能)",
+        b'A' => "地址错误",
+        _ => "未知错误",
+    };
+    let msg = format!("FATEK 错误：{}\t错误码={}", desc, code as char);
+    match code {
+        b'A' => PlcError::Addr(msg),
+        b'2' | b'4' => PlcError::Param(msg),
+        _ => PlcError::Comm(msg),
+    }
+}
+
+/// 读取数据：数量(2，256 为 00) + 起始名称
+pub fn read_data(count: u16, name: &str) -> String {
+    format!("{:02X}{}", count % 256, name)
+}
+
+/// 写入连续位状态的数据
+pub fn write_bits_data(name: &str, states: &[u16]) -> String {
+    let states: String = states
+        .iter()
+        .map(|state| if *state == 0 { '0' } else { '1' })
+        .collect();
+    format!("{}{}", read_data(states.len() as u16, name), states)
+}
+
+/// 写入连续寄存器的数据
+pub fn write_registers_data(name: &str, values: &[u16]) -> String {
+    let values: String = values
+        .iter()
+        .map(|value| format!("{:04X}", value))
+        .collect();
+    format!("{}{}", read_data((values.len() / 4) as u16, name), values)
+}
+
+/// 解析位状态，每个位一个字符
+pub fn parse_bits(data: &str, count: u16) -> Result<Vec<u16>, PlcError> {
+    let states = data
+        .bytes()
+        .map(|b| match b {
+            b'0' => Ok(0),
+            b'1' => Ok(1),
+            _ => Err(()),
+        })
+        .collect::<Result<Vec<u16>, _>>();
+    match states {
+        Ok(states) if states.len() == count as usize => Ok(states),
+        _ => Err(PlcError::Comm(format!(
+            "FATEK 位状态解析失败\t数据={}\t需要={}",
+            data, count
+        ))),
+    }
+}
+
+/// 解析寄存器，每个寄存器4位十六进制
+pub fn parse_registers(data: &str, count: u16) -> Result<Vec<u16>, PlcError> {
+    let invalid = || {
+        PlcError::Comm(format!(
+            "FATEK 寄存器解析失败\t数据={}\t需要={}",
+            data, count
+        ))
+    };
+    if data.len() != count as usize * 4 {
+        return Err(invalid());
+    }
+    (0..data.len())
+        .step_by(4)
+        .map(|i| u16::from_str_radix(&data[i..i + 4], 16).map_err(|_| invalid()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let r = FatekAddress::new("m10", DataType::Bit).unwrap();
+        assert_eq!(r.name(2).unwrap(), "M0012");
+        let r = FatekAddress::new("R100", DataType::Word).unwrap();
+        assert_eq!(r.name(0).unwrap(), "R00100");
+        let r = FatekAddress::new("X255", DataType::Bit).unwrap();
+        assert!(r.name(1).is_err());
+        let invalid = [
+            ("R100", DataType::Bit),
+            ("M10", DataType::Word),
+            ("Z10", DataType::Bit),
+            ("D", DataType::Word),
+            ("D4096", DataType::Word),
+            ("M-1", DataType::Bit),
+        ];
+        for (name, data_type) in invalid {
+            assert!(FatekAddress::new(name, data_type).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_frame() {
+        // 读取站号1的 Y0~Y3 状态
+        let frame = create_frame(1, READ_BITS, &read_data(4, "Y0000"));
+        assert_eq!(frame, b"\x02014404Y000048\x03");
+        let reply = create_frame(1, READ_BITS, "01010");
+        let frame = check_frame(&reply).unwrap().unwrap();
+        assert_eq!(parse_frame(frame, 1, READ_BITS).unwrap(), "1010");
+        assert_eq!(parse_bits("1010", 4).unwrap(), [1, 0, 1, 0]);
+        assert!(parse_bits("1010", 3).is_err());
+        assert!(parse_frame(frame, 2, READ_BITS).is_err());
+
+        let mut bad = reply.clone();
+        bad[6] = b'0';
+        assert!(parse_frame(&bad, 1, READ_BITS).is_err());
+        let reply = create_frame(1, READ_REGISTERS, "A");
+        assert!(matches!(
+            parse_frame(&reply, 1, READ_REGISTERS),
+            Err(PlcError::Addr(_))
+        ));
+        // 非 ASCII 数据返回错误
+        let mut bad = create_frame(1, READ_BITS, "01010");
+        bad[6] = 0xE4;
+        assert!(parse_frame(&bad, 1, READ_BITS).is_err());
+        let bad = "\x02\u{4E2D}\u{6587}01\u{4E2D}\x03".as_bytes();
+        assert!(parse_frame(bad, 1, READ_BITS).is_err());
+        assert!(check_frame(b"\x0201").unwrap().is_err());
+        assert!(check_frame(b"0201\x03").is_err());
+
+        assert_eq!(write_bits_data("M0000", &[1, 0]), "02M000010");
+        assert_eq!(
+            write_registers_data("R00000", &[0x1234, 0xFFFF]),
+            "02R000001234FFFF"
+        );
+        assert_eq!(parse_registers("1234FFFF", 2).unwrap(), [0x1234, 0xFFFF]);
+        assert!(parse_registers("1234FFF", 2).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+// ! 永宏PLC
+
+#[cfg(unix)]
+mod fatek_plc;
+mod fbs;
+
+#[cfg(unix)]
+pub use self::fatek_plc::FatekPlc;
+pub use self::fbs::FatekAddress;
+#[cfg(unix)]
+use crate::{core::PlcConnector, IPlc};
+#[cfg(unix)]
+use std::time::Duration;
+
+/// 创建一个 永宏 FBs PLC (串口或者 TCP 透传)
+#[cfg(unix)]
+pub fn new_fatek_plc(conn: PlcConnector, timeout: Duration) -> FatekPlc {
+    FatekPlc::new(conn, timeout)
+}
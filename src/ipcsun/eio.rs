@@ -0,0 +1,620 @@
+/// ! IPCSUN IO网络控制器通用驱动
+///
+/// 不同型号之间只有点数和回复格式不同，通过 [`EioDevice`] 描述，新增型号只需要声明一个描述。
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::core::{Channel, Transport};
+use crate::modbus::{self, FunctionCode};
+use crate::prelude::*;
+
+/// IPCSUN IO网络控制器型号描述
+///
+/// `IOGETALL` 的回复为 `REPLY_POINTS` 个状态字符 + `\r\n`，
+/// 输出状态从 `OUTPUT_OFFSET` 开始，输入状态从 `INPUT_OFFSET` 开始。
+pub trait EioDevice: Send + Sync + 'static {
+    /// 型号名称
+    const NAME: &'static str;
+    /// 数字量输入点数
+    const INPUTS: u16;
+    /// 继电器输出点数
+    const OUTPUTS: u16;
+    /// `IOGETALL` 回复中状态字符的数量(不含结束符)
+    const REPLY_POINTS: u16;
+    /// 输出状态在回复中的起始位置
+    const OUTPUT_OFFSET: u16;
+    /// 输入状态在回复中的起始位置
+    const INPUT_OFFSET: u16;
+
+    /// 定时输出指令：打开输出点，经过 `duration` 后由控制器自动关闭
+    ///
+    /// * `output` - 输出点的索引，从0开始
+    ///
+    /// 返回 `None` 表示控制器不支持定时输出，由主机定时关闭
+    fn pulse_cmd(output: u16, duration: Duration) -> Option<String> {
+        let _ = (output, duration);
+        None
+    }
+}
+
+/// IPCSUN IO网络控制器通讯协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EioProtocol {
+    /// 白话协议(`IOGETALL`、`OPEN`、`CLOSE`)
+    Text,
+    /// Modbus TCP：输出点为线圈，输入点为离散输入，地址从0开始
+    ModbusTcp,
+}
+
+/// 检查回复数据是否完整的函数
+type CheckFn = fn(&[u8]) -> Result<Result<&[u8], &str>, PlcError>;
+
+/// IPCSUN IO网络控制器，默认使用白话协议，可以通过 [`IpcsunEio::protocol`] 切换为 Modbus TCP。
+pub struct IpcsunEio<D: EioDevice> {
+    /// 通讯通道
+    pub(super) channel: Channel,
+    /// 通讯协议
+    pub(super) protocol: EioProtocol,
+    /// Modbus 单元标识
+    unit_id: u8,
+    /// Modbus 事务标识
+    transaction: Arc<AtomicU16>,
+    /// 型号描述
+    device: PhantomData<D>,
+}
+
+impl<D: EioDevice> Clone for IpcsunEio<D> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            protocol: self.protocol,
+            unit_id: self.unit_id,
+            transaction: self.transaction.clone(),
+            device: PhantomData,
+        }
+    }
+}
+
+impl<D: EioDevice> IpcsunEio<D> {
+    /// 设置通讯协议，需要与IO模块的配置一致
+    pub fn protocol(mut self, protocol: EioProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// 设置 Modbus 单元标识，默认为1
+    pub fn unit_id(mut self, unit_id: u8) -> Self {
+        self.unit_id = unit_id;
+        self
+    }
+
+    /// 使用已经打开的连接代替 `connect`
+    pub fn attach(&mut self, transport: impl Transport + 'static) {
+        self.channel.attach(transport);
+    }
+
+    /// 发送指令并等待IO模块回复
+    ///
+    /// * `check` - 检查回复数据是否完整，返回有效数据
+    pub(super) async fn send_and_receive(
+        &self,
+        buf: &[u8],
+        check: CheckFn,
+    ) -> Result<Vec<u8>, PlcError> {
+        self.channel.send_and_receive(buf, check).await
+    }
+
+    /// 发送 Modbus 请求并返回回复的PDU
+    async fn send_modbus(&self, pdu: &[u8]) -> Result<Vec<u8>, PlcError> {
+        let transaction = self.transaction.fetch_add(1, Ordering::Relaxed);
+        let frame = modbus::create_frame(transaction, self.unit_id, pdu);
+        let reply = self.send_and_receive(&frame, modbus::check_frame).await?;
+        Ok(modbus::frame_pdu(&reply, transaction, self.unit_id)?.to_vec())
+    }
+
+    /// 读取 `IOGETALL` 回复中从 `start` 开始的 `count` 个点，1：打开
+    async fn read_points(&self, start: u16, count: u16) -> Result<Vec<u16>, PlcError> {
+        let (start, end) = (start as usize, (start + count) as usize);
+        if self.protocol == EioProtocol::Text {
+            // 创建读取IO模块数据buffer
+            // ! 2025-05-04 Kim 优化：每个指令增加回车换行符
+            let buf = "IOGETALL\r\n".as_bytes();
+            let states = self.send_and_receive(buf, check_eio_read::<D>).await?;
+            return parse_eio_read(&states[start..end], &DataType::Bit, count);
+        }
+        // Modbus：输出点和输入点分别读取后按回复中的位置合并
+        let mut states = vec![0u16; count as usize];
+        let areas = [
+            (FunctionCode::ReadCoils, D::OUTPUT_OFFSET, D::OUTPUTS),
+            (FunctionCode::ReadDiscreteInputs, D::INPUT_OFFSET, D::INPUTS),
+        ];
+        for (code, offset, points) in areas {
+            let (offset, points) = (offset as usize, points as usize);
+            let first = start.max(offset);
+            let last = end.min(offset + points);
+            if first >= last {
+                continue;
+            }
+            let quantity = (last - first) as u16;
+            let pdu = modbus::read_request(code, (first - offset) as u16, quantity);
+            let reply = self.send_modbus(&pdu).await?;
+            let bits = modbus::parse_bits(code, &reply, quantity)?;
+            states[first - start..last - start].copy_from_slice(&bits);
+        }
+        Ok(states)
+    }
+
+    /// 写入从 `address` 开始的输出点，1：打开
+    async fn write_points(&self, address: u16, states: &[u16]) -> PlcResult {
+        if self.protocol == EioProtocol::Text {
+            for cmd in create_write_cmds(address, states)? {
+                self.send_and_receive(cmd.as_bytes(), check_eio_write)
+                    .await?;
+            }
+            return Ok(());
+        }
+        if states.iter().any(|state| *state > 1) {
+            return Err(PlcError::Param("写入数据只能是 1 或者 0".into()));
+        }
+        let pdu = modbus::write_coils_request(address, states);
+        let reply = self.send_modbus(&pdu).await?;
+        modbus::check_write_reply(FunctionCode::WriteMultipleCoils, &reply, &pdu)
+    }
+}
+
+unsafe impl<D: EioDevice> Send for IpcsunEio<D> {}
+
+unsafe impl<D: EioDevice> Sync for IpcsunEio<D> {}
+
+impl<D: EioDevice> IPlc for IpcsunEio<D> {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        IpcsunEio {
+            channel: Channel::new(conn, timeout),
+            protocol: EioProtocol::Text,
+            unit_id: 1,
+            transaction: Arc::new(AtomicU16::new(0)),
+            device: PhantomData,
+        }
+    }
+
+    async fn connect(&mut self) -> PlcResult {
+        self.channel.connect().await
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    /// 读取IO输入输出状态
+    ///
+    /// # Param
+    /// * `address_name` - 数据起始地址：
+    ///     * `DI1` ~ `DIn` 数字量输入
+    ///     * `DO1` ~ `DOn` 继电器输出
+    ///     * `1` ~ `REPLY_POINTS` 对应 `IOGETALL` 回复中的位置
+    /// * `data_type` - 数据类型：
+    ///     * Bit 每个点一个数据，0：关闭；1：打开
+    ///     * Word 从起始地址开始每16个点组成一个字，起始地址为 bit0
+    /// * `len` - 数据长度，不能超出地址所在的区域
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        let point = parse_point::<D>(address_name.into())?;
+        let (start, points) = point.reply_range::<D>();
+        let max = match data_type {
+            DataType::Bit => points,
+            DataType::Word => points.div_ceil(16),
+        };
+        if len == 0 || len > max {
+            return Err(PlcError::Param(format!("超出读取长度范围[1~{}]", max)));
+        }
+        let count = match data_type {
+            DataType::Bit => len,
+            DataType::Word => (len * 16).min(points),
+        };
+        let states = self.read_points(start, count).await?;
+        match data_type {
+            DataType::Bit => Ok(states),
+            DataType::Word => Ok(bits_to_words(&states)),
+        }
+    }
+
+    /// 控制IO状态
+    ///
+    /// 白话协议中打开和关闭的输出点分别使用 `OPEN`、`CLOSE` 指令发送，Modbus 使用写多个线圈
+    ///
+    /// # Param
+    /// * `address_name` - 数据起始地址：`DO1` ~ `DOn` 或者 `1` ~ `OUTPUTS`，输入点不能写入
+    /// * `data_type` - 数据类型：
+    ///     * Bit 每个点一个数据，0：关闭；1：打开
+    ///     * Word 从起始地址开始每16个点组成一个字，起始地址为 bit0，超出输出点数的位必须为0
+    /// * `datas` - 需要写入的数据
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        // 检查数据起始地址
+        let address = parse_point::<D>(address_name.into())?.output_index::<D>()?;
+        let points = D::OUTPUTS - address;
+        let states = match data_type {
+            DataType::Bit => datas.to_vec(),
+            DataType::Word => words_to_bits(datas, points)?,
+        };
+        if states.is_empty() || states.len() > points as usize {
+            return Err(PlcError::Param(format!(
+                "超出写入长度超出范围[1~{}]",
+                D::OUTPUTS
+            )));
+        }
+        self.write_points(address, &states).await
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+/// IO点地址，索引从0开始
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum EioPoint {
+    /// 数字量输入 `DI1` ~ `DIn`
+    Input(u16),
+    /// 继电器输出 `DO1` ~ `DOn`
+    Output(u16),
+    /// 数字地址 `1` ~ `REPLY_POINTS`，对应 `IOGETALL` 回复中的位置
+    Raw(u16),
+}
+
+impl EioPoint {
+    /// 在 `IOGETALL` 回复中的起始位置及最多可以读取的数量
+    fn reply_range<D: EioDevice>(&self) -> (u16, u16) {
+        match *self {
+            EioPoint::Input(i) => (D::INPUT_OFFSET + i, D::INPUTS - i),
+            EioPoint::Output(i) => (D::OUTPUT_OFFSET + i, D::OUTPUTS - i),
+            EioPoint::Raw(i) => (i, D::REPLY_POINTS - i),
+        }
+    }
+
+    /// 输出点的索引，输入点不能写入
+    pub(super) fn output_index<D: EioDevice>(&self) -> Result<u16, PlcError> {
+        match *self {
+            EioPoint::Output(i) => Ok(i),
+            EioPoint::Raw(i) if i < D::OUTPUTS => Ok(i),
+            EioPoint::Raw(i) => Err(PlcError::Addr(format!(
+                "{} 无效的输出地址[1~{}]\t地址={}",
+                D::NAME,
+                D::OUTPUTS,
+                i + 1
+            ))),
+            EioPoint::Input(i) => Err(PlcError::Addr(format!(
+                "{} 数字量输入不能写入\t地址=DI{}",
+                D::NAME,
+                i + 1
+            ))),
+        }
+    }
+}
+
+/// 解析IO点地址：`DI1` ~ `DIn`、`DO1` ~ `DOn` 或者 `1` ~ `REPLY_POINTS`
+pub(super) fn parse_point<D: EioDevice>(address_name: String) -> Result<EioPoint, PlcError> {
+    let name = address_name.trim().to_uppercase();
+    let (number, max, point): (&str, u16, fn(u16) -> EioPoint) =
+        if let Some(number) = name.strip_prefix("DI") {
+            (number, D::INPUTS, EioPoint::Input)
+        } else if let Some(number) = name.strip_prefix("DO") {
+            (number, D::OUTPUTS, EioPoint::Output)
+        } else {
+            (&name, D::REPLY_POINTS, EioPoint::Raw)
+        };
+    match number.parse::<u16>() {
+        Ok(address) if (1..=max).contains(&address) => Ok(point(address - 1)),
+        _ => Err(PlcError::Addr(format!(
+            "{} 无效的地址[1~{}]\t地址={}",
+            D::NAME,
+            max,
+            address_name
+        ))),
+    }
+}
+
+/// 检查读取IO返回数据是否完整
+/// #Return
+/// * `Ok(OK(buf))` => 数据完整，返回全部状态字符(不含结束符)
+/// * `Ok(Err(err))` => 数据不完整，需要等待接收剩余部分
+/// * `Err(err)` => 接收数据错误
+pub(super) fn check_eio_read<D: EioDevice>(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    let points = D::REPLY_POINTS as usize;
+    // 缓冲区开头可能残留其他指令的回复(例如 OK)，按结束符逐行查找，状态字符在结束符之前
+    let mut start = 0;
+    while let Some(p) = buf[start..].windows(2).position(|w| w == b"\r\n") {
+        let line = &buf[start..start + p];
+        if line.len() >= points {
+            return Ok(Ok(&line[line.len() - points..]));
+        }
+        start += p + 2;
+    }
+    Ok(Err("未找到结束符 0x0D 0x0A"))
+}
+
+/// 检查写入IO返回数据是否完整
+/// #Return
+/// * `Ok(OK(buf))` => 数据完整，且包含完整的数据
+/// * `Ok(Err(err))` => 数据不完整，需要等待接收剩余部分
+/// * `Err(err)` => 接收数据错误
+pub(super) fn check_eio_write(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    if buf.len() < 4 {
+        return Ok(Err("数据未接收完成"));
+    }
+    let target = [0x4F, 0x4B, 0x0D, 0x0A];
+    let r = buf
+        .windows(target.len())
+        .position(|window| window == target);
+    match r {
+        Some(p) => Ok(Ok(&buf[p..(p + 4)])),
+        None => Ok(Err("返回结果中未找到 [0x4F, 0x4B, 0x0D, 0x0A]")),
+    }
+}
+
+/// 解析读取IO返回数据（只有读取的时候才需要解析）
+///
+/// * `buf` - 从起始地址开始的状态字符
+pub(super) fn parse_eio_read(
+    buf: &[u8],
+    data_type: &DataType,
+    len: u16,
+) -> Result<Vec<u16>, PlcError> {
+    let count = match data_type {
+        DataType::Bit => len as usize,
+        DataType::Word => len as usize * 16,
+    };
+    let mut datas: Vec<u16> = Vec::new();
+    for state in buf.iter().take(count) {
+        datas.push(match state {
+            48 => 1, // CLOSE 后是 1
+            49 => 0, // OPEN  后是 0
+            _ => return Result::Err(PlcError::Comm("无效的读取结果".into())),
+        });
+    }
+    match data_type {
+        DataType::Bit => Ok(datas),
+        DataType::Word => Ok(bits_to_words(&datas)),
+    }
+}
+
+/// 每16个点的状态组成一个字，第一个点为 bit0
+fn bits_to_words(bits: &[u16]) -> Vec<u16> {
+    bits.chunks(16)
+        .map(|bits| {
+            bits.iter()
+                .enumerate()
+                .fold(0u16, |word, (i, bit)| word | (bit << i))
+        })
+        .collect()
+}
+
+/// 将字数据拆分为每个点的状态
+///
+/// * `points` - 可以写入的点数，超出部分的位必须为0
+fn words_to_bits(words: &[u16], points: u16) -> Result<Vec<u16>, PlcError> {
+    if words.len() > points.div_ceil(16) as usize {
+        return Err(PlcError::Param(format!(
+            "超出写入长度超出范围[1~{}]",
+            points.div_ceil(16)
+        )));
+    }
+    let mut bits = Vec::new();
+    for (index, word) in words.iter().enumerate() {
+        let count = (points as usize - index * 16).min(16);
+        if count < 16 && word >> count != 0 {
+            return Err(PlcError::Param(format!(
+                "写入数据超出输出点数\t数据={:#06X}",
+                word
+            )));
+        }
+        bits.extend((0..count).map(|i| (word >> i) & 1));
+    }
+    Ok(bits)
+}
+
+/// 控制器定时输出的最长时间，超出时由主机定时关闭
+const MAX_TIMED_OPEN: Duration = Duration::from_millis(65535);
+
+/// 创建定时输出指令 `OPEN<点号>:<毫秒>,`，控制器在打开 `duration` 后自动关闭输出点
+///
+/// * `output` - 输出点的索引，从0开始
+///
+/// 时间为0毫秒或者超过 [`MAX_TIMED_OPEN`] 时返回 `None`
+pub(super) fn create_timed_open_cmd(output: u16, duration: Duration) -> Option<String> {
+    let millis = duration.as_millis();
+    if millis == 0 || duration > MAX_TIMED_OPEN {
+        return None;
+    }
+    Some(format!("OPEN{}:{},\r\n", output + 1, millis))
+}
+
+/// 创建写入指令，打开和关闭的输出点分别生成一条指令
+///
+/// * `address` - 起始输出点的索引，从0开始
+/// * `states` - 每个输出点的状态，0：关闭；1：打开
+fn create_write_cmds(address: u16, states: &[u16]) -> Result<Vec<String>, PlcError> {
+    let mut open = String::from("OPEN");
+    let mut close = String::from("CLOSE");
+    for (index, state) in states.iter().enumerate() {
+        let cmd = match state {
+            0 => &mut close,
+            1 => &mut open,
+            _ => return Err(PlcError::Param("写入数据只能是 1 或者 0".into())),
+        };
+        cmd.push_str(&(address as usize + index + 1).to_string());
+        cmd.push(',');
+    }
+    // ! 2025-05-04 Kim 优化：每个指令增加回车换行符
+    Ok([open, close]
+        .into_iter()
+        .filter(|cmd| cmd.ends_with(','))
+        .map(|cmd| cmd + "\r\n")
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipcsun::{Eio1010G, Eio1608I};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_check_eio_read() {
+        let buf = b"0000000011111111\r\n";
+        assert!(check_eio_read::<Eio1608I>(&buf[..10]).unwrap().is_err());
+        let states = check_eio_read::<Eio1608I>(buf).unwrap().unwrap();
+        assert_eq!(states, b"0000000011111111");
+        let datas = parse_eio_read(&states[6..], &DataType::Bit, 4).unwrap();
+        assert_eq!(datas, [1, 1, 0, 0]);
+
+        let states = check_eio_read::<Eio1010G>(b"0101011111\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(states, b"0101011111");
+
+        // 回复之前残留的数据被跳过
+        let buf = b"OK\r\n0000000011111111\r\n";
+        assert!(check_eio_read::<Eio1608I>(&buf[..12]).unwrap().is_err());
+        let states = check_eio_read::<Eio1608I>(buf).unwrap().unwrap();
+        assert_eq!(states, b"0000000011111111");
+        let states = check_eio_read::<Eio1010G>(b"OK\r\n1\r\n0101011111\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(states, b"0101011111");
+        let states = check_eio_read::<Eio1010G>(b"OK0101011111\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(states, b"0101011111");
+    }
+
+    #[test]
+    fn test_parse_point() {
+        assert_eq!(
+            parse_point::<Eio1010G>("10".into()).unwrap(),
+            EioPoint::Raw(9)
+        );
+        assert_eq!(
+            parse_point::<Eio1010G>("di5".into()).unwrap(),
+            EioPoint::Input(4)
+        );
+        assert_eq!(
+            parse_point::<Eio1010G>("DO1".into()).unwrap(),
+            EioPoint::Output(0)
+        );
+        assert!(parse_point::<Eio1010G>("0".into()).is_err());
+        assert!(parse_point::<Eio1010G>("11".into()).is_err());
+        assert!(parse_point::<Eio1010G>("DI6".into()).is_err());
+        assert!(parse_point::<Eio1010G>("DO0".into()).is_err());
+        assert!(parse_point::<Eio1010G>("X1".into()).is_err());
+    }
+
+    #[test]
+    fn test_point_range() {
+        let point = parse_point::<Eio1608I>("DI3".into()).unwrap();
+        assert_eq!(point.reply_range::<Eio1608I>(), (10, 6));
+        assert!(matches!(
+            point.output_index::<Eio1608I>(),
+            Err(PlcError::Addr(_))
+        ));
+        let point = parse_point::<Eio1608I>("DO8".into()).unwrap();
+        assert_eq!(point.reply_range::<Eio1608I>(), (7, 1));
+        assert_eq!(point.output_index::<Eio1608I>().unwrap(), 7);
+        let point = parse_point::<Eio1608I>("9".into()).unwrap();
+        assert_eq!(point.reply_range::<Eio1608I>(), (8, 8));
+        assert!(point.output_index::<Eio1608I>().is_err());
+    }
+
+    #[test]
+    fn test_word_states() {
+        let states = b"01001111";
+        assert_eq!(
+            parse_eio_read(states, &DataType::Word, 1).unwrap(),
+            [0b0000_1101]
+        );
+        assert_eq!(words_to_bits(&[0b1101], 5).unwrap(), [1, 0, 1, 1, 0]);
+        assert!(words_to_bits(&[0b10_0000], 5).is_err());
+        assert!(words_to_bits(&[0, 0], 16).is_err());
+        assert_eq!(words_to_bits(&[0xFFFF], 16).unwrap(), [1; 16]);
+    }
+
+    #[test]
+    fn test_create_write_cmds() {
+        let cmds = create_write_cmds(2, &[1, 0, 1, 1]).unwrap();
+        assert_eq!(cmds, ["OPEN3,5,6,\r\n", "CLOSE4,\r\n"]);
+        let cmds = create_write_cmds(0, &[0, 0]).unwrap();
+        assert_eq!(cmds, ["CLOSE1,2,\r\n"]);
+    }
+
+    #[test]
+    fn test_create_timed_open_cmd() {
+        let cmd = create_timed_open_cmd(2, Duration::from_millis(500));
+        assert_eq!(cmd.as_deref(), Some("OPEN3:500,\r\n"));
+        let cmd = create_timed_open_cmd(0, Duration::from_millis(65535));
+        assert_eq!(cmd.as_deref(), Some("OPEN1:65535,\r\n"));
+        assert_eq!(create_timed_open_cmd(0, Duration::from_micros(500)), None);
+        assert_eq!(create_timed_open_cmd(0, Duration::from_secs(66)), None);
+        assert_eq!(
+            Eio1608I::pulse_cmd(7, Duration::from_millis(500)).as_deref(),
+            Some("OPEN8:500,\r\n")
+        );
+        assert_eq!(
+            Eio1010G::pulse_cmd(4, Duration::from_millis(20)).as_deref(),
+            Some("OPEN5:20,\r\n")
+        );
+        assert!(create_write_cmds(0, &[1, 2]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_modbus_tcp() {
+        use tokio::net::TcpListener;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let replies: [(&[u8], &[u8]); 3] = [
+                // 读线圈 DO4~DO8
+                (
+                    &[0, 0, 0, 0, 0, 6, 2, 0x01, 0, 3, 0, 5],
+                    &[0, 0, 0, 0, 0, 4, 2, 0x01, 1, 0b10101],
+                ),
+                // 读离散输入 DI1~DI2
+                (
+                    &[0, 1, 0, 0, 0, 6, 2, 0x02, 0, 0, 0, 2],
+                    &[0, 1, 0, 0, 0, 4, 2, 0x02, 1, 0b10],
+                ),
+                // 写线圈 DO2~DO4
+                (
+                    &[0, 2, 0, 0, 0, 8, 2, 0x0F, 0, 1, 0, 3, 1, 0b101],
+                    &[0, 2, 0, 0, 0, 6, 2, 0x0F, 0, 1, 0, 3],
+                ),
+            ];
+            for (request, reply) in replies {
+                let mut buf = vec![0u8; request.len()];
+                socket.read_exact(&mut buf).await.unwrap();
+                assert_eq!(buf, request);
+                socket.write_all(reply).await.unwrap();
+            }
+        });
+        let mut plc = IpcsunEio::<Eio1608I>::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        )
+        .protocol(EioProtocol::ModbusTcp)
+        .unit_id(2);
+        plc.connect().await.unwrap();
+        let r = plc.read("4", DataType::Bit, 7).await.unwrap();
+        assert_eq!(r, [1, 0, 1, 0, 1, 0, 1]);
+        plc.write("DO2", DataType::Bit, &[1, 0, 1]).await.unwrap();
+    }
+}
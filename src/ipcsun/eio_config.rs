@@ -0,0 +1,340 @@
+// ! IPCSUN IO网络控制器 参数配置(网络参数、上电输出状态)
+//
+// 配置指令(白话协议的参数配置指令，均以回车换行结尾)：
+// * `GETIP` => `IP=192.168.1.5,MASK=255.255.255.0,GW=192.168.1.1,PORT=502`
+// * `SETIP=192.168.1.5,255.255.255.0,192.168.1.1,502` => `OK`
+// * `GETPOWERON` => `POWERON=10000000`，每个输出点一个字符，1：上电打开
+// * `SETPOWERON=10000000` => `OK`
+// * `RESET` => `OK`，控制器重启后新的参数生效
+//
+// 指令无效或者参数错误时控制器回复 `ERR`(可能带有错误说明，例如 `ERR:PARAM`)
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+use crate::prelude::*;
+
+use super::eio::{EioDevice, EioProtocol, IpcsunEio};
+
+/// IPCSUN IO网络控制器配置参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EioConfig {
+    /// IP地址
+    pub ip: Ipv4Addr,
+    /// 子网掩码
+    pub mask: Ipv4Addr,
+    /// 网关，`0.0.0.0` 为不使用网关
+    pub gateway: Ipv4Addr,
+    /// 端口
+    pub port: u16,
+    /// 上电后每个输出点的状态，0：关闭；1：打开
+    pub power_on: Vec<u16>,
+}
+
+impl EioConfig {
+    /// 检查配置参数是否有效
+    ///
+    /// # Error
+    /// IP地址、子网掩码、网关或者端口无效，上电状态的数量与输出点数不一致
+    pub fn validate<D: EioDevice>(&self) -> PlcResult {
+        let invalid = |msg: &str| Err(PlcError::Param(format!("{} {}", D::NAME, msg)));
+        let ip = self.ip;
+        if ip.is_unspecified() || ip.is_broadcast() || ip.is_multicast() || ip.is_loopback() {
+            return invalid(&format!("无效的IP地址\tIP={}", ip));
+        }
+        let mask = u32::from(self.mask);
+        if mask == 0 || mask.leading_ones() + mask.trailing_zeros() != 32 {
+            return invalid(&format!("无效的子网掩码\t掩码={}", self.mask));
+        }
+        let host = u32::from(ip) & !mask;
+        if host == 0 || host == !mask {
+            return invalid(&format!("IP地址不能是网络地址或者广播地址\tIP={}", ip));
+        }
+        let gateway = u32::from(self.gateway);
+        if gateway != 0 && (gateway & mask != u32::from(ip) & mask || gateway == u32::from(ip)) {
+            return invalid(&format!("网关与IP地址不在同一网段\t网关={}", self.gateway));
+        }
+        if self.port == 0 {
+            return invalid("端口不能为0");
+        }
+        if self.power_on.len() != D::OUTPUTS as usize {
+            return invalid(&format!("上电状态的数量必须为{}", D::OUTPUTS));
+        }
+        if self.power_on.iter().any(|state| *state > 1) {
+            return invalid("上电状态只能是 1 或者 0");
+        }
+        Ok(())
+    }
+}
+
+impl<D: EioDevice> IpcsunEio<D> {
+    /// 读取控制器的网络参数和上电输出状态(仅支持白话协议)
+    pub async fn read_config(&self) -> Result<EioConfig, PlcError> {
+        self.check_text_protocol()?;
+        let reply = self.send_and_receive(b"GETIP\r\n", check_eio_line).await?;
+        let net = parse_fields(&reply)?;
+        let reply = self
+            .send_and_receive(b"GETPOWERON\r\n", check_eio_line)
+            .await?;
+        let power = parse_fields(&reply)?;
+        let field = |map: &HashMap<String, String>, key: &str| {
+            map.get(key)
+                .cloned()
+                .ok_or_else(|| PlcError::Comm(format!("配置数据中未找到 {}\t数据={:?}", key, map)))
+        };
+        let ip_field = |key: &str| -> Result<Ipv4Addr, PlcError> {
+            let value = field(&net, key)?;
+            value
+                .parse()
+                .map_err(|_| PlcError::Comm(format!("无效的配置数据\t{}={}", key, value)))
+        };
+        let port = field(&net, "PORT")?;
+        let power_on = field(&power, "POWERON")?;
+        Ok(EioConfig {
+            ip: ip_field("IP")?,
+            mask: ip_field("MASK")?,
+            gateway: ip_field("GW")?,
+            port: port
+                .parse()
+                .map_err(|_| PlcError::Comm(format!("无效的配置数据\tPORT={}", port)))?,
+            power_on: power_on
+                .chars()
+                .map(|c| match c {
+                    '0' => Ok(0),
+                    '1' => Ok(1),
+                    _ => Err(PlcError::Comm(format!(
+                        "无效的配置数据\tPOWERON={}",
+                        power_on
+                    ))),
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// 写入控制器的网络参数和上电输出状态(仅支持白话协议)
+    ///
+    /// 写入前会检查参数是否有效，需要调用 [`IpcsunEio::reboot`] 重启控制器后生效
+    pub async fn write_config(&self, config: &EioConfig) -> PlcResult {
+        self.check_text_protocol()?;
+        config.validate::<D>()?;
+        for cmd in create_config_cmds(config) {
+            let reply = self
+                .send_and_receive(cmd.as_bytes(), check_eio_line)
+                .await?;
+            check_ok(&reply)?;
+        }
+        Ok(())
+    }
+
+    /// 重启控制器(仅支持白话协议)，重启后需要重新连接
+    ///
+    /// 重启后连接失效，共用此连接的实例都会断开
+    pub async fn reboot(&mut self) -> PlcResult {
+        self.check_text_protocol()?;
+        let reply = self.send_and_receive(b"RESET\r\n", check_eio_line).await?;
+        check_ok(&reply)?;
+        self.channel.close().await
+    }
+
+    fn check_text_protocol(&self) -> PlcResult {
+        match self.protocol {
+            EioProtocol::Text => Ok(()),
+            EioProtocol::ModbusTcp => Err(PlcError::Param(format!(
+                "{} 参数配置只支持白话协议",
+                D::NAME
+            ))),
+        }
+    }
+}
+
+/// 创建写入配置的指令
+fn create_config_cmds(config: &EioConfig) -> [String; 2] {
+    let power_on: String = config.power_on.iter().map(|s| s.to_string()).collect();
+    [
+        format!(
+            "SETIP={},{},{},{}\r\n",
+            config.ip, config.mask, config.gateway, config.port
+        ),
+        format!("SETPOWERON={}\r\n", power_on),
+    ]
+}
+
+/// 检查配置指令的回复是否完整，返回一行数据(不含结束符)
+fn check_eio_line(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    match buf.windows(2).position(|window| window == b"\r\n") {
+        Some(p) if buf.starts_with(b"ERR") => Err(PlcError::Comm(format!(
+            "控制器返回错误\t数据={}",
+            String::from_utf8_lossy(&buf[..p])
+        ))),
+        Some(p) => Ok(Ok(&buf[..p])),
+        None => Ok(Err("未找到结束符 0x0D 0x0A")),
+    }
+}
+
+/// 检查设置指令的回复是否为 `OK`
+fn check_ok(buf: &[u8]) -> PlcResult {
+    match buf.trim_ascii() {
+        b"OK" => Ok(()),
+        reply => Err(PlcError::Comm(format!(
+            "控制器回复错误\t数据={}",
+            String::from_utf8_lossy(reply)
+        ))),
+    }
+}
+
+/// 解析 `KEY=VALUE,KEY=VALUE` 格式的回复
+fn parse_fields(buf: &[u8]) -> Result<HashMap<String, String>, PlcError> {
+    let line = std::str::from_utf8(buf).map_err(|_| PlcError::Comm("数据解析失败".into()))?;
+    line.trim()
+        .split(',')
+        .map(|field| match field.split_once('=') {
+            Some((key, value)) => Ok((key.trim().to_uppercase(), value.trim().to_string())),
+            None => Err(PlcError::Comm(format!("无效的配置数据\t数据={}", line))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipcsun::{Eio1010G, IpcsunEio1010G};
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn config() -> EioConfig {
+        EioConfig {
+            ip: Ipv4Addr::new(192, 168, 1, 5),
+            mask: Ipv4Addr::new(255, 255, 255, 0),
+            gateway: Ipv4Addr::new(192, 168, 1, 1),
+            port: 502,
+            power_on: vec![1, 0, 0, 0, 0],
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(config().validate::<Eio1010G>().is_ok());
+        let invalid = [
+            EioConfig {
+                ip: Ipv4Addr::new(192, 168, 1, 255),
+                ..config()
+            },
+            EioConfig {
+                mask: Ipv4Addr::new(255, 0, 255, 0),
+                ..config()
+            },
+            EioConfig {
+                gateway: Ipv4Addr::new(192, 168, 2, 1),
+                ..config()
+            },
+            EioConfig {
+                port: 0,
+                ..config()
+            },
+            EioConfig {
+                power_on: vec![1, 0],
+                ..config()
+            },
+        ];
+        for config in invalid {
+            assert!(config.validate::<Eio1010G>().is_err(), "{:?}", config);
+        }
+        let config = EioConfig {
+            gateway: Ipv4Addr::UNSPECIFIED,
+            ..config()
+        };
+        assert!(config.validate::<Eio1010G>().is_ok());
+    }
+
+    #[test]
+    fn test_parse_replies() {
+        let net =
+            parse_fields(b"IP=192.168.1.5,MASK=255.255.255.0,GW=192.168.1.1,PORT=502").unwrap();
+        assert_eq!(net["IP"], "192.168.1.5");
+        assert_eq!(net["MASK"], "255.255.255.0");
+        assert_eq!(net["GW"], "192.168.1.1");
+        assert_eq!(net["PORT"], "502");
+        let power = parse_fields(b"POWERON=10000").unwrap();
+        assert_eq!(power["POWERON"], "10000");
+        assert!(parse_fields(b"POWERON").is_err());
+        // SETIP、SETPOWERON、RESET 的回复
+        assert_eq!(check_eio_line(b"OK\r\n").unwrap().unwrap(), b"OK");
+        assert!(check_ok(b"OK").is_ok());
+        assert!(check_ok(b"FAIL").is_err());
+        assert!(check_eio_line(b"OK").unwrap().is_err());
+        assert!(matches!(
+            check_eio_line(b"ERR:PARAM\r\n"),
+            Err(PlcError::Comm(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_write_config_err() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+            let mut line = String::new();
+            socket.read_line(&mut line).await.unwrap();
+            socket.write_all(b"ERR\r\n").await.unwrap();
+            // 保持连接直到客户端断开
+            let _ = socket.read_line(&mut line).await;
+        });
+        let mut plc = IpcsunEio1010G::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_secs(5),
+        );
+        plc.connect().await.unwrap();
+        let start = std::time::Instant::now();
+        let r = plc.write_config(&config()).await;
+        assert!(matches!(r, Err(PlcError::Comm(_))));
+        // 不等待超时
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_read_write_config() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+            let replies = [
+                (
+                    "GETIP",
+                    "IP=192.168.1.5,MASK=255.255.255.0,GW=192.168.1.1,PORT=502",
+                ),
+                ("GETPOWERON", "POWERON=10000"),
+                ("SETIP=192.168.1.5,255.255.255.0,192.168.1.1,502", "OK"),
+                ("SETPOWERON=10000", "OK"),
+                ("RESET", "OK"),
+            ];
+            for (cmd, reply) in replies {
+                let mut line = String::new();
+                socket.read_line(&mut line).await.unwrap();
+                assert_eq!(line, format!("{}\r\n", cmd));
+                socket
+                    .write_all(format!("{}\r\n", reply).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        let mut plc = IpcsunEio1010G::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        plc.connect().await.unwrap();
+        let read = plc.read_config().await.unwrap();
+        assert_eq!(read, config());
+        let invalid = EioConfig {
+            port: 0,
+            ..config()
+        };
+        assert!(plc.write_config(&invalid).await.is_err());
+        plc.write_config(&read).await.unwrap();
+        plc.reboot().await.unwrap();
+        assert!(!plc.is_connect());
+    }
+}
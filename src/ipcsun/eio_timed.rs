@@ -0,0 +1,291 @@
+// ! IPCSUN IO网络控制器 定时输出(脉冲/闪烁)
+
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, sleep_until, Instant};
+use tracing::{event, Level};
+
+use crate::prelude::*;
+
+use super::eio::{check_eio_write, parse_point, EioDevice, EioProtocol, IpcsunEio};
+
+/// 定时输出任务
+///
+/// 脉冲或者闪烁结束后由后台任务关闭输出点。
+/// * 脉冲输出：实例被释放后脉冲继续输出到结束
+/// * 闪烁输出：实例被释放时立即关闭输出点
+///
+/// 调用 [`EioTimedOutput::stop`] 立即关闭输出点，调用 [`EioTimedOutput::wait`] 等待结束。
+#[must_use = "闪烁输出实例被释放时会立即关闭输出点"]
+pub struct EioTimedOutput {
+    /// 停止信号，发送后后台任务会关闭输出点；释放时闪烁输出关闭，脉冲输出继续到结束
+    stop: Option<oneshot::Sender<()>>,
+    /// 后台任务
+    task: Option<JoinHandle<PlcResult>>,
+}
+
+impl EioTimedOutput {
+    /// 等待输出结束(闪烁输出不会自动结束)
+    pub async fn wait(mut self) -> PlcResult {
+        let task = self.task.take();
+        join(task).await
+    }
+
+    /// 立即关闭输出点并等待后台任务结束
+    pub async fn stop(mut self) -> PlcResult {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        let task = self.task.take();
+        join(task).await
+    }
+
+    /// 输出是否已经结束
+    pub fn is_finished(&self) -> bool {
+        self.task.as_ref().is_none_or(|task| task.is_finished())
+    }
+}
+
+async fn join(task: Option<JoinHandle<PlcResult>>) -> PlcResult {
+    match task {
+        Some(task) => task
+            .await
+            .map_err(|err| PlcError::Comm(format!("定时输出任务异常\t{}", err)))?,
+        None => Ok(()),
+    }
+}
+
+impl<D: EioDevice> IpcsunEio<D> {
+    /// 脉冲输出：打开输出点，经过 `duration` 后关闭
+    ///
+    /// 使用白话协议且控制器支持定时输出时由控制器关闭(主机异常退出也会关闭)，否则由主机的后台任务关闭。
+    /// 释放返回的实例不会中断脉冲。
+    ///
+    /// # Param
+    /// * `address_name` - 输出点地址：`DO1` ~ `DOn` 或者 `1` ~ `OUTPUTS`
+    /// * `duration` - 打开的时间
+    pub async fn pulse(
+        &self,
+        address_name: impl Into<String>,
+        duration: Duration,
+    ) -> Result<EioTimedOutput, PlcError> {
+        let output = parse_point::<D>(address_name.into())?.output_index::<D>()?;
+        if duration.is_zero() {
+            return Err(PlcError::Param("脉冲时间不能为0".into()));
+        }
+        let cmd = match self.protocol {
+            EioProtocol::Text => D::pulse_cmd(output, duration),
+            EioProtocol::ModbusTcp => None,
+        };
+        let hardware = match cmd {
+            Some(cmd) => {
+                self.send_and_receive(cmd.as_bytes(), check_eio_write)
+                    .await?;
+                true
+            }
+            None => {
+                self.write_output(output, 1).await?;
+                false
+            }
+        };
+        Ok(self.spawn_timed(output, duration, None, hardware))
+    }
+
+    /// 闪烁输出：按 `on` 打开、`off` 关闭循环输出，直到停止或者实例被释放
+    ///
+    /// # Param
+    /// * `address_name` - 输出点地址：`DO1` ~ `DOn` 或者 `1` ~ `OUTPUTS`
+    /// * `on` - 每次打开的时间
+    /// * `off` - 每次关闭的时间
+    pub async fn flash(
+        &self,
+        address_name: impl Into<String>,
+        on: Duration,
+        off: Duration,
+    ) -> Result<EioTimedOutput, PlcError> {
+        let output = parse_point::<D>(address_name.into())?.output_index::<D>()?;
+        if on.is_zero() || off.is_zero() {
+            return Err(PlcError::Param("闪烁时间不能为0".into()));
+        }
+        self.write_output(output, 1).await?;
+        Ok(self.spawn_timed(output, on, Some(off), false))
+    }
+
+    async fn write_output(&self, output: u16, state: u16) -> PlcResult {
+        self.write(format!("DO{}", output + 1), DataType::Bit, &[state])
+            .await
+    }
+
+    /// 启动后台任务，调用前输出点已经打开
+    ///
+    /// * `off` - 闪烁输出的关闭时间，`None` 为脉冲输出
+    /// * `hardware` - 脉冲输出是否由控制器关闭
+    fn spawn_timed(
+        &self,
+        output: u16,
+        on: Duration,
+        off: Option<Duration>,
+        hardware: bool,
+    ) -> EioTimedOutput {
+        let (tx, mut rx) = oneshot::channel::<()>();
+        let plc = self.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let deadline = Instant::now() + on;
+                let stopped = tokio::select! {
+                    _ = sleep_until(deadline) => false,
+                    r = &mut rx => match (r, off) {
+                        (Ok(()), _) => true,
+                        // 脉冲输出的实例被释放，继续输出到结束
+                        (Err(_), None) => {
+                            sleep_until(deadline).await;
+                            false
+                        }
+                        // 闪烁输出的实例被释放，立即关闭
+                        (Err(_), Some(_)) => true,
+                    },
+                };
+                // 控制器定时关闭的脉冲正常结束时不需要再关闭
+                if !hardware || stopped {
+                    if let Err(err) = plc.write_output(output, 0).await {
+                        event!(
+                            Level::ERROR,
+                            "定时输出关闭失败\t输出=DO{}\t{}",
+                            output + 1,
+                            err
+                        );
+                        return Err(err);
+                    }
+                }
+                let Some(off) = off else {
+                    return Ok(());
+                };
+                if stopped {
+                    return Ok(());
+                }
+                tokio::select! {
+                    _ = sleep(off) => {},
+                    _ = &mut rx => return Ok(()),
+                };
+                plc.write_output(output, 1).await?;
+            }
+        });
+        EioTimedOutput {
+            stop: Some(tx),
+            task: Some(task),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipcsun::IpcsunEio1608I;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// 读取一条指令(以换行符结尾)并回复OK
+    async fn reply_ok(socket: &mut TcpStream) -> String {
+        let mut cmd = Vec::new();
+        loop {
+            let b = socket.read_u8().await.unwrap();
+            cmd.push(b);
+            if b == b'\n' {
+                socket.write_all(b"OK\r\n").await.unwrap();
+                return String::from_utf8(cmd).unwrap();
+            }
+        }
+    }
+
+    async fn connect_mock() -> (IpcsunEio1608I, TcpListener) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let plc = IpcsunEio1608I::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        (plc, listener)
+    }
+
+    /// 不支持定时输出的控制器
+    struct NoTimer;
+
+    impl EioDevice for NoTimer {
+        const NAME: &'static str = "NOTIMER";
+        const INPUTS: u16 = 8;
+        const OUTPUTS: u16 = 8;
+        const REPLY_POINTS: u16 = 16;
+        const OUTPUT_OFFSET: u16 = 0;
+        const INPUT_OFFSET: u16 = 8;
+    }
+
+    #[tokio::test]
+    async fn test_pulse() {
+        let (mut plc, listener) = connect_mock().await;
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // 控制器定时关闭，主机不发送 CLOSE
+            assert_eq!(reply_ok(&mut socket).await, "OPEN3:50,\r\n");
+            // 提前停止时由主机关闭
+            assert_eq!(reply_ok(&mut socket).await, "OPEN4:60000,\r\n");
+            assert_eq!(reply_ok(&mut socket).await, "CLOSE4,\r\n");
+            // 超出控制器定时范围时由主机定时关闭
+            assert_eq!(reply_ok(&mut socket).await, "OPEN5,\r\n");
+            assert_eq!(reply_ok(&mut socket).await, "CLOSE5,\r\n");
+        });
+        plc.connect().await.unwrap();
+        assert!(plc.pulse("DI1", Duration::from_millis(50)).await.is_err());
+        let pulse = plc.pulse("DO3", Duration::from_millis(50)).await.unwrap();
+        pulse.wait().await.unwrap();
+        let pulse = plc.pulse("DO4", Duration::from_secs(60)).await.unwrap();
+        assert!(!pulse.is_finished());
+        pulse.stop().await.unwrap();
+        let pulse = plc.pulse("DO5", Duration::from_secs(70)).await.unwrap();
+        pulse.stop().await.unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pulse_drop() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut plc = IpcsunEio::<NoTimer>::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            assert_eq!(reply_ok(&mut socket).await, "OPEN2,\r\n");
+            let start = std::time::Instant::now();
+            assert_eq!(reply_ok(&mut socket).await, "CLOSE2,\r\n");
+            // 释放实例后脉冲继续输出到结束
+            assert!(start.elapsed() >= Duration::from_millis(70));
+        });
+        plc.connect().await.unwrap();
+        let pulse = plc.pulse("DO2", Duration::from_millis(80)).await.unwrap();
+        drop(pulse);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flash() {
+        let (mut plc, listener) = connect_mock().await;
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            for _ in 0..2 {
+                assert_eq!(reply_ok(&mut socket).await, "OPEN1,\r\n");
+                assert_eq!(reply_ok(&mut socket).await, "CLOSE1,\r\n");
+            }
+        });
+        plc.connect().await.unwrap();
+        let flash = plc
+            .flash("1", Duration::from_millis(40), Duration::from_millis(40))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        flash.stop().await.unwrap();
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+// ! IPCSUN IO网络控制器 输入点变化监视
+
+use std::time::{Duration, SystemTime};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, MissedTickBehavior};
+use tracing::{event, Level};
+
+use crate::core::{open_transport, Transport};
+use crate::prelude::*;
+
+use super::eio::{parse_eio_read, EioDevice, EioProtocol, IpcsunEio};
+
+/// 事件通道的缓存数量
+const CHANNEL_SIZE: usize = 64;
+
+/// 主动上传模式断开后重新连接的间隔
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// 主动上传模式超过此时间没有收到数据时发送 `IOGETALL` 确认连接
+const HEARTBEAT_IDLE: Duration = Duration::from_secs(5);
+
+/// 输入点监视方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EioWatchMode {
+    /// 按周期发送 `IOGETALL` 查询，与读写共用连接
+    Poll(Duration),
+    /// 控制器已配置为主动上传，建立单独的连接接收上传数据
+    ///
+    /// 上传数据的格式与 `IOGETALL` 的回复一致。长时间没有上传数据时发送 `IOGETALL` 确认连接，
+    /// 超时没有回复则认为连接断开并重新连接
+    ActiveUpload,
+}
+
+/// 输入点变化方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EioEdge {
+    /// 上升沿 OFF -> ON
+    Rising,
+    /// 下降沿 ON -> OFF
+    Falling,
+}
+
+/// 输入点变化事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EioInputEvent {
+    /// 输入点编号，对应 `DI1` ~ `DIn`
+    pub input: u16,
+    /// 变化方向
+    pub edge: EioEdge,
+    /// 收到状态的时间
+    pub timestamp: SystemTime,
+}
+
+/// 输入点监视任务，实例被释放时停止监视
+pub struct EioInputWatch {
+    rx: mpsc::Receiver<Result<EioInputEvent, PlcError>>,
+    task: JoinHandle<()>,
+}
+
+impl EioInputWatch {
+    /// 接收下一个事件
+    ///
+    /// 通讯错误也会通过事件返回，监视任务会继续运行
+    pub async fn recv(&mut self) -> Option<Result<EioInputEvent, PlcError>> {
+        self.rx.recv().await
+    }
+}
+
+impl Drop for EioInputWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl<D: EioDevice> IpcsunEio<D> {
+    /// 监视全部数字量输入的变化
+    ///
+    /// 第一次读取到的状态作为初始状态，之后每次状态变化时发送上升沿/下降沿事件。
+    ///
+    /// # Param
+    /// * `mode` - 监视方式，查询方式需要先连接控制器，主动上传只支持白话协议
+    pub async fn watch_inputs(&self, mode: EioWatchMode) -> Result<EioInputWatch, PlcError> {
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        let task = match mode {
+            EioWatchMode::Poll(period) => {
+                if period.is_zero() {
+                    return Err(PlcError::Param("查询周期不能为0".into()));
+                }
+                if !self.is_connect() {
+                    return Err(PlcError::NotConnect);
+                }
+                tokio::spawn(poll_inputs(self.clone(), period, tx))
+            }
+            EioWatchMode::ActiveUpload => {
+                if self.protocol != EioProtocol::Text {
+                    return Err(PlcError::Param("主动上传只支持白话协议".into()));
+                }
+                let stream = open_transport(self.channel.conn(), self.channel.timeout()).await?;
+                tokio::spawn(receive_inputs::<D>(
+                    self.channel.conn().clone(),
+                    self.channel.timeout(),
+                    stream,
+                    tx,
+                ))
+            }
+        };
+        Ok(EioInputWatch { rx, task })
+    }
+}
+
+type EventSender = mpsc::Sender<Result<EioInputEvent, PlcError>>;
+
+/// 查询方式：按周期读取输入点状态
+async fn poll_inputs<D: EioDevice>(plc: IpcsunEio<D>, period: Duration, tx: EventSender) {
+    let mut last = None;
+    let mut ticker = tokio::time::interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        ticker.tick().await;
+        let r = plc.read("DI1", DataType::Bit, D::INPUTS).await;
+        if !send_changes(&tx, &mut last, r).await {
+            return;
+        }
+    }
+}
+
+/// 主动上传方式：接收控制器上传的状态，断开后自动重新连接
+async fn receive_inputs<D: EioDevice>(
+    conn: PlcConnector,
+    time: Duration,
+    stream: Box<dyn Transport>,
+    tx: EventSender,
+) {
+    let mut last = None;
+    let mut stream = Some(stream);
+    let mut buf = Vec::new();
+    // 已发送 `IOGETALL`，等待回复
+    let mut heartbeat = false;
+    loop {
+        let Some(client) = stream.as_mut() else {
+            sleep(RECONNECT_DELAY).await;
+            match open_transport(&conn, time).await {
+                Ok(client) => stream = Some(client),
+                Err(err) => {
+                    if tx.send(Err(err)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            continue;
+        };
+        let mut chunk = [0u8; 256];
+        let idle = if heartbeat { time } else { HEARTBEAT_IDLE };
+        let err = match timeout(idle, client.read(&mut chunk)).await {
+            Ok(Ok(0)) => PlcError::Comm("控制器断开连接".into()),
+            Ok(Ok(n)) => {
+                heartbeat = false;
+                buf.extend_from_slice(&chunk[..n]);
+                while let Some(states) = take_frame::<D>(&mut buf) {
+                    let start = D::INPUT_OFFSET as usize;
+                    let r = parse_eio_read(&states[start..], &DataType::Bit, D::INPUTS);
+                    if !send_changes(&tx, &mut last, r).await {
+                        return;
+                    }
+                }
+                continue;
+            }
+            Ok(Err(err)) => PlcError::Io(err),
+            // 半开连接：查询没有回复
+            Err(_) if heartbeat => PlcError::Timeout,
+            Err(_) => match client.write_all(b"IOGETALL\r\n").await {
+                Ok(()) => {
+                    heartbeat = true;
+                    continue;
+                }
+                Err(err) => PlcError::Io(err),
+            },
+        };
+        event!(Level::WARN, "主动上传连接断开，重新连接\t{}", err);
+        stream = None;
+        heartbeat = false;
+        buf.clear();
+        if tx.send(Err(err)).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// 与上一次的状态比较并发送事件，接收端已关闭时返回 false
+async fn send_changes(
+    tx: &EventSender,
+    last: &mut Option<Vec<u16>>,
+    r: Result<Vec<u16>, PlcError>,
+) -> bool {
+    let timestamp = SystemTime::now();
+    let states = match r {
+        Ok(states) => states,
+        Err(err) => return tx.send(Err(err)).await.is_ok(),
+    };
+    if let Some(prev) = last.as_deref() {
+        for event in diff_inputs(prev, &states, timestamp) {
+            if tx.send(Ok(event)).await.is_err() {
+                return false;
+            }
+        }
+    }
+    *last = Some(states);
+    !tx.is_closed()
+}
+
+/// 比较两次的输入状态，生成变化事件
+fn diff_inputs(prev: &[u16], states: &[u16], timestamp: SystemTime) -> Vec<EioInputEvent> {
+    prev.iter()
+        .zip(states)
+        .enumerate()
+        .filter(|(_, (prev, state))| prev != state)
+        .map(|(index, (_, state))| EioInputEvent {
+            input: index as u16 + 1,
+            edge: if *state == 1 {
+                EioEdge::Rising
+            } else {
+                EioEdge::Falling
+            },
+            timestamp,
+        })
+        .collect()
+}
+
+/// 从接收缓存中取出一帧完整的状态数据(不含结束符)，不完整的行会被丢弃
+fn take_frame<D: EioDevice>(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let points = D::REPLY_POINTS as usize;
+    loop {
+        let end = buf.windows(2).position(|window| window == b"\r\n")?;
+        let line: Vec<u8> = buf.drain(..end + 2).take(end).collect();
+        if line.len() >= points {
+            return Some(line[line.len() - points..].to_vec());
+        }
+        event!(Level::DEBUG, "丢弃无效的上传数据\t数据={:?}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipcsun::{Eio1010G, IpcsunEio1010G};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_diff_inputs() {
+        let now = SystemTime::now();
+        let events = diff_inputs(&[0, 1, 0], &[1, 1, 0], now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].input, 1);
+        assert_eq!(events[0].edge, EioEdge::Rising);
+        let events = diff_inputs(&[1, 1, 0], &[1, 0, 1], now);
+        let edges: Vec<_> = events.iter().map(|e| (e.input, e.edge)).collect();
+        assert_eq!(edges, [(2, EioEdge::Falling), (3, EioEdge::Rising)]);
+    }
+
+    #[test]
+    fn test_take_frame() {
+        let mut buf = b"OK\r\n0000011111\r\n00000".to_vec();
+        assert_eq!(take_frame::<Eio1010G>(&mut buf).unwrap(), b"0000011111");
+        assert!(take_frame::<Eio1010G>(&mut buf).is_none());
+        assert_eq!(buf, b"00000");
+    }
+
+    #[tokio::test]
+    async fn test_watch_poll() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // 输入 DI2 打开后关闭，'0' 为打开
+            for reply in ["1111111111", "1111110111", "1111111111"] {
+                let mut cmd = [0u8; 10];
+                socket.read_exact(&mut cmd).await.unwrap();
+                assert_eq!(&cmd, b"IOGETALL\r\n");
+                socket.write_all(reply.as_bytes()).await.unwrap();
+                socket.write_all(b"\r\n").await.unwrap();
+            }
+        });
+        let mut plc = IpcsunEio1010G::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        let mode = EioWatchMode::Poll(Duration::from_millis(10));
+        assert!(plc.watch_inputs(mode).await.is_err());
+        plc.connect().await.unwrap();
+        let mut watch = plc.watch_inputs(mode).await.unwrap();
+        let event = watch.recv().await.unwrap().unwrap();
+        assert_eq!((event.input, event.edge), (2, EioEdge::Rising));
+        let event = watch.recv().await.unwrap().unwrap();
+        assert_eq!((event.input, event.edge), (2, EioEdge::Falling));
+        // 服务端关闭后返回通讯错误
+        assert!(watch.recv().await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_watch_active_upload() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(b"1111111111\r\n11111").await.unwrap();
+            socket.write_all(b"11110\r\n").await.unwrap();
+            sleep(Duration::from_secs(1)).await;
+        });
+        let plc = IpcsunEio1010G::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        let mut watch = plc.watch_inputs(EioWatchMode::ActiveUpload).await.unwrap();
+        let event = watch.recv().await.unwrap().unwrap();
+        assert_eq!((event.input, event.edge), (5, EioEdge::Rising));
+    }
+
+    #[tokio::test]
+    async fn test_watch_silent_peer() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            // 第一个连接只接收数据不回复，空闲后收到查询
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut cmd = [0u8; 10];
+            socket.read_exact(&mut cmd).await.unwrap();
+            assert_eq!(&cmd, b"IOGETALL\r\n");
+            // 超时后重新连接
+            let (_socket, _) = listener.accept().await.unwrap();
+            socket
+        });
+        let plc = IpcsunEio1010G::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        let mut watch = plc.watch_inputs(EioWatchMode::ActiveUpload).await.unwrap();
+        let r = watch.recv().await.unwrap();
+        assert!(matches!(r, Err(PlcError::Timeout)));
+        server.await.unwrap();
+    }
+}
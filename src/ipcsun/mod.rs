@@ -1,8 +1,16 @@
+mod eio;
 mod eio1010g;
 mod eio1608i;
+mod eio_config;
+mod eio_timed;
+mod eio_watch;
 use crate::{core::PlcConnector, IPlc};
-pub use eio1010g::IpcsunEio1010G;
-pub use eio1608i::IpcsunEio1608I;
+pub use eio::{EioDevice, EioProtocol, IpcsunEio};
+pub use eio1010g::{Eio1010G, IpcsunEio1010G};
+pub use eio1608i::{Eio1608I, IpcsunEio1608I};
+pub use eio_config::EioConfig;
+pub use eio_timed::EioTimedOutput;
+pub use eio_watch::{EioEdge, EioInputEvent, EioInputWatch, EioWatchMode};
 use std::time::Duration;
 
 /// 创建一个 ipcsun 网口IO (16口)
@@ -10,6 +18,7 @@ pub fn new_eio1608i_tcp_plc(conn: PlcConnector, timeout: Duration) -> IpcsunEio1
     IpcsunEio1608I::new(conn, timeout)
 }
 
+/// 创建一个 ipcsun 网口IO (10口)
 pub fn new_eio1010g_tcp_plc(conn: PlcConnector, timeout: Duration) -> IpcsunEio1010G {
     IpcsunEio1010G::new(conn, timeout)
 }
@@ -0,0 +1,484 @@
+// ! 基恩士 KV 上位链路协议(ASCII)
+//
+// 指令以回车(CR)结尾，回复以回车换行(CR LF)结尾：
+// * `RD DM100` => `00123`
+// * `RDS DM100 3` => `00001 00002 00003`
+// * `WR DM100 123` => `OK`
+// * `WRS DM100 3 1 2 3` => `OK`
+// * 错误时回复 `E0`~`E6`
+
+use crate::prelude::*;
+
+/// 数据格式后缀
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KvFormat {
+    /// `.U` 16位无符号十进制
+    Unsigned,
+    /// `.S` 16位有符号十进制
+    Signed,
+    /// `.D` 32位无符号十进制
+    UnsignedDouble,
+    /// `.L` 32位有符号十进制
+    SignedDouble,
+    /// `.H` 16位十六进制
+    Hex,
+}
+
+impl KvFormat {
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "U" => Some(KvFormat::Unsigned),
+            "S" => Some(KvFormat::Signed),
+            "D" => Some(KvFormat::UnsignedDouble),
+            "L" => Some(KvFormat::SignedDouble),
+            "H" => Some(KvFormat::Hex),
+            _ => None,
+        }
+    }
+
+    /// 指令中使用的后缀
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            KvFormat::Unsigned => ".U",
+            KvFormat::Signed => ".S",
+            KvFormat::UnsignedDouble => ".D",
+            KvFormat::SignedDouble => ".L",
+            KvFormat::Hex => ".H",
+        }
+    }
+
+    /// 每个数据占用的字数
+    pub fn words(&self) -> u16 {
+        match self {
+            KvFormat::UnsignedDouble | KvFormat::SignedDouble => 2,
+            _ => 1,
+        }
+    }
+
+    /// 一次最多读写的数据数量
+    pub fn max_count(&self) -> u16 {
+        1000 / self.words()
+    }
+
+    /// 数据转换为指令中的文本，32位数据为低字在前的两个字
+    fn format(&self, words: &[u16]) -> String {
+        match self {
+            KvFormat::Unsigned => words[0].to_string(),
+            KvFormat::Signed => (words[0] as i16).to_string(),
+            KvFormat::Hex => format!("{:04X}", words[0]),
+            KvFormat::UnsignedDouble => double(words).to_string(),
+            KvFormat::SignedDouble => (double(words) as i32).to_string(),
+        }
+    }
+
+    /// 回复中的文本转换为数据，32位数据转换为低字在前的两个字
+    fn parse(&self, text: &str) -> Option<Vec<u16>> {
+        let value = match self {
+            KvFormat::Unsigned => return text.parse::<u16>().ok().map(|v| vec![v]),
+            KvFormat::Signed => return text.parse::<i16>().ok().map(|v| vec![v as u16]),
+            KvFormat::Hex => return u16::from_str_radix(text, 16).ok().map(|v| vec![v]),
+            KvFormat::UnsignedDouble => text.parse::<u32>().ok()?,
+            KvFormat::SignedDouble => text.parse::<i32>().ok()? as u32,
+        };
+        Some(vec![value as u16, (value >> 16) as u16])
+    }
+}
+
+fn double(words: &[u16]) -> u32 {
+    words[0] as u32 | (words[1] as u32) << 16
+}
+
+/// KV 软元件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KvDevice {
+    /// 数据存储器
+    Dm,
+    /// 扩展数据存储器
+    Em,
+    /// 文件寄存器
+    Fm,
+    /// 继电器
+    R,
+    /// 内部辅助继电器
+    Mr,
+    /// 锁存继电器
+    Lr,
+    /// 控制继电器
+    Cr,
+    /// 定时器
+    T,
+    /// 计数器
+    C,
+}
+
+impl KvDevice {
+    fn from_header(header: &str) -> Option<Self> {
+        match header {
+            "DM" => Some(KvDevice::Dm),
+            "EM" => Some(KvDevice::Em),
+            "FM" => Some(KvDevice::Fm),
+            "R" => Some(KvDevice::R),
+            "MR" => Some(KvDevice::Mr),
+            "LR" => Some(KvDevice::Lr),
+            "CR" => Some(KvDevice::Cr),
+            "T" => Some(KvDevice::T),
+            "C" => Some(KvDevice::C),
+            _ => None,
+        }
+    }
+
+    /// 指令中使用的软元件名称
+    pub fn header(&self) -> &'static str {
+        match self {
+            KvDevice::Dm => "DM",
+            KvDevice::Em => "EM",
+            KvDevice::Fm => "FM",
+            KvDevice::R => "R",
+            KvDevice::Mr => "MR",
+            KvDevice::Lr => "LR",
+            KvDevice::Cr => "CR",
+            KvDevice::T => "T",
+            KvDevice::C => "C",
+        }
+    }
+
+    /// 是否为继电器(编号为通道 + 两位位号 00~15)
+    pub fn is_relay(&self) -> bool {
+        matches!(
+            self,
+            KvDevice::R | KvDevice::Mr | KvDevice::Lr | KvDevice::Cr
+        )
+    }
+
+    /// 是否为定时器或者计数器，读取时回复 `接点,当前值`
+    pub fn is_timer(&self) -> bool {
+        matches!(self, KvDevice::T | KvDevice::C)
+    }
+
+    /// 最大编号(KV-8000)
+    fn max_number(&self) -> u32 {
+        match self {
+            KvDevice::Dm | KvDevice::Em => 65534,
+            KvDevice::Fm => 32767,
+            KvDevice::R => 199915,
+            KvDevice::Mr => 399915,
+            KvDevice::Lr => 99915,
+            KvDevice::Cr => 7915,
+            KvDevice::T | KvDevice::C => 3999,
+        }
+    }
+}
+
+/// KV 寄存器地址
+///
+/// 支持 `DM`、`EM`、`FM`、`R`、`MR`、`LR`、`CR`、`T`、`C`，可以带数据格式后缀 `.U/.S/.D/.L/.H`：
+/// * 按字：`DM100`、`DM100.S`、`EM0.D`、`R1000.U`(从 R1000 开始的16个继电器组成一个字)、`T0`(当前值)
+/// * 按位：`R1000`(通道10的第0位)、`MR315`、`LR0`、`CR2002`、`T0`(接点)
+///
+/// 按字读写时未指定后缀默认为 `.U`，定时器和计数器默认为 `.D`；`.D/.L` 每个数据为低字在前的两个字
+pub struct KvAddress {
+    address_name: String,
+    data_type: DataType,
+    /// 软元件
+    inner_device: KvDevice,
+    /// 编号，继电器为 通道 * 100 + 位号
+    inner_number: u32,
+    /// 数据格式，按位读写时为 None
+    inner_format: Option<KvFormat>,
+}
+
+impl KvAddress {
+    /// 实例化一个寄存器地址
+    ///
+    /// # Error
+    /// 无效的寄存器地址，或者地址与数据类型不一致
+    pub fn new(address_name: impl Into<String>, data_type: DataType) -> Result<Self, PlcError> {
+        let address_name = address_name.into().trim().to_uppercase();
+        let invalid = || PlcError::Addr(format!("KV 无效的寄存器地址\t寄存器={}", &address_name));
+        let (device, suffix) = match address_name.split_once('.') {
+            Some((device, suffix)) => (device, Some(suffix)),
+            None => (address_name.as_str(), None),
+        };
+        let split = device
+            .find(|c: char| c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (header, number) = device.split_at(split);
+        let device = KvDevice::from_header(header).ok_or_else(invalid)?;
+        if !number.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let number = number.parse::<u32>().map_err(|_| invalid())?;
+        if number > device.max_number() || (device.is_relay() && number % 100 > 15) {
+            return Err(invalid());
+        }
+        let format = match (suffix, &data_type) {
+            (Some(suffix), DataType::Word) => {
+                Some(KvFormat::from_suffix(suffix).ok_or_else(invalid)?)
+            }
+            (None, DataType::Word) if device.is_timer() => Some(KvFormat::UnsignedDouble),
+            (None, DataType::Word) => Some(KvFormat::Unsigned),
+            (None, DataType::Bit) if device.is_relay() || device.is_timer() => None,
+            (Some(_), DataType::Bit) => {
+                return Err(PlcError::Addr(format!(
+                    "KV 按位读写时不能指定数据格式\t寄存器={}",
+                    &address_name
+                )))
+            }
+            (None, DataType::Bit) => {
+                return Err(PlcError::Addr(format!(
+                    "KV 数据存储器不能按位读写\t寄存器={}",
+                    &address_name
+                )))
+            }
+        };
+        Ok(Self {
+            address_name,
+            data_type,
+            inner_device: device,
+            inner_number: number,
+            inner_format: format,
+        })
+    }
+
+    /// 软元件
+    pub fn device(&self) -> KvDevice {
+        self.inner_device
+    }
+
+    /// 编号，继电器为 通道 * 100 + 位号
+    pub fn number(&self) -> u32 {
+        self.inner_number
+    }
+
+    /// 数据格式，按位读写时为 None
+    pub fn format(&self) -> Option<KvFormat> {
+        self.inner_format
+    }
+
+    /// 从当前地址偏移 `offset` 个数据后的软元件名称(含后缀)
+    ///
+    /// 继电器每个通道16个位；按字读写继电器时每个数据为一个通道
+    pub fn device_name(&self, offset: u32) -> Result<String, PlcError> {
+        let device = self.inner_device;
+        let number = match (device.is_relay(), self.inner_format) {
+            (true, format) => {
+                let step = if format.is_some() { 16 } else { 1 };
+                let bit = (self.inner_number / 100) * 16 + self.inner_number % 100 + offset * step;
+                (bit / 16) * 100 + bit % 16
+            }
+            (false, format) => {
+                let words = format.map_or(1, |format| format.words()) as u32;
+                self.inner_number + offset * if device.is_timer() { 1 } else { words }
+            }
+        };
+        if number > device.max_number() {
+            return Err(PlcError::Addr(format!(
+                "KV 读写范围超出地址范围\t寄存器={}",
+                self.address_name
+            )));
+        }
+        let suffix = self.inner_format.map_or("", |format| format.suffix());
+        Ok(format!("{}{}{}", device.header(), number, suffix))
+    }
+}
+
+impl IAddress for KvAddress {
+    fn get_address_name(&self) -> &str {
+        &self.address_name
+    }
+
+    fn get_address_header(&self) -> &str {
+        self.inner_device.header()
+    }
+
+    fn get_address(&self) -> u32 {
+        self.inner_number
+    }
+
+    fn get_data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// 读取指令，数量为1时使用 `RD`，否则使用 `RDS`
+pub fn create_read_cmd(device: &str, count: u16) -> String {
+    match count {
+        1 => format!("RD {}\r", device),
+        _ => format!("RDS {} {}\r", device, count),
+    }
+}
+
+/// 写入指令，数量为1时使用 `WR`，否则使用 `WRS`
+///
+/// # Param
+/// * `format` - 数据格式，按位写入时为 None，数据为 0 或者 1
+/// * `datas` - 按字写入时每个数据占用 `format.words()` 个字
+pub fn create_write_cmd(device: &str, format: Option<KvFormat>, datas: &[u16]) -> String {
+    let values: Vec<String> = match format {
+        Some(format) => datas
+            .chunks(format.words() as usize)
+            .map(|words| format.format(words))
+            .collect(),
+        None => datas.iter().map(|state| state.to_string()).collect(),
+    };
+    match values.as_slice() {
+        [value] => format!("WR {} {}\r", device, value),
+        _ => format!("WRS {} {} {}\r", device, values.len(), values.join(" ")),
+    }
+}
+
+/// 解析读取回复
+///
+/// 定时器和计数器的数据为 `接点,当前值`，按位读取时返回接点，按字读取时返回当前值
+pub fn parse_read_reply(
+    reply: &[u8],
+    address: &KvAddress,
+    count: u16,
+) -> Result<Vec<u16>, PlcError> {
+    let text = check_reply(reply)?;
+    let invalid = || PlcError::Comm(format!("KV 回复数据解析失败\t数据={}", text));
+    let mut datas = Vec::with_capacity(count as usize * 2);
+    for value in text.split_whitespace() {
+        let value = match (address.device().is_timer(), value.split_once(',')) {
+            (true, Some((contact, current))) => match address.format() {
+                None => contact,
+                Some(_) => current,
+            },
+            _ => value,
+        };
+        match address.format() {
+            Some(format) => datas.extend(format.parse(value).ok_or_else(invalid)?),
+            None => datas.push(match value {
+                "0" => 0,
+                "1" => 1,
+                _ => return Err(invalid()),
+            }),
+        }
+    }
+    let words = address.format().map_or(1, |format| format.words());
+    if datas.len() != (count * words) as usize {
+        return Err(PlcError::Comm(format!(
+            "KV 回复的数据数量错误\t数量={}\t需要={}",
+            datas.len() / words as usize,
+            count
+        )));
+    }
+    Ok(datas)
+}
+
+/// 检查写入回复
+pub fn check_write_reply(reply: &[u8]) -> PlcResult {
+    match check_reply(reply)? {
+        "OK" => Ok(()),
+        text => Err(PlcError::Comm(format!("KV 写入回复错误\t数据={}", text))),
+    }
+}
+
+/// 检查回复是否完整，返回一行数据(不含结束符)
+pub fn check_kv_reply(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    match buf.windows(2).position(|window| window == b"\r\n") {
+        Some(p) => Ok(Ok(&buf[..p])),
+        None => Ok(Err("未找到结束符 0x0D 0x0A")),
+    }
+}
+
+/// 错误回复转换为错误
+fn check_reply(reply: &[u8]) -> Result<&str, PlcError> {
+    let text = std::str::from_utf8(reply)
+        .map_err(|_| PlcError::Comm("数据解析失败".into()))?
+        .trim();
+    match text {
+        "E0" => Err(PlcError::Addr("KV 错误E0：软元件编号错误".into())),
+        "E1" => Err(PlcError::Param("KV 错误E1：指令错误".into())),
+        "E2" => Err(PlcError::Comm("KV 错误E2：程序未登录".into())),
+        "E4" => Err(PlcError::Param("KV 错误E4：禁止写入".into())),
+        "E5" => Err(PlcError::Comm("KV 错误E5：单元错误".into())),
+        "E6" => Err(PlcError::Comm("KV 错误E6：没有注释".into())),
+        _ => Ok(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let cases = [
+            ("DM100", DataType::Word, "DM102.U"),
+            ("dm100.s", DataType::Word, "DM102.S"),
+            ("EM0.D", DataType::Word, "EM4.D"),
+            ("FM10.H", DataType::Word, "FM12.H"),
+            ("R1014", DataType::Bit, "R1100"),
+            ("MR315", DataType::Bit, "MR401"),
+            ("R1000.U", DataType::Word, "R1200.U"),
+            ("T0", DataType::Bit, "T2"),
+            ("C10", DataType::Word, "C12.D"),
+        ];
+        for (name, data_type, next) in cases {
+            let r = KvAddress::new(name, data_type).unwrap();
+            assert_eq!(r.device_name(2).unwrap(), next, "{}", name);
+        }
+        let invalid = [
+            ("DM100", DataType::Bit),
+            ("R1016", DataType::Bit),
+            ("R1000.U", DataType::Bit),
+            ("DM65535", DataType::Word),
+            ("DM100.X", DataType::Word),
+            ("ZZ100", DataType::Word),
+            ("DM", DataType::Word),
+            ("T4000", DataType::Bit),
+        ];
+        for (name, data_type) in invalid {
+            assert!(KvAddress::new(name, data_type).is_err(), "{}", name);
+        }
+        let r = KvAddress::new("DM65534", DataType::Word).unwrap();
+        assert!(r.device_name(1).is_err());
+    }
+
+    #[test]
+    fn test_cmd() {
+        assert_eq!(create_read_cmd("DM100.S", 1), "RD DM100.S\r");
+        assert_eq!(create_read_cmd("DM100", 3), "RDS DM100 3\r");
+        assert_eq!(
+            create_write_cmd("DM100.S", Some(KvFormat::Signed), &[0xFFFF]),
+            "WR DM100.S -1\r"
+        );
+        assert_eq!(
+            create_write_cmd(
+                "DM100.L",
+                Some(KvFormat::SignedDouble),
+                &[0xFFFE, 0xFFFF, 1, 0]
+            ),
+            "WRS DM100.L 2 -2 1\r"
+        );
+        assert_eq!(
+            create_write_cmd("R1000", None, &[1, 0]),
+            "WRS R1000 2 1 0\r"
+        );
+
+        let address = KvAddress::new("DM0.D", DataType::Word).unwrap();
+        assert_eq!(
+            parse_read_reply(b"65536 1", &address, 2).unwrap(),
+            [0, 1, 1, 0]
+        );
+        assert!(parse_read_reply(b"65536", &address, 2).is_err());
+        let address = KvAddress::new("T0", DataType::Bit).unwrap();
+        assert_eq!(
+            parse_read_reply(b"1,0000000100 0,0000000000", &address, 2).unwrap(),
+            [1, 0]
+        );
+        let address = KvAddress::new("DM0.H", DataType::Word).unwrap();
+        assert_eq!(parse_read_reply(b"ABCD", &address, 1).unwrap(), [0xABCD]);
+        assert!(matches!(
+            parse_read_reply(b"E0", &address, 1),
+            Err(PlcError::Addr(_))
+        ));
+        assert!(check_write_reply(b"OK").is_ok());
+        assert!(matches!(check_write_reply(b"E4"), Err(PlcError::Param(_))));
+    }
+}
@@ -0,0 +1,273 @@
+// ! 基恩士 KV 上位链路协议(TCP)
+
+use std::time::Duration;
+
+use super::kv::{self, KvAddress};
+use crate::core::{Channel, Transport};
+use crate::prelude::*;
+
+/// 基恩士 KV-8000/KV-7500 网口PLC 上位链路协议，端口一般为 8501
+pub struct KvTcpPlc {
+    /// 通讯通道
+    channel: Channel,
+}
+
+impl Clone for KvTcpPlc {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl KvTcpPlc {
+    /// 使用已经打开的连接代替 `connect`
+    pub fn attach(&mut self, transport: impl Transport + 'static) {
+        self.channel.attach(transport);
+    }
+
+    /// 发送指令并返回回复的一行数据(不含结束符)
+    async fn send_and_receive(&self, cmd: &str) -> Result<Vec<u8>, PlcError> {
+        self.channel
+            .send_and_receive(cmd.as_bytes(), kv::check_kv_reply)
+            .await
+    }
+}
+
+/// 按字读写时每个数据占用的字数，长度必须是其整数倍
+fn item_words(address: &KvAddress, len: usize) -> Result<usize, PlcError> {
+    let words = address.format().map_or(1, |format| format.words()) as usize;
+    if !len.is_multiple_of(words) {
+        return Err(PlcError::Param(format!(
+            "KV 32位数据的长度必须是2的倍数\t长度={}",
+            len
+        )));
+    }
+    Ok(words)
+}
+
+unsafe impl Send for KvTcpPlc {}
+
+unsafe impl Sync for KvTcpPlc {}
+
+impl IPlc for KvTcpPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        KvTcpPlc {
+            channel: Channel::new(conn, timeout),
+        }
+    }
+
+    async fn connect(&mut self) -> PlcResult {
+        self.channel.connect().await
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    /// 读取数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `DM100`、`DM100.S`、`EM0.D`、`R1000`、`MR315`、`T0`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 读取继电器、定时器或者计数器的接点，每个位一个数据
+    ///     * Word 按字读取，`.D/.L` 以及定时器、计数器的当前值每个数据为低字在前的两个字
+    /// * `len` - 数据长度(字数或者位数)
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        if len == 0 {
+            return Err(PlcError::Param("读取长度不能为0".into()));
+        }
+        let address = KvAddress::new(address_name, data_type)?;
+        let words = item_words(&address, len as usize)?;
+        let total = len as usize / words;
+        // 检查结束地址是否超出范围
+        address.device_name(total as u32 - 1)?;
+        let max = address.format().map_or(1000, |format| format.max_count()) as usize;
+        let mut datas = Vec::with_capacity(len as usize);
+        let mut offset = 0;
+        while offset < total {
+            let count = max.min(total - offset) as u16;
+            let cmd = kv::create_read_cmd(&address.device_name(offset as u32)?, count);
+            let reply = self.send_and_receive(&cmd).await?;
+            datas.extend(kv::parse_read_reply(&reply, &address, count)?);
+            offset += count as usize;
+        }
+        Ok(datas)
+    }
+
+    /// 写入数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `DM100`、`DM100.L`、`R1000`、`MR315`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 写入继电器，0：关闭；1：打开
+    ///     * Word 按字写入，`.D/.L` 每个数据为低字在前的两个字
+    /// * `datas` - 需要写入的数据
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        if datas.is_empty() {
+            return Err(PlcError::Param("写入数据不能为空".into()));
+        }
+        let address = KvAddress::new(address_name, data_type)?;
+        if address.format().is_none() && datas.iter().any(|state| *state > 1) {
+            return Err(PlcError::Param("位写入数据只能是 1 或者 0".into()));
+        }
+        let words = item_words(&address, datas.len())?;
+        address.device_name((datas.len() / words) as u32 - 1)?;
+        let max = address.format().map_or(1000, |format| format.max_count()) as usize;
+        for (i, chunk) in datas.chunks(max * words).enumerate() {
+            let device = address.device_name((i * max) as u32)?;
+            let cmd = kv::create_write_cmd(&device, address.format(), chunk);
+            let reply = self.send_and_receive(&cmd).await?;
+            kv::check_write_reply(&reply)?;
+        }
+        Ok(())
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// 模拟 PLC 处理指令，只支持 DM(.U/.D) 和 R，初始值为 0
+    fn handle_cmd(memory: &mut HashMap<String, u32>, cmd: &str) -> String {
+        let parts: Vec<&str> = cmd.split_whitespace().collect();
+        let device = parts[1];
+        let (name, double) = match device.split_once('.') {
+            Some((name, suffix)) => (name, suffix == "D"),
+            None => (device, false),
+        };
+        let (header, number) = name.split_at(name.find(|c: char| c.is_ascii_digit()).unwrap());
+        let number: u32 = number.parse().unwrap();
+        if header == "DM" && number > 65534 {
+            return "E0".into();
+        }
+        let step = if header == "R" {
+            1
+        } else if double {
+            2
+        } else {
+            1
+        };
+        let key = |i: u32| match header {
+            "R" => {
+                let bit = number / 100 * 16 + number % 100 + i;
+                format!("R{}", bit / 16 * 100 + bit % 16)
+            }
+            _ => format!("{}{}", header, number + i * step),
+        };
+        let get = |memory: &HashMap<String, u32>, i: u32| {
+            let value = |k: String| memory.get(&k).copied().unwrap_or(0);
+            match double {
+                true => value(key(i)) | value(format!("DM{}", number + i * 2 + 1)) << 16,
+                false => value(key(i)),
+            }
+        };
+        match parts[0] {
+            "RD" => get(memory, 0).to_string(),
+            "RDS" => {
+                let count: u32 = parts[2].parse().unwrap();
+                let values: Vec<String> = (0..count).map(|i| get(memory, i).to_string()).collect();
+                values.join(" ")
+            }
+            "WR" | "WRS" => {
+                let values = match parts[0] {
+                    "WR" => &parts[2..],
+                    _ => &parts[3..],
+                };
+                for (i, value) in values.iter().enumerate() {
+                    let value: u32 = value.parse().unwrap();
+                    match double {
+                        true => {
+                            memory.insert(key(i as u32), value & 0xFFFF);
+                            memory.insert(format!("DM{}", number + i as u32 * 2 + 1), value >> 16);
+                        }
+                        false => {
+                            memory.insert(key(i as u32), value);
+                        }
+                    }
+                }
+                "OK".into()
+            }
+            _ => "E1".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(reader);
+            let mut memory = HashMap::new();
+            loop {
+                let mut cmd = Vec::new();
+                if reader.read_until(b'\r', &mut cmd).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                let cmd = String::from_utf8(cmd).unwrap();
+                let reply = handle_cmd(&mut memory, &cmd);
+                writer
+                    .write_all(format!("{}\r\n", reply).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        let mut plc = KvTcpPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        plc.connect().await.unwrap();
+
+        plc.write("DM100", DataType::Word, &[1, 2, 3])
+            .await
+            .unwrap();
+        let r = plc.read("DM100", DataType::Word, 3).await.unwrap();
+        assert_eq!(r, [1, 2, 3]);
+        plc.write("DM200.D", DataType::Word, &[0x0001, 0x0002])
+            .await
+            .unwrap();
+        let r = plc.read("DM200", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [1, 2]);
+        let r = plc.read("DM200.D", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [1, 2]);
+        assert!(matches!(
+            plc.read("DM200.D", DataType::Word, 3).await,
+            Err(PlcError::Param(_))
+        ));
+
+        // 继电器跨通道写入
+        plc.write("R1015", DataType::Bit, &[1, 1]).await.unwrap();
+        let r = plc.read("R1014", DataType::Bit, 4).await.unwrap();
+        assert_eq!(r, [0, 1, 1, 0]);
+        assert!(matches!(
+            plc.write("R1000", DataType::Bit, &[2]).await,
+            Err(PlcError::Param(_))
+        ));
+
+        // 超过单次最大数量时分包读取
+        let r = plc.read("DM0", DataType::Word, 1001).await.unwrap();
+        assert_eq!(r[100..103], [1, 2, 3]);
+        let r = plc.read("DM65000", DataType::Word, 1000).await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+        plc.disconnect().await.unwrap();
+    }
+}
@@ -0,0 +1,14 @@
+// ! 基恩士PLC
+
+mod kv;
+mod kv_tcp;
+
+pub use self::kv::{KvAddress, KvDevice, KvFormat};
+pub use self::kv_tcp::KvTcpPlc;
+use crate::{core::PlcConnector, IPlc};
+use std::time::Duration;
+
+/// 创建一个 基恩士 KV 网口PLC (上位链路协议，端口一般为8501)
+pub fn new_kv_tcp_plc(conn: PlcConnector, timeout: Duration) -> KvTcpPlc {
+    KvTcpPlc::new(conn, timeout)
+}
@@ -1,9 +1,17 @@
+pub mod allen_bradley;
+pub mod beckhoff;
 mod core;
 mod error;
+pub mod fatek;
 pub mod ipcsun;
+pub mod keyence;
+pub mod ls;
 pub mod mitsubishi;
+pub mod modbus;
+pub mod omron;
 pub mod panasonic;
 pub mod prelude;
+pub mod siemens;
 
 use core::PlcConnector;
 use prelude::*;
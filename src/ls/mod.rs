@@ -0,0 +1,14 @@
+// ! LS(LS Electric) PLC
+
+mod xgt;
+mod xgt_fenet;
+
+pub use self::xgt::XgtAddress;
+pub use self::xgt_fenet::XgtFenetPlc;
+use crate::{core::PlcConnector, IPlc};
+use std::time::Duration;
+
+/// 创建一个 LS 网口PLC (XGT 专用以太网协议，端口一般为2004)
+pub fn new_xgt_fenet_plc(conn: PlcConnector, timeout: Duration) -> XgtFenetPlc {
+    XgtFenetPlc::new(conn, timeout)
+}
@@ -0,0 +1,394 @@
+// ! LS XGT 专用以太网协议(FEnet)
+//
+// * 公司头(20字节)：公司ID `LSIS-XGT`(10) + PLC信息(2) + CPU信息(1) + 方向(1) + 调用编号(2)
+//   + 指令长度(2) + FEnet位置(1) + 校验和(1)，小端
+// * 指令：命令(2) + 数据类型(2) + 保留(2) + [错误状态(2)] + 块数量(2) + 块
+// * 单个读写(个别)每次最多16个变量，连续读写每次最多1400字节
+
+use crate::prelude::*;
+
+/// 公司头长度
+pub const HEADER_LEN: usize = 20;
+/// 读取请求
+pub const READ_REQUEST: u16 = 0x0054;
+/// 读取回复
+pub const READ_RESPONSE: u16 = 0x0055;
+/// 写入请求
+pub const WRITE_REQUEST: u16 = 0x0058;
+/// 写入回复
+pub const WRITE_RESPONSE: u16 = 0x0059;
+/// 数据类型：位
+pub const TYPE_BIT: u16 = 0x0000;
+/// 数据类型：连续
+pub const TYPE_CONTINUOUS: u16 = 0x0014;
+/// 单个读写的最大变量数量
+pub const MAX_BLOCKS: usize = 16;
+/// 连续读写的最大字节数
+pub const MAX_BYTES: usize = 1400;
+
+/// 公司ID
+const COMPANY_ID: &[u8; 10] = b"LSIS-XGT\0\0";
+/// 方向：客户端到 PLC
+const SOURCE_CLIENT: u8 = 0x33;
+/// 方向：PLC 到客户端
+const SOURCE_SERVER: u8 = 0x11;
+
+/// XGT 寄存器地址(XGK/XGB)
+///
+/// 格式为 `%` + 软元件 + 类型 + 编号：
+/// * 字：`%MW100`、`%DW200`
+/// * 位：字编号 + 十六进制位号，例如 `%MX100A` 为 M100 的第10位，`%PX0` 为 P0 的第0位
+///
+/// 支持的软元件：P、M、K、F、T、C、L、N、D、R、Z
+pub struct XgtAddress {
+    address_name: String,
+    data_type: DataType,
+    /// 软元件
+    inner_device: char,
+    /// 字编号
+    inner_word: u32,
+    /// 位号
+    inner_bit: u8,
+}
+
+impl XgtAddress {
+    /// 实例化一个寄存器地址
+    ///
+    /// # Error
+    /// 无效的寄存器地址，或者地址与数据类型不一致
+    pub fn new(address_name: impl Into<String>, data_type: DataType) -> Result<Self, PlcError> {
+        let address_name = address_name.into().trim().to_uppercase();
+        let invalid = || PlcError::Addr(format!("XGT 无效的寄存器地址\t寄存器={}", &address_name));
+        let mut chars = address_name.strip_prefix('%').ok_or_else(invalid)?.chars();
+        let device = chars.next().ok_or_else(invalid)?;
+        let kind = chars.next().ok_or_else(invalid)?;
+        let number = chars.as_str();
+        let max_word = device_max_word(device).ok_or_else(invalid)?;
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let (word, bit) = match (kind, &data_type) {
+            ('W', DataType::Word) => (number, "0"),
+            // 最后一位为十六进制位号，只有位号时字编号为0
+            ('X', DataType::Bit) if number.len() == 1 => ("0", number),
+            ('X', DataType::Bit) => number.split_at(number.len() - 1),
+            _ => {
+                return Err(PlcError::Addr(format!(
+                    "XGT 寄存器地址与数据类型不一致\t寄存器={}",
+                    &address_name
+                )))
+            }
+        };
+        if !word.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let word = word.parse::<u32>().map_err(|_| invalid())?;
+        let bit = u8::from_str_radix(bit, 16).map_err(|_| invalid())?;
+        if word > max_word {
+            return Err(invalid());
+        }
+        Ok(Self {
+            address_name,
+            data_type,
+            inner_device: device,
+            inner_word: word,
+            inner_bit: bit,
+        })
+    }
+
+    /// 软元件
+    pub fn device(&self) -> char {
+        self.inner_device
+    }
+
+    /// 字编号
+    pub fn word(&self) -> u32 {
+        self.inner_word
+    }
+
+    /// 位号
+    pub fn bit(&self) -> u8 {
+        self.inner_bit
+    }
+
+    /// 从当前位偏移 `offset` 个位后的位变量名称，例如 `%MX100A`
+    pub fn bit_name(&self, offset: u32) -> Result<String, PlcError> {
+        let bit = self.inner_word * 16 + self.inner_bit as u32 + offset;
+        self.check_word(bit / 16)?;
+        Ok(format!("%{}X{}{:X}", self.inner_device, bit / 16, bit % 16))
+    }
+
+    /// 从当前字偏移 `offset` 个字后的字节变量名称(连续读写使用)，例如 `%MB200`
+    pub fn byte_name(&self, offset: u32) -> Result<String, PlcError> {
+        let word = self.inner_word + offset;
+        self.check_word(word)?;
+        Ok(format!("%{}B{}", self.inner_device, word * 2))
+    }
+
+    /// 检查字编号是否超出软元件范围
+    pub fn check_word(&self, word: u32) -> PlcResult {
+        match device_max_word(self.inner_device) {
+            Some(max) if word <= max => Ok(()),
+            _ => Err(PlcError::Addr(format!(
+                "XGT 读写范围超出地址范围\t寄存器={}",
+                self.address_name
+            ))),
+        }
+    }
+}
+
+/// 软元件的最大字编号(XGK)
+fn device_max_word(device: char) -> Option<u32> {
+    match device {
+        'P' | 'M' | 'K' | 'F' | 'T' | 'C' => Some(2047),
+        'L' => Some(11263),
+        'N' => Some(21503),
+        'D' | 'R' => Some(32767),
+        'Z' => Some(127),
+        _ => None,
+    }
+}
+
+impl IAddress for XgtAddress {
+    fn get_address_name(&self) -> &str {
+        &self.address_name
+    }
+
+    fn get_address_header(&self) -> &str {
+        &self.address_name[1..2]
+    }
+
+    fn get_address(&self) -> u32 {
+        self.inner_word
+    }
+
+    fn get_data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// 添加公司头
+pub fn create_frame(invoke_id: u16, instruction: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_LEN + instruction.len());
+    frame.extend_from_slice(COMPANY_ID);
+    // PLC信息 + CPU信息(客户端请求时保留)
+    frame.extend([0, 0, 0, SOURCE_CLIENT]);
+    frame.extend(invoke_id.to_le_bytes());
+    frame.extend((instruction.len() as u16).to_le_bytes());
+    frame.push(0);
+    frame.push(checksum(&frame));
+    frame.extend_from_slice(instruction);
+    frame
+}
+
+/// 公司头的校验和
+fn checksum(header: &[u8]) -> u8 {
+    header.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+/// 检查报文是否完整
+pub fn check_frame(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    if buf.len() < HEADER_LEN {
+        return Ok(Err("数据长度不足"));
+    }
+    if !buf.starts_with(&COMPANY_ID[..8]) || buf[13] != SOURCE_SERVER {
+        return Err(PlcError::Comm(format!(
+            "XGT 公司头错误\t数据={:02X?}",
+            &buf[..HEADER_LEN]
+        )));
+    }
+    let len = HEADER_LEN + u16::from_le_bytes([buf[16], buf[17]]) as usize;
+    match buf.get(..len) {
+        Some(frame) => Ok(Ok(frame)),
+        None => Ok(Err("数据长度不足")),
+    }
+}
+
+/// 解析报文，返回调用编号和指令
+pub fn parse_frame(frame: &[u8]) -> (u16, &[u8]) {
+    (
+        u16::from_le_bytes([frame[14], frame[15]]),
+        &frame[HEADER_LEN..],
+    )
+}
+
+/// 指令头：命令 + 数据类型 + 保留 + 块数量
+fn instruction(command: u16, data_type: u16, blocks: usize) -> Vec<u8> {
+    let mut instruction = Vec::with_capacity(256);
+    instruction.extend(command.to_le_bytes());
+    instruction.extend(data_type.to_le_bytes());
+    instruction.extend([0, 0]);
+    instruction.extend((blocks as u16).to_le_bytes());
+    instruction
+}
+
+/// 添加变量名称
+fn push_name(instruction: &mut Vec<u8>, name: &str) {
+    instruction.extend((name.len() as u16).to_le_bytes());
+    instruction.extend_from_slice(name.as_bytes());
+}
+
+/// 单个读取请求，每个变量一个块
+pub fn read_individual_request(data_type: u16, names: &[String]) -> Vec<u8> {
+    let mut request = instruction(READ_REQUEST, data_type, names.len());
+    for name in names {
+        push_name(&mut request, name);
+    }
+    request
+}
+
+/// 连续读取请求，从字节变量开始读取 `count` 个字节
+pub fn read_continuous_request(name: &str, count: u16) -> Vec<u8> {
+    let mut request = instruction(READ_REQUEST, TYPE_CONTINUOUS, 1);
+    push_name(&mut request, name);
+    request.extend(count.to_le_bytes());
+    request
+}
+
+/// 单个写入请求，每个变量一个块
+pub fn write_individual_request(data_type: u16, blocks: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut request = instruction(WRITE_REQUEST, data_type, blocks.len());
+    for (name, _) in blocks {
+        push_name(&mut request, name);
+    }
+    for (_, data) in blocks {
+        request.extend((data.len() as u16).to_le_bytes());
+        request.extend_from_slice(data);
+    }
+    request
+}
+
+/// 连续写入请求，从字节变量开始写入
+pub fn write_continuous_request(name: &str, data: &[u8]) -> Vec<u8> {
+    write_individual_request(TYPE_CONTINUOUS, &[(name.to_string(), data.to_vec())])
+}
+
+/// 检查回复的命令和错误状态，返回块数量之后的数据
+fn check_response(response: &[u8], command: u16) -> Result<(u16, &[u8]), PlcError> {
+    let invalid = || PlcError::Comm(format!("XGT 回复解析失败\t数据={:02X?}", response));
+    if response.len() < 10 || u16::from_le_bytes([response[0], response[1]]) != command {
+        return Err(invalid());
+    }
+    // 错误状态不为0时，后面为错误码
+    let value = u16::from_le_bytes([response[8], response[9]]);
+    if response[6..8] != [0, 0] {
+        return Err(nak_error(value));
+    }
+    Ok((value, &response[10..]))
+}
+
+/// 解析读取回复，返回每个块的数据
+pub fn parse_read_response(response: &[u8]) -> Result<Vec<&[u8]>, PlcError> {
+    let (blocks, mut data) = check_response(response, READ_RESPONSE)?;
+    let invalid = || PlcError::Comm(format!("XGT 读取回复解析失败\t数据={:02X?}", response));
+    let mut datas = Vec::with_capacity(blocks as usize);
+    for _ in 0..blocks {
+        let [s0, s1, rest @ ..] = data else {
+            return Err(invalid());
+        };
+        let size = u16::from_le_bytes([*s0, *s1]) as usize;
+        datas.push(rest.get(..size).ok_or_else(invalid)?);
+        data = &rest[size..];
+    }
+    Ok(datas)
+}
+
+/// 检查写入回复
+pub fn check_write_response(response: &[u8]) -> PlcResult {
+    check_response(response, WRITE_RESPONSE)?;
+    Ok(())
+}
+
+/// NAK 错误码转换为错误
+pub fn nak_error(code: u16) -> PlcError {
+    let desc = match code {
+        0x0001 => "单个读写的变量数量超过16",
+        0x0002 => "无效的数据类型",
+        0x0003 => "不支持的软元件",
+        0x0004 => "超出软元件的地址范围",
+        0x0005 => "数据长度超过1400字节",
+        0x0006 => "块的数据长度超过1400字节",
+        0x0010 => "无效的命令",
+        0x0011 => "变量名称错误",
+        0x0012 => "无效的数据类型(变量与命令不一致)",
+        0x0021 => "数据长度错误",
+        _ => "未知错误",
+    };
+    let msg = format!("XGT 错误：{}\t错误码=0x{:04X}", desc, code);
+    match code {
+        0x0003 | 0x0004 | 0x0011 => PlcError::Addr(msg),
+        0x0001 | 0x0002 | 0x0005 | 0x0006 | 0x0012 | 0x0021 => PlcError::Param(msg),
+        _ => PlcError::Comm(msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let r = XgtAddress::new("%mw100", DataType::Word).unwrap();
+        assert_eq!((r.device(), r.word()), ('M', 100));
+        assert_eq!(r.byte_name(2).unwrap(), "%MB204");
+        let r = XgtAddress::new("%MX100F", DataType::Bit).unwrap();
+        assert_eq!((r.word(), r.bit()), (100, 15));
+        assert_eq!(r.bit_name(1).unwrap(), "%MX1010");
+        let r = XgtAddress::new("%PXA", DataType::Bit).unwrap();
+        assert_eq!((r.word(), r.bit()), (0, 10));
+        let r = XgtAddress::new("%DW32767", DataType::Word).unwrap();
+        assert!(r.byte_name(1).is_err());
+        let invalid = [
+            ("MW100", DataType::Word),
+            ("%MW100", DataType::Bit),
+            ("%MX100", DataType::Word),
+            ("%XW100", DataType::Word),
+            ("%MWA0", DataType::Word),
+            ("%MW2048", DataType::Word),
+            ("%MX", DataType::Bit),
+            ("%MXG", DataType::Bit),
+        ];
+        for (name, data_type) in invalid {
+            assert!(XgtAddress::new(name, data_type).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_frame() {
+        let request = read_continuous_request("%MB200", 4);
+        assert_eq!(
+            request,
+            [0x54, 0, 0x14, 0, 0, 0, 1, 0, 6, 0, b'%', b'M', b'B', b'2', b'0', b'0', 4, 0]
+        );
+        let frame = create_frame(0x1234, &request);
+        assert_eq!(&frame[..10], b"LSIS-XGT\0\0");
+        assert_eq!(&frame[13..18], [0x33, 0x34, 0x12, 18, 0]);
+        assert_eq!(frame[19], checksum(&frame[..19]));
+
+        let request = write_individual_request(TYPE_BIT, &[("%MX0".into(), vec![1])]);
+        assert_eq!(
+            request,
+            [0x58, 0, 0, 0, 0, 0, 1, 0, 4, 0, b'%', b'M', b'X', b'0', 1, 0, 1]
+        );
+
+        let mut reply = b"LSIS-XGT\0\0\0\0\xA0\x11\x34\x12".to_vec();
+        let response = [0x55, 0, 0x02, 0, 0, 0, 0, 0, 2, 0, 2, 0, 1, 2, 2, 0, 3, 4];
+        reply.extend((response.len() as u16).to_le_bytes());
+        reply.extend([0, 0]);
+        reply.extend(response);
+        assert!(check_frame(&reply[..30]).unwrap().is_err());
+        let frame = check_frame(&reply).unwrap().unwrap();
+        let (invoke_id, response) = parse_frame(frame);
+        assert_eq!(invoke_id, 0x1234);
+        assert_eq!(parse_read_response(response).unwrap(), [[1, 2], [3, 4]]);
+        assert!(parse_read_response(&response[..17]).is_err());
+
+        let nak = [0x59, 0, 0x14, 0, 0, 0, 0xFF, 0xFF, 0x04, 0x00];
+        assert!(matches!(check_write_response(&nak), Err(PlcError::Addr(_))));
+        assert!(check_write_response(&[0x59, 0, 0x14, 0, 0, 0, 0, 0, 1, 0]).is_ok());
+    }
+}
@@ -0,0 +1,330 @@
+// ! LS XGT 专用以太网协议(TCP)
+
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{event, Level};
+
+use super::xgt::{self, XgtAddress};
+use crate::core::{Channel, Transport};
+use crate::prelude::*;
+
+/// LS XGK/XGB 网口PLC XGT 专用以太网协议(FEnet)，端口一般为 2004
+///
+/// 按字读写使用连续读写，按位读写使用单个读写
+pub struct XgtFenetPlc {
+    /// 通讯通道
+    channel: Channel,
+    /// 调用编号
+    invoke_id: Arc<AtomicU16>,
+}
+
+impl Clone for XgtFenetPlc {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            invoke_id: self.invoke_id.clone(),
+        }
+    }
+}
+
+impl XgtFenetPlc {
+    /// 使用已经打开的连接代替 `connect`
+    pub fn attach(&mut self, transport: impl Transport + 'static) {
+        self.channel.attach(transport);
+    }
+
+    /// 发送指令并返回回复的指令，丢弃调用编号不一致的回复
+    async fn send_and_receive(&self, instruction: &[u8]) -> Result<Vec<u8>, PlcError> {
+        let invoke_id = self.invoke_id.fetch_add(1, Ordering::Relaxed);
+        let frame = xgt::create_frame(invoke_id, instruction);
+        let reply = self
+            .channel
+            .send_and_receive(&frame, |mut buf| {
+                while let Ok(frame) = xgt::check_frame(buf)? {
+                    if xgt::parse_frame(frame).0 == invoke_id {
+                        return Ok(Ok(frame));
+                    }
+                    event!(Level::WARN, "丢弃其他请求的回复\t回复={:02X?}", frame);
+                    buf = &buf[frame.len()..];
+                }
+                Ok(Err("数据长度不足"))
+            })
+            .await?;
+        Ok(xgt::parse_frame(&reply).1.to_vec())
+    }
+}
+
+unsafe impl Send for XgtFenetPlc {}
+
+unsafe impl Sync for XgtFenetPlc {}
+
+impl IPlc for XgtFenetPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        XgtFenetPlc {
+            channel: Channel::new(conn, timeout),
+            invoke_id: Arc::new(AtomicU16::new(0)),
+        }
+    }
+
+    async fn connect(&mut self) -> PlcResult {
+        self.channel.connect().await
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    /// 读取数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `%MW100`、`%DW200`、`%MX100A`、`%PX0`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 从位地址开始读取，每个位一个数据
+    ///     * Word 从字地址开始读取
+    /// * `len` - 数据长度
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        if len == 0 {
+            return Err(PlcError::Param("读取长度不能为0".into()));
+        }
+        let address = XgtAddress::new(address_name, data_type.clone())?;
+        let mut datas = Vec::with_capacity(len as usize);
+        match data_type {
+            DataType::Word => {
+                address.check_word(address.word() + len as u32 - 1)?;
+                let max = (xgt::MAX_BYTES / 2) as u16;
+                while datas.len() < len as usize {
+                    let count = max.min(len - datas.len() as u16);
+                    let name = address.byte_name(datas.len() as u32)?;
+                    let request = xgt::read_continuous_request(&name, count * 2);
+                    let response = self.send_and_receive(&request).await?;
+                    let blocks = xgt::parse_read_response(&response)?;
+                    match blocks.as_slice() {
+                        [data] if data.len() == count as usize * 2 => datas.extend(
+                            data.chunks_exact(2)
+                                .map(|word| u16::from_le_bytes([word[0], word[1]])),
+                        ),
+                        _ => {
+                            return Err(PlcError::Comm(format!(
+                                "XGT 读取回复的数据长度错误\t数据={:02X?}",
+                                blocks
+                            )))
+                        }
+                    }
+                }
+            }
+            DataType::Bit => {
+                while datas.len() < len as usize {
+                    let count = xgt::MAX_BLOCKS.min(len as usize - datas.len());
+                    let names = (datas.len()..datas.len() + count)
+                        .map(|i| address.bit_name(i as u32))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let request = xgt::read_individual_request(xgt::TYPE_BIT, &names);
+                    let response = self.send_and_receive(&request).await?;
+                    let blocks = xgt::parse_read_response(&response)?;
+                    if blocks.len() != count || blocks.iter().any(|data| data.is_empty()) {
+                        return Err(PlcError::Comm(format!(
+                            "XGT 读取回复的数据数量错误\t数据={:02X?}",
+                            blocks
+                        )));
+                    }
+                    datas.extend(blocks.iter().map(|data| (data[0] & 1) as u16));
+                }
+            }
+        }
+        Ok(datas)
+    }
+
+    /// 写入数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `%MW100`、`%DW200`、`%MX100A`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 从位地址开始写入，0：关闭；1：打开
+    ///     * Word 从字地址开始写入
+    /// * `datas` - 需要写入的数据
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        if datas.is_empty() {
+            return Err(PlcError::Param("写入数据不能为空".into()));
+        }
+        let address = XgtAddress::new(address_name, data_type.clone())?;
+        match data_type {
+            DataType::Word => {
+                address.check_word(address.word() + datas.len() as u32 - 1)?;
+                let max = xgt::MAX_BYTES / 2;
+                for (i, chunk) in datas.chunks(max).enumerate() {
+                    let name = address.byte_name((i * max) as u32)?;
+                    let data: Vec<u8> = chunk.iter().flat_map(|word| word.to_le_bytes()).collect();
+                    let request = xgt::write_continuous_request(&name, &data);
+                    let response = self.send_and_receive(&request).await?;
+                    xgt::check_write_response(&response)?;
+                }
+            }
+            DataType::Bit => {
+                if datas.iter().any(|state| *state > 1) {
+                    return Err(PlcError::Param("位写入数据只能是 1 或者 0".into()));
+                }
+                for (i, chunk) in datas.chunks(xgt::MAX_BLOCKS).enumerate() {
+                    let blocks = chunk
+                        .iter()
+                        .enumerate()
+                        .map(|(j, state)| {
+                            let name = address.bit_name((i * xgt::MAX_BLOCKS + j) as u32)?;
+                            Ok((name, vec![*state as u8]))
+                        })
+                        .collect::<Result<Vec<_>, PlcError>>()?;
+                    let request = xgt::write_individual_request(xgt::TYPE_BIT, &blocks);
+                    let response = self.send_and_receive(&request).await?;
+                    xgt::check_write_response(&response)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 模拟 PLC 的回复指令，每个软元件的初始值为字编号
+    fn handle_instruction(memory: &mut HashMap<char, Vec<u16>>, request: &[u8]) -> Vec<u8> {
+        let command = u16::from_le_bytes([request[0], request[1]]);
+        let data_type = u16::from_le_bytes([request[2], request[3]]);
+        let blocks = u16::from_le_bytes([request[6], request[7]]) as usize;
+        let mut response = (command + 1).to_le_bytes().to_vec();
+        response.extend_from_slice(&request[2..6]);
+        let mut offset = 8;
+        let mut names = vec![];
+        for _ in 0..blocks {
+            let len = u16::from_le_bytes([request[offset], request[offset + 1]]) as usize;
+            names.push(String::from_utf8(request[offset + 2..offset + 2 + len].to_vec()).unwrap());
+            offset += 2 + len;
+        }
+        if blocks > xgt::MAX_BLOCKS {
+            response.extend([0xFF, 0xFF, 0x01, 0x00]);
+            return response;
+        }
+        response.extend([0, 0]);
+        response.extend((blocks as u16).to_le_bytes());
+        for name in names {
+            let words = memory
+                .entry(name.as_bytes()[1] as char)
+                .or_insert_with(|| (0..2048).collect());
+            let number = &name[3..];
+            match (command, data_type) {
+                (xgt::READ_REQUEST, xgt::TYPE_CONTINUOUS) => {
+                    let byte: usize = number.parse().unwrap();
+                    let count = u16::from_le_bytes([request[offset], request[offset + 1]]) as usize;
+                    let Some(values) = words.get(byte / 2..(byte + count) / 2) else {
+                        let mut nak = response[..6].to_vec();
+                        nak.extend([0xFF, 0xFF, 0x04, 0x00]);
+                        return nak;
+                    };
+                    response.extend((count as u16).to_le_bytes());
+                    response.extend(values.iter().flat_map(|word| word.to_le_bytes()));
+                }
+                (xgt::READ_REQUEST, _) => {
+                    let bit = usize::from_str_radix(&number[number.len() - 1..], 16).unwrap();
+                    let word: usize = number[..number.len() - 1].parse().unwrap_or(0);
+                    response.extend([1, 0, ((words[word] >> bit) & 1) as u8]);
+                }
+                (_, xgt::TYPE_CONTINUOUS) => {
+                    let byte: usize = number.parse().unwrap();
+                    let len = u16::from_le_bytes([request[offset], request[offset + 1]]) as usize;
+                    for (i, word) in request[offset + 2..offset + 2 + len].chunks(2).enumerate() {
+                        words[byte / 2 + i] = u16::from_le_bytes([word[0], word[1]]);
+                    }
+                }
+                _ => {
+                    let bit = usize::from_str_radix(&number[number.len() - 1..], 16).unwrap();
+                    let word: usize = number[..number.len() - 1].parse().unwrap_or(0);
+                    match request[offset + 2] {
+                        0 => words[word] &= !(1 << bit),
+                        _ => words[word] |= 1 << bit,
+                    }
+                    offset += 3;
+                }
+            }
+        }
+        response
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut memory = HashMap::new();
+            loop {
+                let mut header = [0u8; 20];
+                if socket.read_exact(&mut header).await.is_err() {
+                    return;
+                }
+                assert_eq!(&header[..8], b"LSIS-XGT");
+                let len = u16::from_le_bytes([header[16], header[17]]) as usize;
+                let mut request = vec![0u8; len];
+                socket.read_exact(&mut request).await.unwrap();
+                let response = handle_instruction(&mut memory, &request);
+                let mut reply = header.to_vec();
+                reply[13] = 0x11;
+                reply[16..18].copy_from_slice(&(response.len() as u16).to_le_bytes());
+                // 先回复一个调用编号不一致的回复，应该被丢弃
+                let mut stale = reply.clone();
+                stale[14] = stale[14].wrapping_sub(1);
+                stale.extend_from_slice(&response);
+                reply.extend(response);
+                socket.write_all(&[stale, reply].concat()).await.unwrap();
+            }
+        });
+        let mut plc = XgtFenetPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        plc.connect().await.unwrap();
+
+        let r = plc.read("%MW100", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [100, 101]);
+        plc.write("%DW10", DataType::Word, &[0x1234, 0x5678])
+            .await
+            .unwrap();
+        let r = plc.read("%DW10", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x1234, 0x5678]);
+        // 超过1400字节时分包读取
+        let r = plc.read("%DW0", DataType::Word, 705).await.unwrap();
+        assert_eq!(r[704], 704);
+
+        // 5 = 0b0101，超过16个位时分包
+        let r = plc.read("%PX50", DataType::Bit, 4).await.unwrap();
+        assert_eq!(r, [1, 0, 1, 0]);
+        plc.write("%MX0F", DataType::Bit, &[1; 18]).await.unwrap();
+        let r = plc.read("%MW0", DataType::Word, 3).await.unwrap();
+        assert_eq!(r, [0x8000, 0xFFFF, 0x0003]);
+        let r = plc.read("%MX1F", DataType::Bit, 20).await.unwrap();
+        assert_eq!(r[..3], [1, 1, 1]);
+
+        let r = plc.read("%MW2047", DataType::Word, 2).await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+        let r = plc.write("%MX0", DataType::Bit, &[2]).await;
+        assert!(matches!(r, Err(PlcError::Param(_))));
+        plc.disconnect().await.unwrap();
+    }
+}
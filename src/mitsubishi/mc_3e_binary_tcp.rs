@@ -1,93 +1,47 @@
-use std::sync::Arc;
 use std::time::Duration;
-use tokio::io::AsyncReadExt;
-use tokio::sync::Mutex;
-use tokio::time::timeout;
-use tokio::{io::AsyncWriteExt, net::TcpStream};
-use tracing::{event, Level};
 
 use super::mc::{self, McAddress};
+use crate::core::{Channel, Transport};
 use crate::prelude::*;
 
 /// 三菱 网口PLC MC协议 二进制
 pub struct Mc3eBinaryTcpPlc {
-    /// 连接参数
-    conn: PlcConnector,
-    /// 客户端连接
-    client: Option<Arc<Mutex<TcpStream>>>,
-    /// 超时时间
-    timeout: Duration,
+    /// 通讯通道
+    channel: Channel,
 }
 
 impl Clone for Mc3eBinaryTcpPlc {
     fn clone(&self) -> Self {
         Self {
-            conn: self.conn.clone(),
-            client: if self.client.is_none() {
-                None
-            } else {
-                let client = self.client.as_ref().unwrap();
-                Some(client.clone())
-            },
-            timeout: self.timeout.clone(),
+            channel: self.channel.clone(),
         }
     }
 }
 
+impl Mc3eBinaryTcpPlc {
+    /// 使用已经打开的连接代替 `connect`
+    pub fn attach(&mut self, transport: impl Transport + 'static) {
+        self.channel.attach(transport);
+    }
+}
+
 unsafe impl Send for Mc3eBinaryTcpPlc {}
 
 unsafe impl Sync for Mc3eBinaryTcpPlc {}
 
 impl IPlc for Mc3eBinaryTcpPlc {
-    fn new(conn: PlcConnector, timeout: std::time::Duration) -> Self {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
         Mc3eBinaryTcpPlc {
-            conn,
-            client: None,
-            timeout,
+            channel: Channel::new(conn, timeout),
         }
     }
 
     async fn connect(&mut self) -> PlcResult {
-        match &self.conn {
-            PlcConnector::SerialPort(value) => {
-                let err = format!("连接参数错误,此处需要Network参数\t{:?}", value);
-                event!(Level::ERROR, "\t{}", &err);
-                Err(PlcError::Param(err))
-            }
-            PlcConnector::Network(value) => {
-                let addr = format!("{}:{}", value.ip_address, value.ip_port);
-
-                let r = timeout(self.timeout, TcpStream::connect(addr)).await;
-                if let Err(err) = r {
-                    let err = format!("连接超时错误\t{}", err);
-                    event!(Level::ERROR, "\t{}", &err);
-                    Err(PlcError::Timeout)
-                } else {
-                    let r = r.unwrap();
-                    if let Err(err) = r {
-                        let err = format!("连接错误\t{}", err);
-                        event!(Level::ERROR, "\t{}", &err);
-                        Err(PlcError::Comm(err))
-                    } else {
-                        let client = r.unwrap();
-                        let _ = client.set_nodelay(true); // ! 解决网络物理断开后重连需要50秒的问题
-                        self.client = Some(Arc::new(Mutex::new(client)));
-                        Ok(())
-                    }
-                }
-            }
-        }
+        self.channel.connect().await
     }
 
     async fn disconnect(&mut self) -> PlcResult {
-        if let Some(tcp) = &self.client {
-            let tcp = Arc::clone(&tcp);
-            let mut tcp = tcp.lock().await;
-            let _ = tcp.shutdown();
-            drop(tcp);
-        }
-        self.client = None;
-        Ok(())
+        self.channel.disconnect().await
     }
 
     async fn read(
@@ -107,49 +61,11 @@ impl IPlc for Mc3eBinaryTcpPlc {
         let address = McAddress::new(address_name, data_type).await?;
         // 创建读取PLC数据buffer
         let buf = create_read_buf(&address, len)?;
-        // 获取发送客户端
-        if let None = self.client {
-            return Err(PlcError::NotConnect);
-        }
-        let client = self.client.as_ref().unwrap();
-        let mut client = client.lock().await;
-        let r = client.writable().await;
-        if let Err(err) = r {
-            return Err(PlcError::Comm(err.to_string()));
-        }
-        // 写入数据
-        let r = timeout(self.timeout, client.write(buf.as_slice())).await?;
-        if let Err(err) = r {
-            return Err(PlcError::Comm(err.to_string()));
-        }
-        // 读取返回数据,有可能接收数据不完整，所以需要循环读取
-        let mut buf = [0u8; 1024 * 4];
-        let mut index = 0;
-        loop {
-            let r = timeout(self.timeout, client.read(&mut buf[index..])).await?;
-            match r {
-                Ok(n) if n == 0 => return Err(PlcError::Comm("读取数据为空".into())),
-                Ok(n) => {
-                    index += n;
-                    let r = check_mc_3e_binary(&buf[0..index]);
-                    match r {
-                        Ok(r) => match r {
-                            Ok(arr) => {
-                                let arr = parse_mc_3e_binary(arr, address.get_data_type(), len)?;
-                                return Ok(arr);
-                            }
-                            Err(err) => {
-                                println!("数据不完整,继续等待...\terr={}", err);
-                                index += n;
-                                continue;
-                            }
-                        },
-                        Err(err) => return Err(PlcError::Comm(err.to_string())),
-                    }
-                }
-                Err(err) => return Err(PlcError::Comm(err.to_string())),
-            };
-        }
+        let reply = self
+            .channel
+            .send_and_receive(&buf, check_mc_3e_binary)
+            .await?;
+        parse_mc_3e_binary(&reply, address.get_data_type(), len)
     }
 
     async fn write(
@@ -168,46 +84,14 @@ impl IPlc for Mc3eBinaryTcpPlc {
         let address = McAddress::new(address_name, data_type).await?;
         // 创建写入PLC数据buffer
         let buf = create_write_buf(&address, datas)?;
-        // 获取发送客户端
-        if let None = self.client {
-            return Err(PlcError::NotConnect);
-        }
-        let client = self.client.as_ref().unwrap();
-        let mut client = client.lock().await;
-        let r = client.writable().await;
-        if let Err(err) = r {
-            return Err(PlcError::Comm(err.to_string()));
-        }
-        // 写入数据
-        let r = timeout(self.timeout, client.write(buf.as_slice())).await?;
-        if let Err(err) = r {
-            return Err(PlcError::Comm(err.to_string()));
-        }
-        if r.unwrap() != buf.len() {
-            return Err(PlcError::Comm("写入数据失败，无法写入数据".into()));
-        }
-        // 读取返回数据,有可能接收数据不完整，所以需要循环读取
-        let mut buf = [0u8; 1024];
-        let mut index = 0;
-        loop {
-            let r = timeout(self.timeout, client.read(&mut buf[index..])).await?;
-            match r {
-                Ok(n) if n == 0 => return Err(PlcError::Comm("读取数据为空".into())),
-                Ok(n) => {
-                    index += n;
-                    let r = check_mc_3e_binary(&buf[0..index])?;
-                    match r {
-                        Ok(_) => return Ok(()),
-                        Err(_) => continue,
-                    }
-                }
-                Err(err) => return Err(PlcError::Comm(err.to_string())),
-            };
-        }
+        self.channel
+            .send_and_receive(&buf, check_mc_3e_binary)
+            .await?;
+        Ok(())
     }
 
     fn is_connect(&self) -> bool {
-        self.client.is_some()
+        self.channel.is_connect()
     }
 }
 
@@ -440,6 +324,25 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_attach() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let (local, mut remote) = tokio::io::duplex(1024);
+        tokio::spawn(async move {
+            let mut buf = [0u8; 21];
+            remote.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf[11..15], mc::cmd::READ_WORD);
+            remote
+                .write_all(&[0xD0, 0, 0, 0xFF, 0xFF, 3, 0, 4, 0, 0, 0, 0x34, 0x12])
+                .await
+                .unwrap();
+        });
+        let mut plc = Mc3eBinaryTcpPlc::new(Network::default().into(), Duration::from_millis(300));
+        plc.attach(local);
+        let r = plc.read("D0", DataType::Word, 1).await.unwrap();
+        assert_eq!(r, [0x1234]);
+    }
+
     #[test]
     fn move_bit() {
         let value = 0x102030;
@@ -0,0 +1,205 @@
+use crate::{DataType, IAddress, PlcError};
+
+/// Modbus 数据区
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModbusArea {
+    /// 线圈(0x)，可读写
+    Coil,
+    /// 离散输入(1x)，只读
+    DiscreteInput,
+    /// 输入寄存器(3x)，只读
+    InputRegister,
+    /// 保持寄存器(4x)，可读写
+    HoldingRegister,
+}
+
+impl ModbusArea {
+    /// 地址中使用的头部
+    pub fn header(&self) -> &'static str {
+        match self {
+            ModbusArea::Coil => "0x",
+            ModbusArea::DiscreteInput => "1x",
+            ModbusArea::InputRegister => "3x",
+            ModbusArea::HoldingRegister => "4x",
+        }
+    }
+
+    /// 是否为位数据区
+    pub fn is_bit(&self) -> bool {
+        matches!(self, ModbusArea::Coil | ModbusArea::DiscreteInput)
+    }
+
+    /// 是否可以写入
+    pub fn is_writable(&self) -> bool {
+        matches!(self, ModbusArea::Coil | ModbusArea::HoldingRegister)
+    }
+
+    fn from_digit(digit: u8) -> Option<Self> {
+        match digit {
+            b'0' => Some(ModbusArea::Coil),
+            b'1' => Some(ModbusArea::DiscreteInput),
+            b'3' => Some(ModbusArea::InputRegister),
+            b'4' => Some(ModbusArea::HoldingRegister),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "COIL" => Some(ModbusArea::Coil),
+            "DI" => Some(ModbusArea::DiscreteInput),
+            "IR" => Some(ModbusArea::InputRegister),
+            "HR" => Some(ModbusArea::HoldingRegister),
+            _ => None,
+        }
+    }
+}
+
+/// Modbus 寄存器地址
+///
+/// 支持以下格式：
+/// * 按数据区名称，地址为协议地址(从0开始)：`coil:10`、`di:10`、`ir:100`、`hr:100`
+/// * 按数据区编号，地址从1开始：`0x00001`、`1x1`、`3x100`、`4x100`
+/// * 按数据区编号加完整的寄存器编号：`4x40001`、`4x400001`
+/// * 完整的寄存器编号(5位或6位)：`00001`、`10001`、`30001`、`40001`、`400001`
+pub struct ModbusAddress {
+    address_name: String,
+    data_type: DataType,
+    /// 数据区
+    inner_area: ModbusArea,
+    /// 协议地址，从0开始
+    inner_address: u16,
+}
+
+impl ModbusAddress {
+    /// 实例化一个寄存器地址
+    ///
+    /// # Error
+    /// 无效的寄存器地址，或者寄存器不支持指定的数据类型
+    pub fn new(address_name: impl Into<String>, data_type: DataType) -> Result<Self, PlcError> {
+        let address_name = address_name.into().trim().to_uppercase();
+        let invalid =
+            || PlcError::Addr(format!("Modbus 无效的寄存器地址\t寄存器={}", &address_name));
+        let (area, address) = if let Some((name, number)) = address_name.split_once(':') {
+            // 协议地址，从0开始
+            let area = ModbusArea::from_name(name.trim()).ok_or_else(invalid)?;
+            let address = number.trim().parse::<u16>().map_err(|_| invalid())?;
+            (area, address)
+        } else {
+            let bytes = address_name.as_bytes();
+            let (area, number) = match bytes {
+                [digit, b'X', ..] => (ModbusArea::from_digit(*digit), &address_name[2..]),
+                _ if matches!(bytes.len(), 5 | 6) => (
+                    ModbusArea::from_digit(bytes[0]),
+                    // 完整的寄存器编号，例如 40001
+                    &address_name[..],
+                ),
+                _ => (None, ""),
+            };
+            let area = area.ok_or_else(invalid)?;
+            if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            // 5位或6位且首位与数据区编号一致时为完整的寄存器编号，去掉首位
+            let first = area.header().as_bytes()[0];
+            let number = match number.len() {
+                5 | 6 if number.as_bytes()[0] == first => &number[1..],
+                _ => number,
+            };
+            let number = number.parse::<u32>().map_err(|_| invalid())?;
+            if !(1..=65536).contains(&number) {
+                return Err(invalid());
+            }
+            (area, (number - 1) as u16)
+        };
+        if !area.is_bit() && data_type == DataType::Bit {
+            return Err(PlcError::Addr(format!(
+                "Modbus 寄存器不支持按位读写\t寄存器={}",
+                &address_name
+            )));
+        }
+        Ok(Self {
+            address_name,
+            data_type,
+            inner_area: area,
+            inner_address: address,
+        })
+    }
+
+    /// 数据区
+    pub fn area(&self) -> ModbusArea {
+        self.inner_area
+    }
+
+    /// 协议地址，从0开始
+    pub fn protocol_address(&self) -> u16 {
+        self.inner_address
+    }
+}
+
+impl IAddress for ModbusAddress {
+    fn get_address_name(&self) -> &str {
+        &self.address_name
+    }
+
+    fn get_address_header(&self) -> &str {
+        self.inner_area.header()
+    }
+
+    fn get_address(&self) -> u32 {
+        self.inner_address as u32
+    }
+
+    fn get_data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let cases = [
+            ("0x00001", ModbusArea::Coil, 0),
+            ("0x10", ModbusArea::Coil, 9),
+            ("1x1", ModbusArea::DiscreteInput, 0),
+            ("3x30010", ModbusArea::InputRegister, 9),
+            ("4x40001", ModbusArea::HoldingRegister, 0),
+            ("4x400001", ModbusArea::HoldingRegister, 0),
+            ("4x100", ModbusArea::HoldingRegister, 99),
+            ("40001", ModbusArea::HoldingRegister, 0),
+            ("465536", ModbusArea::HoldingRegister, 65535),
+            ("coil:10", ModbusArea::Coil, 10),
+            ("DI:0", ModbusArea::DiscreteInput, 0),
+            ("ir:65535", ModbusArea::InputRegister, 65535),
+            ("hr:100", ModbusArea::HoldingRegister, 100),
+        ];
+        for (name, area, address) in cases {
+            let r = ModbusAddress::new(name, DataType::Word).unwrap();
+            assert_eq!(
+                (r.area(), r.protocol_address()),
+                (area, address),
+                "{}",
+                name
+            );
+        }
+        let invalid = [
+            "0x0", "4x", "2x1", "5x1", "hr:65536", "xx:1", "4x4a", "400", "4x465537",
+        ];
+        for name in invalid {
+            assert!(
+                ModbusAddress::new(name, DataType::Word).is_err(),
+                "{}",
+                name
+            );
+        }
+        assert!(ModbusAddress::new("hr:0", DataType::Bit).is_err());
+        assert!(ModbusAddress::new("coil:0", DataType::Bit).is_ok());
+    }
+}
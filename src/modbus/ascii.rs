@@ -0,0 +1,99 @@
+use crate::PlcError;
+
+/// LRC 校验：所有字节求和后取补码
+pub fn lrc(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg()
+}
+
+/// 创建 Modbus ASCII 报文：`:` + 十六进制(从站地址 + PDU + LRC) + CRLF
+pub fn create_ascii_frame(slave: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(pdu.len() + 2);
+    data.push(slave);
+    data.extend_from_slice(pdu);
+    data.push(lrc(&data));
+    let mut frame = Vec::with_capacity(data.len() * 2 + 3);
+    frame.push(b':');
+    for byte in data {
+        frame.extend_from_slice(format!("{:02X}", byte).as_bytes());
+    }
+    frame.extend_from_slice(b"\r\n");
+    frame
+}
+
+/// 检查 Modbus ASCII 回复数据是否完整
+/// #Return
+/// * `Ok(OK(buf))` => 数据完整，返回完整的报文(含结束符)
+/// * `Ok(Err(err))` => 数据不完整，需要等待接收剩余部分
+/// * `Err(err)` => 接收数据错误
+pub fn check_ascii_frame(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    match buf.first() {
+        None => return Ok(Err("数据未接收完成")),
+        Some(b':') => {}
+        Some(_) => {
+            return Err(PlcError::Comm(format!(
+                "Modbus ASCII 起始符错误\t数据={:02X?}",
+                buf
+            )))
+        }
+    }
+    match buf.windows(2).position(|window| window == b"\r\n") {
+        Some(p) => Ok(Ok(&buf[..p + 2])),
+        None => Ok(Err("未找到结束符 0x0D 0x0A")),
+    }
+}
+
+/// 检查回复报文的 LRC，返回从站地址和 PDU
+pub fn ascii_pdu(frame: &[u8]) -> Result<(u8, Vec<u8>), PlcError> {
+    let invalid = || PlcError::Comm(format!("Modbus ASCII 报文格式错误\t数据={:02X?}", frame));
+    let hex = frame
+        .strip_prefix(b":")
+        .and_then(|frame| frame.strip_suffix(b"\r\n"))
+        .ok_or_else(invalid)?;
+    if hex.len() < 6 || hex.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let data = hex
+        .chunks(2)
+        .map(|byte| {
+            std::str::from_utf8(byte)
+                .ok()
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    if lrc(&data) != 0 {
+        return Err(PlcError::Comm(format!(
+            "Modbus ASCII LRC 校验错误\t数据={}",
+            String::from_utf8_lossy(frame).trim_end()
+        )));
+    }
+    Ok((data[0], data[1..data.len() - 1].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_frame() {
+        let frame = create_ascii_frame(0xF7, &[0x03, 0x13, 0x89, 0x00, 0x0A]);
+        assert_eq!(frame, b":F7031389000A60\r\n");
+
+        let reply = create_ascii_frame(0x11, &[0x03, 0x02, 0x12, 0x34]);
+        assert!(check_ascii_frame(&reply[..5]).unwrap().is_err());
+        let mut buf = reply.clone();
+        buf.extend_from_slice(b":11");
+        let frame = check_ascii_frame(&buf).unwrap().unwrap();
+        assert_eq!(frame, reply);
+        assert_eq!(
+            ascii_pdu(frame).unwrap(),
+            (0x11, vec![0x03, 0x02, 0x12, 0x34])
+        );
+        assert!(check_ascii_frame(b"11\r\n").is_err());
+
+        assert!(ascii_pdu(b":1103021234FF\r\n").is_err());
+        assert!(ascii_pdu(b":11030G\r\n").is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::PlcError;
+
+/// MBAP 报文头长度：事务标识(2) + 协议标识(2) + 长度(2) + 单元标识(1)
+pub const MBAP_LEN: usize = 7;
+
+/// 创建 Modbus TCP 报文：MBAP 报文头 + PDU
+pub fn create_frame(transaction: u16, unit_id: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(MBAP_LEN + pdu.len());
+    frame.extend_from_slice(&transaction.to_be_bytes());
+    frame.extend_from_slice(&[0x00, 0x00]);
+    frame.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+    frame.push(unit_id);
+    frame.extend_from_slice(pdu);
+    frame
+}
+
+/// 检查 Modbus TCP 回复数据是否完整
+/// #Return
+/// * `Ok(OK(buf))` => 数据完整，返回完整的报文
+/// * `Ok(Err(err))` => 数据不完整，需要等待接收剩余部分
+/// * `Err(err)` => 接收数据错误
+pub fn check_frame(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    if buf.len() < MBAP_LEN {
+        return Ok(Err("数据未接收完成"));
+    }
+    if buf[2..4] != [0x00, 0x00] {
+        return Err(PlcError::Comm(format!(
+            "Modbus 协议标识错误\t数据={:02X?}",
+            &buf[..MBAP_LEN]
+        )));
+    }
+    let len = u16::from_be_bytes([buf[4], buf[5]]) as usize;
+    if !(2..=254).contains(&len) {
+        return Err(PlcError::Comm(format!("Modbus 报文长度错误\t长度={}", len)));
+    }
+    match buf.len() >= len + 6 {
+        true => Ok(Ok(&buf[..len + 6])),
+        false => Ok(Err("数据未接收完成")),
+    }
+}
+
+/// 检查回复报文的事务标识和单元标识，返回 PDU
+pub fn frame_pdu(frame: &[u8], transaction: u16, unit_id: u8) -> Result<&[u8], PlcError> {
+    if frame.len() <= MBAP_LEN || frame[0..2] != transaction.to_be_bytes() || frame[6] != unit_id {
+        return Err(PlcError::Comm(format!(
+            "Modbus 回复与请求不一致\t事务标识={}\t单元标识={}\t数据={:02X?}",
+            transaction, unit_id, frame
+        )));
+    }
+    Ok(&frame[MBAP_LEN..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame() {
+        let frame = create_frame(0x0102, 0x11, &[0x01, 0x00, 0x13, 0x00, 0x25]);
+        assert_eq!(
+            frame,
+            [0x01, 0x02, 0x00, 0x00, 0x00, 0x06, 0x11, 0x01, 0x00, 0x13, 0x00, 0x25]
+        );
+        let reply = [
+            0x01, 0x02, 0x00, 0x00, 0x00, 0x04, 0x11, 0x01, 0x01, 0x05, 0xFF,
+        ];
+        assert!(check_frame(&reply[..9]).unwrap().is_err());
+        let frame = check_frame(&reply).unwrap().unwrap();
+        assert_eq!(frame.len(), 10);
+        assert_eq!(frame_pdu(frame, 0x0102, 0x11).unwrap(), [0x01, 0x01, 0x05]);
+        assert!(frame_pdu(frame, 0x0103, 0x11).is_err());
+        assert!(check_frame(&[0, 1, 0, 1, 0, 4, 1]).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+// ! Modbus 协议
+
+mod address;
+mod ascii;
+mod mbap;
+#[cfg(unix)]
+mod modbus_rtu;
+mod modbus_tcp;
+mod pdu;
+mod request;
+mod rtu;
+
+pub use self::address::{ModbusAddress, ModbusArea};
+pub(crate) use self::mbap::{check_frame, create_frame, frame_pdu};
+#[cfg(unix)]
+pub use self::modbus_rtu::{ModbusFraming, ModbusRtuPlc};
+pub use self::modbus_tcp::ModbusTcpPlc;
+pub(crate) use self::pdu::{
+    check_write_reply, parse_bits, read_request, write_coils_request, FunctionCode,
+};
+use crate::{core::PlcConnector, IPlc};
+use std::time::Duration;
+
+/// 创建一个 Modbus TCP 客户端
+pub fn new_modbus_tcp_plc(conn: PlcConnector, timeout: Duration) -> ModbusTcpPlc {
+    ModbusTcpPlc::new(conn, timeout)
+}
+
+/// 创建一个 Modbus RTU/ASCII 客户端(串口或者 TCP 透传)
+#[cfg(unix)]
+pub fn new_modbus_rtu_plc(conn: PlcConnector, timeout: Duration) -> ModbusRtuPlc {
+    ModbusRtuPlc::new(conn, timeout)
+}
@@ -0,0 +1,497 @@
+// ! Modbus RTU/ASCII 协议(串口，或者通过串口服务器透传的 TCP)
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep_until, Instant};
+use tracing::{event, Level};
+
+use crate::core::{open_transport, Channel, Transport};
+use crate::prelude::*;
+
+use super::ascii::{ascii_pdu, check_ascii_frame, create_ascii_frame};
+use super::request::{ReadRequest, WriteRequest};
+use super::rtu::{check_rtu_frame, create_rtu_frame, rtu_pdu};
+
+/// 串口调度有延迟(例如 USB 转串口)，接收时字符间的静默时间不小于此值才认为报文中断
+const MIN_SILENCE: Duration = Duration::from_millis(20);
+
+/// Modbus 串行链路的报文格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModbusFraming {
+    /// 二进制报文，CRC 校验，报文之间以 3.5 个字符的静默时间分隔
+    Rtu,
+    /// 十六进制字符报文，LRC 校验，以 `:` 开始，回车换行结束
+    Ascii,
+}
+
+impl ModbusFraming {
+    fn create_frame(&self, slave: u8, pdu: &[u8]) -> Vec<u8> {
+        match self {
+            ModbusFraming::Rtu => create_rtu_frame(slave, pdu),
+            ModbusFraming::Ascii => create_ascii_frame(slave, pdu),
+        }
+    }
+
+    fn check_frame<'a>(&self, buf: &'a [u8]) -> Result<Result<&'a [u8], &'a str>, PlcError> {
+        match self {
+            ModbusFraming::Rtu => check_rtu_frame(buf),
+            ModbusFraming::Ascii => check_ascii_frame(buf),
+        }
+    }
+
+    fn frame_pdu(&self, frame: &[u8]) -> Result<(u8, Vec<u8>), PlcError> {
+        match self {
+            ModbusFraming::Rtu => rtu_pdu(frame).map(|(slave, pdu)| (slave, pdu.to_vec())),
+            ModbusFraming::Ascii => ascii_pdu(frame),
+        }
+    }
+}
+
+/// 串行总线的传输层：发送前等待帧间隔，接收时字符间的静默时间超过帧间隔则丢弃不完整的报文
+struct RtuTransport {
+    stream: Box<dyn Transport>,
+    /// 帧间隔(3.5个字符时间)，ASCII 时为0
+    frame_gap: Duration,
+    /// 一个字符的传输时间
+    char_time: Duration,
+    /// 接收时字符间的静默时间超过此值则丢弃不完整的报文
+    silence: Option<Duration>,
+    /// 总线上最后一次收发数据的时间
+    last_activity: Instant,
+}
+
+impl RtuTransport {
+    fn new(stream: Box<dyn Transport>, param: &SerailPort, framing: ModbusFraming) -> Self {
+        let char_time = param.char_time();
+        let (frame_gap, silence) = match framing {
+            ModbusFraming::Rtu => {
+                // 波特率大于 19200 时使用固定的 1.75ms
+                let frame_gap = match param.baud_rate {
+                    0..=19200 => char_time.mul_f64(3.5),
+                    _ => Duration::from_micros(1750),
+                };
+                (frame_gap, Some(frame_gap.max(MIN_SILENCE)))
+            }
+            ModbusFraming::Ascii => (Duration::ZERO, None),
+        };
+        // 设置了字符间超时时优先使用
+        let silence = param.inter_byte_timeout.or(silence);
+        Self {
+            stream,
+            frame_gap,
+            char_time,
+            silence,
+            last_activity: Instant::now(),
+        }
+    }
+
+    /// 等待总线静默一个帧间隔后发送，返回时报文已经发送完成
+    async fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        sleep_until(self.last_activity + self.frame_gap).await;
+        self.stream.send_frame(buf).await?;
+        let sent = Instant::now() + self.char_time * buf.len() as u32;
+        self.last_activity = sent;
+        // 从发送完成开始计算超时时间，广播时也需要等待发送完成
+        sleep_until(sent).await;
+        Ok(())
+    }
+}
+
+impl Transport for RtuTransport {
+    fn clear_input(&mut self) -> PlcResult {
+        self.stream.clear_input()
+    }
+
+    fn send_frame<'a>(
+        &'a mut self,
+        buf: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>> {
+        Box::pin(self.send(buf))
+    }
+
+    fn inter_byte_timeout(&self) -> Option<Duration> {
+        self.silence
+    }
+}
+
+impl AsyncRead for RtuTransport {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let r = Pin::new(&mut self.stream).poll_read(cx, buf);
+        if buf.filled().len() > filled {
+            self.last_activity = Instant::now();
+        }
+        r
+    }
+}
+
+impl AsyncWrite for RtuTransport {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Modbus RTU/ASCII 客户端
+///
+/// 连接参数为 SerialPort 时使用串口，为 Network 时通过 TCP 连接串口服务器透传报文。
+/// 报文格式默认为 RTU，可以通过 [`ModbusRtuPlc::framing`] 设置为 ASCII。
+///
+/// 克隆的客户端共用同一个连接，通过 [`ModbusRtuPlc::slave`] 设置不同的从站地址即可访问总线上的多个从站，
+/// 请求按顺序在总线上发送。需要在连接后克隆，才能共用连接。
+pub struct ModbusRtuPlc {
+    /// 通讯通道
+    channel: Channel,
+    /// 报文格式
+    framing: ModbusFraming,
+    /// 从站地址
+    slave: u8,
+}
+
+impl Clone for ModbusRtuPlc {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            framing: self.framing,
+            slave: self.slave,
+        }
+    }
+}
+
+impl ModbusRtuPlc {
+    /// 设置报文格式，默认为 RTU，需要在连接前设置
+    pub fn framing(mut self, framing: ModbusFraming) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// 设置从站地址 1~247，默认为1
+    ///
+    /// 地址为0时为广播，只能写入，从站不回复
+    pub fn slave(mut self, slave: u8) -> Self {
+        self.slave = slave;
+        self
+    }
+
+    /// 读写多个寄存器(功能码23)，先写入后读取
+    ///
+    /// # Param
+    /// * `read_address` - 读取的保持寄存器起始地址
+    /// * `len` - 读取数量 1~125
+    /// * `write_address` - 写入的保持寄存器起始地址
+    /// * `datas` - 写入的数据，数量 1~121
+    pub async fn read_write(
+        &self,
+        read_address: impl Into<String>,
+        len: u16,
+        write_address: impl Into<String>,
+        datas: &[u16],
+    ) -> Result<Vec<u16>, PlcError> {
+        let request = ReadRequest::read_write(read_address, len, write_address, datas)?;
+        self.check_unicast()?;
+        let reply = self.send_and_receive(&request.pdu).await?;
+        request.parse(&reply)
+    }
+
+    /// 广播不能读取数据
+    fn check_unicast(&self) -> PlcResult {
+        match self.slave {
+            0 => Err(PlcError::Param("Modbus 广播地址不能读取数据".into())),
+            _ => Ok(()),
+        }
+    }
+
+    /// 发送请求PDU并返回回复的PDU，广播时返回空数据
+    async fn send_and_receive(&self, pdu: &[u8]) -> Result<Vec<u8>, PlcError> {
+        if self.slave > 247 {
+            return Err(PlcError::Param(format!(
+                "Modbus 从站地址超出范围[0~247]\t地址={}",
+                self.slave
+            )));
+        }
+        let frame = self.framing.create_frame(self.slave, pdu);
+        if self.slave == 0 {
+            self.channel.send(&frame).await?;
+            return Ok(Vec::new());
+        }
+        let (framing, slave) = (self.framing, self.slave);
+        let reply = self
+            .channel
+            .send_and_receive(&frame, |mut buf| {
+                // 缓冲区中可能包含其他从站迟到的回复，依次处理
+                while let Ok(reply) = framing.check_frame(buf)? {
+                    if framing.frame_pdu(reply)?.0 == slave {
+                        return Ok(Ok(reply));
+                    }
+                    event!(Level::WARN, "丢弃其他从站的回复\t回复={:02X?}", reply);
+                    buf = &buf[reply.len()..];
+                }
+                Ok(Err("数据不完整"))
+            })
+            .await?;
+        Ok(framing.frame_pdu(&reply)?.1)
+    }
+}
+
+unsafe impl Send for ModbusRtuPlc {}
+
+unsafe impl Sync for ModbusRtuPlc {}
+
+impl IPlc for ModbusRtuPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        ModbusRtuPlc {
+            channel: Channel::new(conn, timeout),
+            framing: ModbusFraming::Rtu,
+            slave: 1,
+        }
+    }
+
+    /// 连接：TCP 透传时报文可能被拆分为多个 TCP 包，不按静默时间分隔报文
+    async fn connect(&mut self) -> PlcResult {
+        let PlcConnector::SerialPort(param) = self.channel.conn() else {
+            return self.channel.connect().await;
+        };
+        let stream = open_transport(self.channel.conn(), self.channel.timeout()).await?;
+        let transport = RtuTransport::new(stream, param, self.framing);
+        self.channel.attach(transport);
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    /// 读取数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `0x00001`、`1x1`、`3x100`、`4x40001`、`coil:10`、`hr:100`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 读取线圈或者离散输入，每个点一个数据
+    ///     * Word 读取寄存器；读取线圈或者离散输入时每16个点组成一个字，起始地址为 bit0
+    /// * `len` - 数据长度
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        let request = ReadRequest::new(address_name, data_type, len)?;
+        self.check_unicast()?;
+        let reply = self.send_and_receive(&request.pdu).await?;
+        request.parse(&reply)
+    }
+
+    /// 写入数据
+    ///
+    /// 写入一个数据时使用功能码5/6，多个数据时使用功能码15/16；广播时不等待回复
+    ///
+    /// # Param
+    /// * `address_name` - 线圈或者保持寄存器地址
+    /// * `data_type` - 数据类型：
+    ///     * Bit 写入线圈，0：关闭；1：打开
+    ///     * Word 写入保持寄存器；写入线圈时每个字拆分为16个点，bit0 为起始地址
+    /// * `datas` - 需要写入的数据
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        let request = WriteRequest::new(address_name, data_type, datas)?;
+        let reply = self.send_and_receive(&request.pdu).await?;
+        match self.slave {
+            0 => Ok(()),
+            _ => request.check(&reply),
+        }
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{open_pty, SerialStream};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::time::sleep;
+
+    /// 模拟从站：依次检查请求报文并回复，回复为空时不回复
+    ///
+    /// 任务结束后返回主设备，避免串口提前关闭
+    fn mock_slave(
+        exchanges: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> (String, tokio::task::JoinHandle<SerialStream>) {
+        let (mut master, name) = open_pty();
+        let task = tokio::spawn(async move {
+            for (request, reply) in exchanges {
+                let mut buf = vec![0u8; request.len()];
+                master.read_exact(&mut buf).await.unwrap();
+                assert_eq!(buf, request);
+                master.write_all(&reply).await.unwrap();
+            }
+            master
+        });
+        (name, task)
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let frame = create_rtu_frame;
+        let (name, task) = mock_slave(vec![
+            (
+                frame(0x11, &[0x03, 0x00, 0x00, 0x00, 0x02]),
+                frame(0x11, &[0x03, 0x04, 0x00, 0x01, 0x12, 0x34]),
+            ),
+            (
+                frame(0x11, &[0x06, 0x00, 0x63, 0x00, 0x07]),
+                frame(0x11, &[0x06, 0x00, 0x63, 0x00, 0x07]),
+            ),
+            (
+                frame(0x11, &[0x03, 0x27, 0x0F, 0x00, 0x01]),
+                frame(0x11, &[0x83, 0x02]),
+            ),
+            // 其他从站的回复被丢弃
+            (frame(0x02, &[0x01, 0x00, 0x0A, 0x00, 0x03]), {
+                let mut reply = frame(0x11, &[0x83, 0x02]);
+                reply.extend(frame(0x02, &[0x01, 0x01, 0x05]));
+                reply
+            }),
+            // 广播不回复
+            (frame(0x00, &[0x05, 0x00, 0x0A, 0xFF, 0x00]), vec![]),
+            // CRC 错误
+            (frame(0x02, &[0x04, 0x00, 0x00, 0x00, 0x01]), {
+                let mut reply = frame(0x02, &[0x04, 0x02, 0x00, 0x01]);
+                reply[4] ^= 0xFF;
+                reply
+            }),
+            // 不完整的报文被丢弃
+            (
+                frame(0x02, &[0x04, 0x00, 0x00, 0x00, 0x01]),
+                vec![0x02, 0x04],
+            ),
+        ]);
+        let param = SerailPort {
+            port_name: name,
+            baud_rate: 115200,
+            parity: 0,
+            ..Default::default()
+        };
+        let mut plc = ModbusRtuPlc::new(param.into(), Duration::from_millis(200)).slave(0x11);
+        plc.connect().await.unwrap();
+        let r = plc.read("4x40001", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x0001, 0x1234]);
+        plc.write("hr:99", DataType::Word, &[7]).await.unwrap();
+        let r = plc.read("hr:9999", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+
+        // 克隆的客户端共用串口
+        let other = plc.clone().slave(0x02);
+        let r = other.read("coil:10", DataType::Bit, 3).await.unwrap();
+        assert_eq!(r, [1, 0, 1]);
+        let broadcast = plc.clone().slave(0);
+        broadcast
+            .write("coil:10", DataType::Bit, &[1])
+            .await
+            .unwrap();
+        assert!(broadcast.read("coil:10", DataType::Bit, 1).await.is_err());
+        assert!(plc
+            .clone()
+            .slave(248)
+            .read("hr:0", DataType::Word, 1)
+            .await
+            .is_err());
+        assert!(other.read("ir:0", DataType::Word, 1).await.is_err());
+        let r = other.read("ir:0", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::Timeout)));
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ascii() {
+        let frame = create_ascii_frame;
+        let (name, task) = mock_slave(vec![
+            (
+                frame(0x11, &[0x03, 0x00, 0x00, 0x00, 0x02]),
+                frame(0x11, &[0x03, 0x04, 0x00, 0x01, 0x12, 0x34]),
+            ),
+            (
+                frame(0x11, &[0x05, 0x00, 0x0A, 0xFF, 0x00]),
+                frame(0x11, &[0x05, 0x00, 0x0A, 0xFF, 0x00]),
+            ),
+            // LRC 错误
+            (
+                frame(0x11, &[0x04, 0x00, 0x00, 0x00, 0x01]),
+                b":110402000100\r\n".to_vec(),
+            ),
+        ]);
+        let param = SerailPort {
+            port_name: name,
+            data_bits: 7,
+            parity: 2,
+            ..Default::default()
+        };
+        let mut plc = ModbusRtuPlc::new(param.into(), Duration::from_millis(200))
+            .framing(ModbusFraming::Ascii)
+            .slave(0x11);
+        plc.connect().await.unwrap();
+        let r = plc.read("hr:0", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x0001, 0x1234]);
+        plc.write("coil:10", DataType::Bit, &[1]).await.unwrap();
+        assert!(plc.read("ir:0", DataType::Word, 1).await.is_err());
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rtu_over_tcp() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 8];
+            socket.read_exact(&mut buf).await.unwrap();
+            assert_eq!(
+                buf[..],
+                create_rtu_frame(0x05, &[0x04, 0x00, 0x09, 0x00, 0x01])
+            );
+            // 回复被拆分为多个 TCP 包
+            let reply = create_rtu_frame(0x05, &[0x04, 0x02, 0xFF, 0xFF]);
+            socket.write_all(&reply[..3]).await.unwrap();
+            sleep(Duration::from_millis(50)).await;
+            socket.write_all(&reply[3..]).await.unwrap();
+        });
+        let mut plc = ModbusRtuPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        )
+        .slave(0x05);
+        plc.connect().await.unwrap();
+        let r = plc.read("3x10", DataType::Word, 1).await.unwrap();
+        assert_eq!(r, [0xFFFF]);
+        // 连接断开时与其他协议一样返回 Io 错误，由调用方重新连接
+        let r = plc.read("3x10", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::Io(_))));
+        plc.disconnect().await.unwrap();
+        assert!(!plc.is_connect());
+    }
+}
@@ -0,0 +1,253 @@
+// ! Modbus TCP 协议
+
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{event, Level};
+
+use crate::core::{Channel, Transport};
+use crate::prelude::*;
+
+use super::mbap::{check_frame, create_frame, frame_pdu};
+use super::request::{ReadRequest, WriteRequest};
+
+/// Modbus TCP 客户端
+pub struct ModbusTcpPlc {
+    /// 通讯通道
+    channel: Channel,
+    /// 单元标识(从站地址)
+    unit_id: u8,
+    /// 事务标识
+    transaction: Arc<AtomicU16>,
+}
+
+impl Clone for ModbusTcpPlc {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            unit_id: self.unit_id,
+            transaction: self.transaction.clone(),
+        }
+    }
+}
+
+impl ModbusTcpPlc {
+    /// 设置单元标识(从站地址)，默认为1
+    ///
+    /// 直连设备一般为1或者255，通过网关访问串口设备时为串口设备的从站地址
+    pub fn unit_id(mut self, unit_id: u8) -> Self {
+        self.unit_id = unit_id;
+        self
+    }
+
+    /// 读写多个寄存器(功能码23)，先写入后读取
+    ///
+    /// # Param
+    /// * `read_address` - 读取的保持寄存器起始地址
+    /// * `len` - 读取数量 1~125
+    /// * `write_address` - 写入的保持寄存器起始地址
+    /// * `datas` - 写入的数据，数量 1~121
+    pub async fn read_write(
+        &self,
+        read_address: impl Into<String>,
+        len: u16,
+        write_address: impl Into<String>,
+        datas: &[u16],
+    ) -> Result<Vec<u16>, PlcError> {
+        let request = ReadRequest::read_write(read_address, len, write_address, datas)?;
+        let reply = self.send_and_receive(&request.pdu).await?;
+        request.parse(&reply)
+    }
+
+    /// 使用已经打开的连接代替 `connect`
+    pub fn attach(&mut self, transport: impl Transport + 'static) {
+        self.channel.attach(transport);
+    }
+
+    /// 发送请求PDU并返回回复的PDU
+    async fn send_and_receive(&self, pdu: &[u8]) -> Result<Vec<u8>, PlcError> {
+        let transaction = self.transaction.fetch_add(1, Ordering::Relaxed);
+        let frame = create_frame(transaction, self.unit_id, pdu);
+        let reply = self
+            .channel
+            .send_and_receive(&frame, |mut buf| {
+                // 缓冲区中可能包含上一次超时后迟到的回复，依次处理
+                while let Ok(reply) = check_frame(buf)? {
+                    if reply[0..2] == transaction.to_be_bytes() {
+                        return Ok(Ok(reply));
+                    }
+                    event!(Level::WARN, "丢弃其他事务的回复\t回复={:02X?}", reply);
+                    buf = &buf[reply.len()..];
+                }
+                Ok(Err("数据不完整"))
+            })
+            .await?;
+        Ok(frame_pdu(&reply, transaction, self.unit_id)?.to_vec())
+    }
+}
+
+unsafe impl Send for ModbusTcpPlc {}
+
+unsafe impl Sync for ModbusTcpPlc {}
+
+impl IPlc for ModbusTcpPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        ModbusTcpPlc {
+            channel: Channel::new(conn, timeout),
+            unit_id: 1,
+            transaction: Arc::new(AtomicU16::new(0)),
+        }
+    }
+
+    async fn connect(&mut self) -> PlcResult {
+        self.channel.connect().await
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    /// 读取数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `0x00001`、`1x1`、`3x100`、`4x40001`、`coil:10`、`hr:100`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 读取线圈或者离散输入，每个点一个数据
+    ///     * Word 读取寄存器；读取线圈或者离散输入时每16个点组成一个字，起始地址为 bit0
+    /// * `len` - 数据长度
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        let request = ReadRequest::new(address_name, data_type, len)?;
+        let reply = self.send_and_receive(&request.pdu).await?;
+        request.parse(&reply)
+    }
+
+    /// 写入数据
+    ///
+    /// 写入一个数据时使用功能码5/6，多个数据时使用功能码15/16
+    ///
+    /// # Param
+    /// * `address_name` - 线圈或者保持寄存器地址
+    /// * `data_type` - 数据类型：
+    ///     * Bit 写入线圈，0：关闭；1：打开
+    ///     * Word 写入保持寄存器；写入线圈时每个字拆分为16个点，bit0 为起始地址
+    /// * `datas` - 需要写入的数据
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        let request = WriteRequest::new(address_name, data_type, datas)?;
+        let reply = self.send_and_receive(&request.pdu).await?;
+        request.check(&reply)
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 模拟从站：依次检查请求PDU并回复
+    async fn mock_server(exchanges: Vec<(Vec<u8>, Vec<u8>)>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            for (request, reply) in exchanges {
+                let mut header = [0u8; 7];
+                socket.read_exact(&mut header).await.unwrap();
+                let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+                let mut pdu = vec![0u8; len - 1];
+                socket.read_exact(&mut pdu).await.unwrap();
+                assert_eq!(header[6], 0x11);
+                assert_eq!(pdu, request);
+                let frame = create_frame(u16::from_be_bytes([header[0], header[1]]), 0x11, &reply);
+                socket.write_all(&frame).await.unwrap();
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let port = mock_server(vec![
+            (
+                vec![0x03, 0x00, 0x00, 0x00, 0x02],
+                vec![0x03, 0x04, 0x00, 0x01, 0x12, 0x34],
+            ),
+            (
+                vec![0x04, 0x00, 0x09, 0x00, 0x01],
+                vec![0x04, 0x02, 0xFF, 0xFF],
+            ),
+            (vec![0x01, 0x00, 0x0A, 0x00, 0x03], vec![0x01, 0x01, 0x05]),
+            (
+                vec![0x02, 0x00, 0x00, 0x00, 0x10],
+                vec![0x02, 0x02, 0x01, 0x80],
+            ),
+            (
+                vec![0x06, 0x00, 0x63, 0x00, 0x07],
+                vec![0x06, 0x00, 0x63, 0x00, 0x07],
+            ),
+            (
+                vec![0x10, 0x00, 0x63, 0x00, 0x02, 0x04, 0x00, 0x01, 0x00, 0x02],
+                vec![0x10, 0x00, 0x63, 0x00, 0x02],
+            ),
+            (
+                vec![0x05, 0x00, 0x0A, 0xFF, 0x00],
+                vec![0x05, 0x00, 0x0A, 0xFF, 0x00],
+            ),
+            (
+                vec![0x0F, 0x00, 0x0A, 0x00, 0x03, 0x01, 0x05],
+                vec![0x0F, 0x00, 0x0A, 0x00, 0x03],
+            ),
+            (
+                vec![
+                    0x17, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x02, 0x00, 0x09,
+                ],
+                vec![0x17, 0x02, 0x00, 0x08],
+            ),
+            (vec![0x03, 0x27, 0x0F, 0x00, 0x01], vec![0x83, 0x02]),
+        ])
+        .await;
+        let mut plc = ModbusTcpPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        )
+        .unit_id(0x11);
+        plc.connect().await.unwrap();
+        let r = plc.read("4x40001", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x0001, 0x1234]);
+        let r = plc.read("3x10", DataType::Word, 1).await.unwrap();
+        assert_eq!(r, [0xFFFF]);
+        let r = plc.read("coil:10", DataType::Bit, 3).await.unwrap();
+        assert_eq!(r, [1, 0, 1]);
+        let r = plc.read("1x1", DataType::Word, 1).await.unwrap();
+        assert_eq!(r, [0x8001]);
+        plc.write("hr:99", DataType::Word, &[7]).await.unwrap();
+        plc.write("4x100", DataType::Word, &[1, 2]).await.unwrap();
+        plc.write("0x11", DataType::Bit, &[1]).await.unwrap();
+        plc.write("coil:10", DataType::Bit, &[1, 0, 1])
+            .await
+            .unwrap();
+        let r = plc.read_write("hr:0", 1, "hr:1", &[9]).await.unwrap();
+        assert_eq!(r, [8]);
+        let r = plc.read("hr:9999", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+        // 只读数据区
+        assert!(plc.write("3x1", DataType::Word, &[1]).await.is_err());
+        assert!(plc.write("1x1", DataType::Bit, &[1]).await.is_err());
+        assert!(plc.read("hr:65535", DataType::Word, 2).await.is_err());
+        assert!(plc.read("hr:0", DataType::Word, 126).await.is_err());
+    }
+}
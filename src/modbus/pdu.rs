@@ -0,0 +1,243 @@
+use crate::PlcError;
+
+/// Modbus 功能码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionCode {
+    /// 读线圈
+    ReadCoils = 0x01,
+    /// 读离散输入
+    ReadDiscreteInputs = 0x02,
+    /// 读保持寄存器
+    ReadHoldingRegisters = 0x03,
+    /// 读输入寄存器
+    ReadInputRegisters = 0x04,
+    /// 写单个线圈
+    WriteSingleCoil = 0x05,
+    /// 写单个寄存器
+    WriteSingleRegister = 0x06,
+    /// 写多个线圈
+    WriteMultipleCoils = 0x0F,
+    /// 写多个寄存器
+    WriteMultipleRegisters = 0x10,
+    /// 读写多个寄存器
+    ReadWriteMultipleRegisters = 0x17,
+}
+
+/// 单次读取的最大线圈/离散输入数量
+pub const MAX_READ_BITS: u16 = 2000;
+
+/// 单次读取的最大寄存器数量
+pub const MAX_READ_REGISTERS: u16 = 125;
+
+/// 单次写入的最大线圈数量
+pub const MAX_WRITE_BITS: u16 = 1968;
+
+/// 单次写入的最大寄存器数量
+pub const MAX_WRITE_REGISTERS: u16 = 123;
+
+/// 读写多个寄存器时单次写入的最大寄存器数量
+pub const MAX_READ_WRITE_REGISTERS: u16 = 121;
+
+/// 创建读取请求：功能码(1) + 起始地址(2) + 数量(2)
+pub fn read_request(code: FunctionCode, address: u16, count: u16) -> Vec<u8> {
+    let mut pdu = vec![code as u8];
+    pdu.extend_from_slice(&address.to_be_bytes());
+    pdu.extend_from_slice(&count.to_be_bytes());
+    pdu
+}
+
+/// 创建写多个线圈请求：功能码(1) + 起始地址(2) + 数量(2) + 字节数(1) + 线圈状态(低位在前)
+pub fn write_coils_request(address: u16, states: &[u16]) -> Vec<u8> {
+    let mut pdu = vec![FunctionCode::WriteMultipleCoils as u8];
+    pdu.extend_from_slice(&address.to_be_bytes());
+    pdu.extend_from_slice(&(states.len() as u16).to_be_bytes());
+    let bytes = pack_bits(states);
+    pdu.push(bytes.len() as u8);
+    pdu.extend_from_slice(&bytes);
+    pdu
+}
+
+/// 创建写单个线圈请求：功能码(1) + 地址(2) + 状态(2，0xFF00：打开；0x0000：关闭)
+pub fn write_coil_request(address: u16, state: u16) -> Vec<u8> {
+    let value: u16 = if state != 0 { 0xFF00 } else { 0x0000 };
+    write_single_request(FunctionCode::WriteSingleCoil, address, value)
+}
+
+/// 创建写单个寄存器请求：功能码(1) + 地址(2) + 数据(2)
+pub fn write_register_request(address: u16, value: u16) -> Vec<u8> {
+    write_single_request(FunctionCode::WriteSingleRegister, address, value)
+}
+
+fn write_single_request(code: FunctionCode, address: u16, value: u16) -> Vec<u8> {
+    let mut pdu = vec![code as u8];
+    pdu.extend_from_slice(&address.to_be_bytes());
+    pdu.extend_from_slice(&value.to_be_bytes());
+    pdu
+}
+
+/// 创建写多个寄存器请求：功能码(1) + 起始地址(2) + 数量(2) + 字节数(1) + 数据(高字节在前)
+pub fn write_registers_request(address: u16, datas: &[u16]) -> Vec<u8> {
+    let mut pdu = vec![FunctionCode::WriteMultipleRegisters as u8];
+    pdu.extend_from_slice(&address.to_be_bytes());
+    pdu.extend_from_slice(&(datas.len() as u16).to_be_bytes());
+    pdu.push((datas.len() * 2) as u8);
+    datas
+        .iter()
+        .for_each(|data| pdu.extend_from_slice(&data.to_be_bytes()));
+    pdu
+}
+
+/// 创建读写多个寄存器请求：功能码(1) + 读起始地址(2) + 读数量(2) + 写起始地址(2) + 写数量(2) + 字节数(1) + 写入数据
+pub fn read_write_registers_request(
+    read_address: u16,
+    read_count: u16,
+    write_address: u16,
+    datas: &[u16],
+) -> Vec<u8> {
+    let mut pdu = vec![FunctionCode::ReadWriteMultipleRegisters as u8];
+    pdu.extend_from_slice(&read_address.to_be_bytes());
+    pdu.extend_from_slice(&read_count.to_be_bytes());
+    // 写入部分与写多个寄存器请求去掉功能码后的格式一致
+    pdu.extend_from_slice(&write_registers_request(write_address, datas)[1..]);
+    pdu
+}
+
+/// 检查回复的功能码，异常回复转换为错误
+///
+/// # Return
+/// 去掉功能码后的数据
+pub fn check_reply(code: FunctionCode, pdu: &[u8]) -> Result<&[u8], PlcError> {
+    match pdu {
+        [fc, data @ ..] if *fc == code as u8 => Ok(data),
+        [fc, exception, ..] if *fc == code as u8 | 0x80 => Err(exception_error(*exception)),
+        _ => Err(PlcError::Comm(format!(
+            "Modbus 回复的功能码错误\t功能码={:#04X}\t数据={:02X?}",
+            code as u8, pdu
+        ))),
+    }
+}
+
+/// 解析读取线圈/离散输入的回复数据
+pub fn parse_bits(code: FunctionCode, pdu: &[u8], count: u16) -> Result<Vec<u16>, PlcError> {
+    let data = check_reply(code, pdu)?;
+    let bytes = (count as usize).div_ceil(8);
+    match data {
+        [len, bits @ ..] if *len as usize == bytes && bits.len() == bytes => Ok((0..count
+            as usize)
+            .map(|i| ((bits[i / 8] >> (i % 8)) & 1) as u16)
+            .collect()),
+        _ => Err(PlcError::Comm(format!(
+            "Modbus 回复的数据长度错误\t数据={:02X?}",
+            pdu
+        ))),
+    }
+}
+
+/// 解析读取寄存器的回复数据
+pub fn parse_registers(code: FunctionCode, pdu: &[u8], count: u16) -> Result<Vec<u16>, PlcError> {
+    let data = check_reply(code, pdu)?;
+    let bytes = count as usize * 2;
+    match data {
+        [len, words @ ..] if *len as usize == bytes && words.len() == bytes => Ok(words
+            .chunks(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect()),
+        _ => Err(PlcError::Comm(format!(
+            "Modbus 回复的数据长度错误\t数据={:02X?}",
+            pdu
+        ))),
+    }
+}
+
+/// 检查写入请求的回复：起始地址(2) + 数量(2)(写单个时为数据(2)) 与请求一致
+pub fn check_write_reply(code: FunctionCode, pdu: &[u8], request: &[u8]) -> Result<(), PlcError> {
+    let data = check_reply(code, pdu)?;
+    if data.len() != 4 || request.len() < 5 || data != &request[1..5] {
+        return Err(PlcError::Comm(format!(
+            "Modbus 写入回复与请求不一致\t数据={:02X?}",
+            pdu
+        )));
+    }
+    Ok(())
+}
+
+/// 每8个位组成一个字节，低位在前
+fn pack_bits(states: &[u16]) -> Vec<u8> {
+    states
+        .chunks(8)
+        .map(|bits| {
+            bits.iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, bit)| byte | (((*bit != 0) as u8) << i))
+        })
+        .collect()
+}
+
+/// Modbus 异常码转换为错误
+pub fn exception_error(code: u8) -> PlcError {
+    match code {
+        0x01 => PlcError::Param("Modbus 异常码01：非法功能码".into()),
+        0x02 => PlcError::Addr("Modbus 异常码02：非法数据地址".into()),
+        0x03 => PlcError::Param("Modbus 异常码03：非法数据值".into()),
+        0x04 => PlcError::Comm("Modbus 异常码04：从站设备故障".into()),
+        0x05 => PlcError::Comm("Modbus 异常码05：请求已确认，正在处理".into()),
+        0x06 => PlcError::Comm("Modbus 异常码06：从站设备忙".into()),
+        0x08 => PlcError::Comm("Modbus 异常码08：存储奇偶性错误".into()),
+        0x0A => PlcError::Comm("Modbus 异常码0A：网关路径不可用".into()),
+        0x0B => PlcError::Timeout,
+        _ => PlcError::Comm(format!("Modbus 未知的异常码{:02X}", code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits() {
+        let pdu = write_coils_request(0x13, &[1, 0, 1, 1, 0, 0, 1, 1, 1, 0]);
+        assert_eq!(pdu, [0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01]);
+        let reply = [0x01, 0x02, 0xCD, 0x01];
+        let bits = parse_bits(FunctionCode::ReadCoils, &reply, 10).unwrap();
+        assert_eq!(bits, [1, 0, 1, 1, 0, 0, 1, 1, 1, 0]);
+        assert!(parse_bits(FunctionCode::ReadCoils, &reply, 17).is_err());
+        assert!(parse_bits(FunctionCode::ReadDiscreteInputs, &reply, 10).is_err());
+    }
+
+    #[test]
+    fn test_exception() {
+        let r = parse_bits(FunctionCode::ReadDiscreteInputs, &[0x82, 0x02], 8);
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+        let request = write_coils_request(0, &[1]);
+        let r = check_write_reply(FunctionCode::WriteMultipleCoils, &[0x8F, 0x0B], &request);
+        assert!(matches!(r, Err(PlcError::Timeout)));
+        let reply = [0x0F, 0x00, 0x00, 0x00, 0x01];
+        assert!(check_write_reply(FunctionCode::WriteMultipleCoils, &reply, &request).is_ok());
+    }
+
+    #[test]
+    fn test_registers() {
+        assert_eq!(write_coil_request(0xAC, 1), [0x05, 0x00, 0xAC, 0xFF, 0x00]);
+        assert_eq!(
+            write_register_request(0x01, 0x03),
+            [0x06, 0x00, 0x01, 0x00, 0x03]
+        );
+        assert_eq!(
+            write_registers_request(0x01, &[0x0A, 0x0102]),
+            [0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02]
+        );
+        assert_eq!(
+            read_write_registers_request(0x03, 6, 0x0E, &[0xFF, 0xFF, 0xFF]),
+            [
+                0x17, 0x00, 0x03, 0x00, 0x06, 0x00, 0x0E, 0x00, 0x03, 0x06, 0x00, 0xFF, 0x00, 0xFF,
+                0x00, 0xFF
+            ]
+        );
+        let reply = [0x03, 0x06, 0x02, 0x2B, 0x00, 0x00, 0x00, 0x64];
+        let words = parse_registers(FunctionCode::ReadHoldingRegisters, &reply, 3).unwrap();
+        assert_eq!(words, [0x022B, 0x0000, 0x0064]);
+        assert!(parse_registers(FunctionCode::ReadHoldingRegisters, &reply, 2).is_err());
+        let r = parse_registers(FunctionCode::ReadInputRegisters, &[0x84, 0x01], 1);
+        assert!(matches!(r, Err(PlcError::Param(_))));
+    }
+}
@@ -0,0 +1,233 @@
+// ! Modbus 请求，TCP/RTU 等不同的报文格式共用
+
+use crate::prelude::*;
+
+use super::address::{ModbusAddress, ModbusArea};
+use super::pdu::{self, FunctionCode};
+
+/// 读取请求
+pub struct ReadRequest {
+    /// 功能码
+    code: FunctionCode,
+    /// 读取的点数或者寄存器数量
+    count: u16,
+    /// 线圈和离散输入按字读取时需要组合为字
+    pack_words: bool,
+    /// 请求PDU
+    pub pdu: Vec<u8>,
+}
+
+impl ReadRequest {
+    /// 创建读取请求
+    ///
+    /// * `data_type` - Bit 读取线圈或者离散输入；Word 读取寄存器，读取线圈或者离散输入时每16个点组成一个字
+    pub fn new(
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Self, PlcError> {
+        let address = ModbusAddress::new(address_name, data_type.clone())?;
+        let start = address.protocol_address();
+        let (code, count, max) = match address.area() {
+            ModbusArea::HoldingRegister => (
+                FunctionCode::ReadHoldingRegisters,
+                len,
+                pdu::MAX_READ_REGISTERS,
+            ),
+            ModbusArea::InputRegister => (
+                FunctionCode::ReadInputRegisters,
+                len,
+                pdu::MAX_READ_REGISTERS,
+            ),
+            area => (
+                match area {
+                    ModbusArea::Coil => FunctionCode::ReadCoils,
+                    _ => FunctionCode::ReadDiscreteInputs,
+                },
+                match data_type {
+                    DataType::Bit => len,
+                    DataType::Word => len.saturating_mul(16),
+                },
+                pdu::MAX_READ_BITS,
+            ),
+        };
+        check_len(&address, count, max)?;
+        Ok(Self {
+            code,
+            count,
+            pack_words: address.area().is_bit() && data_type == DataType::Word,
+            pdu: pdu::read_request(code, start, count),
+        })
+    }
+
+    /// 创建读写多个寄存器请求(功能码23)
+    pub fn read_write(
+        read_address: impl Into<String>,
+        len: u16,
+        write_address: impl Into<String>,
+        datas: &[u16],
+    ) -> Result<Self, PlcError> {
+        let read = ModbusAddress::new(read_address, DataType::Word)?;
+        let write = ModbusAddress::new(write_address, DataType::Word)?;
+        for address in [&read, &write] {
+            if address.area() != ModbusArea::HoldingRegister {
+                return Err(PlcError::Addr(format!(
+                    "读写多个寄存器只支持保持寄存器\t寄存器={}",
+                    address.get_address_name()
+                )));
+            }
+        }
+        check_len(&read, len, pdu::MAX_READ_REGISTERS)?;
+        check_len(&write, datas.len(), pdu::MAX_READ_WRITE_REGISTERS)?;
+        Ok(Self {
+            code: FunctionCode::ReadWriteMultipleRegisters,
+            count: len,
+            pack_words: false,
+            pdu: pdu::read_write_registers_request(
+                read.protocol_address(),
+                len,
+                write.protocol_address(),
+                datas,
+            ),
+        })
+    }
+
+    /// 解析回复的PDU
+    pub fn parse(&self, reply: &[u8]) -> Result<Vec<u16>, PlcError> {
+        match self.code {
+            FunctionCode::ReadCoils | FunctionCode::ReadDiscreteInputs => {
+                let bits = pdu::parse_bits(self.code, reply, self.count)?;
+                if !self.pack_words {
+                    return Ok(bits);
+                }
+                Ok(bits
+                    .chunks(16)
+                    .map(|bits| {
+                        bits.iter()
+                            .enumerate()
+                            .fold(0u16, |word, (i, bit)| word | (bit << i))
+                    })
+                    .collect())
+            }
+            _ => pdu::parse_registers(self.code, reply, self.count),
+        }
+    }
+}
+
+/// 写入请求
+///
+/// 写入一个数据时使用功能码5/6，多个数据时使用功能码15/16
+pub struct WriteRequest {
+    /// 功能码
+    code: FunctionCode,
+    /// 请求PDU
+    pub pdu: Vec<u8>,
+}
+
+impl WriteRequest {
+    /// 创建写入请求
+    ///
+    /// * `data_type` - Bit 写入线圈；Word 写入保持寄存器，写入线圈时每个字拆分为16个点，bit0 为起始地址
+    pub fn new(
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> Result<Self, PlcError> {
+        let address = ModbusAddress::new(address_name, data_type.clone())?;
+        let start = address.protocol_address();
+        let area = address.area();
+        if !area.is_writable() {
+            return Err(PlcError::Addr(format!(
+                "Modbus 只读的数据区不能写入\t寄存器={}",
+                address.get_address_name()
+            )));
+        }
+        let (code, pdu) = match area {
+            ModbusArea::HoldingRegister => {
+                check_len(&address, datas.len(), pdu::MAX_WRITE_REGISTERS)?;
+                match datas {
+                    [data] => (
+                        FunctionCode::WriteSingleRegister,
+                        pdu::write_register_request(start, *data),
+                    ),
+                    _ => (
+                        FunctionCode::WriteMultipleRegisters,
+                        pdu::write_registers_request(start, datas),
+                    ),
+                }
+            }
+            _ => {
+                let states: Vec<u16> = match data_type {
+                    DataType::Bit => datas.to_vec(),
+                    DataType::Word => datas
+                        .iter()
+                        .flat_map(|word| (0..16).map(move |i| (word >> i) & 1))
+                        .collect(),
+                };
+                if states.iter().any(|state| *state > 1) {
+                    return Err(PlcError::Param("线圈写入数据只能是 1 或者 0".into()));
+                }
+                check_len(&address, states.len(), pdu::MAX_WRITE_BITS)?;
+                match states.as_slice() {
+                    [state] => (
+                        FunctionCode::WriteSingleCoil,
+                        pdu::write_coil_request(start, *state),
+                    ),
+                    _ => (
+                        FunctionCode::WriteMultipleCoils,
+                        pdu::write_coils_request(start, &states),
+                    ),
+                }
+            }
+        };
+        Ok(Self { code, pdu })
+    }
+
+    /// 检查回复的PDU
+    pub fn check(&self, reply: &[u8]) -> PlcResult {
+        pdu::check_write_reply(self.code, reply, &self.pdu)
+    }
+}
+
+/// 检查数据数量在 1~`max` 之间，且不超出地址范围
+fn check_len(address: &ModbusAddress, len: impl Into<usize>, max: u16) -> PlcResult {
+    let len = len.into();
+    if len == 0 || len > max as usize {
+        return Err(PlcError::Param(format!(
+            "超出读写长度范围[1~{}]\t长度={}",
+            max, len
+        )));
+    }
+    if address.protocol_address() as usize + len > 0x10000 {
+        return Err(PlcError::Addr(format!(
+            "读写范围超出地址范围\t寄存器={}\t长度={}",
+            address.get_address_name(),
+            len
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let request = ReadRequest::new("1x1", DataType::Word, 1).unwrap();
+        assert_eq!(request.pdu, [0x02, 0x00, 0x00, 0x00, 0x10]);
+        assert_eq!(request.parse(&[0x02, 0x02, 0x01, 0x80]).unwrap(), [0x8001]);
+        assert!(ReadRequest::new("hr:65535", DataType::Word, 2).is_err());
+        assert!(ReadRequest::new("hr:0", DataType::Word, 126).is_err());
+
+        let request = WriteRequest::new("coil:0", DataType::Word, &[0x0003]).unwrap();
+        assert_eq!(
+            request.pdu,
+            [0x0F, 0x00, 0x00, 0x00, 0x10, 0x02, 0x03, 0x00]
+        );
+        assert!(request.check(&[0x0F, 0x00, 0x00, 0x00, 0x10]).is_ok());
+        assert!(WriteRequest::new("3x1", DataType::Word, &[1]).is_err());
+        assert!(WriteRequest::new("coil:0", DataType::Bit, &[2]).is_err());
+        assert!(ReadRequest::read_write("hr:0", 1, "3x1", &[1]).is_err());
+    }
+}
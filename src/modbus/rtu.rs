@@ -0,0 +1,99 @@
+use crate::PlcError;
+
+/// CRC-16/MODBUS：多项式 0xA001(反向)，初始值 0xFFFF
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u16, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xA001,
+            _ => crc >> 1,
+        })
+    })
+}
+
+/// 创建 Modbus RTU 报文：从站地址 + PDU + CRC(低字节在前)
+pub fn create_rtu_frame(slave: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(pdu.len() + 3);
+    frame.push(slave);
+    frame.extend_from_slice(pdu);
+    let crc = crc16(&frame);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+/// 检查 Modbus RTU 回复数据是否完整，按功能码计算报文长度
+/// #Return
+/// * `Ok(OK(buf))` => 数据完整，返回完整的报文
+/// * `Ok(Err(err))` => 数据不完整，需要等待接收剩余部分
+/// * `Err(err)` => 接收数据错误
+pub fn check_rtu_frame(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    let len = match buf {
+        [] | [_] => return Ok(Err("数据未接收完成")),
+        // 异常回复：从站地址 + 功能码 + 异常码 + CRC
+        [_, fc, ..] if fc & 0x80 != 0 => 5,
+        // 读取回复：从站地址 + 功能码 + 字节数 + 数据 + CRC
+        [_, 0x01..=0x04 | 0x17] => return Ok(Err("数据未接收完成")),
+        [_, 0x01..=0x04 | 0x17, count, ..] => 3 + *count as usize + 2,
+        // 写入回复：从站地址 + 功能码 + 起始地址 + 数量(数据) + CRC
+        [_, 0x05 | 0x06 | 0x0F | 0x10, ..] => 8,
+        [_, fc, ..] => {
+            return Err(PlcError::Comm(format!(
+                "Modbus 不支持的回复功能码\t功能码={:#04X}\t数据={:02X?}",
+                fc, buf
+            )))
+        }
+    };
+    match buf.len() >= len {
+        true => Ok(Ok(&buf[..len])),
+        false => Ok(Err("数据未接收完成")),
+    }
+}
+
+/// 检查回复报文的 CRC，返回从站地址和 PDU
+pub fn rtu_pdu(frame: &[u8]) -> Result<(u8, &[u8]), PlcError> {
+    let [slave, pdu @ .., crc_lo, crc_hi] = frame else {
+        return Err(PlcError::Comm(format!(
+            "Modbus RTU 报文长度错误\t数据={:02X?}",
+            frame
+        )));
+    };
+    if crc16(&frame[..frame.len() - 2]) != u16::from_le_bytes([*crc_lo, *crc_hi]) {
+        return Err(PlcError::Comm(format!(
+            "Modbus RTU CRC 校验错误\t数据={:02X?}",
+            frame
+        )));
+    }
+    Ok((*slave, pdu))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtu_frame() {
+        let frame = create_rtu_frame(0x01, &[0x03, 0x00, 0x00, 0x00, 0x0A]);
+        assert_eq!(frame, [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]);
+
+        let reply = create_rtu_frame(0x11, &[0x03, 0x04, 0x00, 0x01, 0x12, 0x34]);
+        assert!(check_rtu_frame(&reply[..2]).unwrap().is_err());
+        assert!(check_rtu_frame(&reply[..8]).unwrap().is_err());
+        let mut buf = reply.clone();
+        buf.extend_from_slice(&[0x11, 0x03]);
+        let frame = check_rtu_frame(&buf).unwrap().unwrap();
+        assert_eq!(frame, reply);
+        assert_eq!(
+            rtu_pdu(frame).unwrap(),
+            (0x11, &[0x03, 0x04, 0x00, 0x01, 0x12, 0x34][..])
+        );
+
+        let exception = create_rtu_frame(0x11, &[0x83, 0x02]);
+        assert_eq!(check_rtu_frame(&exception).unwrap().unwrap().len(), 5);
+        let write = create_rtu_frame(0x11, &[0x06, 0x00, 0x01, 0x00, 0x03]);
+        assert_eq!(check_rtu_frame(&write).unwrap().unwrap(), write);
+        assert!(check_rtu_frame(&[0x11, 0x2B]).is_err());
+
+        let mut invalid = reply.clone();
+        invalid[3] ^= 0xFF;
+        assert!(rtu_pdu(&invalid).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+// ! FINS 协议：FINS/TCP 报文头 + FINS 报文
+
+use crate::prelude::*;
+
+/// FINS/TCP 报文头长度：`FINS`(4) + 长度(4) + 命令(4) + 错误码(4)
+pub const TCP_HEADER_LEN: usize = 16;
+
+/// FINS 报文头长度：ICF RSV GCT DNA DA1 DA2 SNA SA1 SA2 SID
+pub const FINS_HEADER_LEN: usize = 10;
+
+/// 每次最多读写的字或者位数量
+pub const MAX_COUNT: u16 = 990;
+
+/// 读取内存区域命令
+pub const MEMORY_AREA_READ: [u8; 2] = [0x01, 0x01];
+
+/// 写入内存区域命令
+pub const MEMORY_AREA_WRITE: [u8; 2] = [0x01, 0x02];
+
+/// FINS 内存区域
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinsArea {
+    /// CIO 区
+    Cio,
+    /// 工作区(W)
+    Work,
+    /// 保持区(H)
+    Holding,
+    /// 辅助区(A)
+    Auxiliary,
+    /// 数据区(DM)
+    Dm,
+    /// 扩展数据区(EM)，参数为存储体编号 0~F
+    Em(u8),
+}
+
+impl FinsArea {
+    /// 按字读写的区域代码
+    pub fn word_code(&self) -> u8 {
+        match self {
+            FinsArea::Cio => 0xB0,
+            FinsArea::Work => 0xB1,
+            FinsArea::Holding => 0xB2,
+            FinsArea::Auxiliary => 0xB3,
+            FinsArea::Dm => 0x82,
+            FinsArea::Em(bank) => 0xA0 + bank,
+        }
+    }
+
+    /// 按位读写的区域代码
+    pub fn bit_code(&self) -> u8 {
+        match self {
+            FinsArea::Cio => 0x30,
+            FinsArea::Work => 0x31,
+            FinsArea::Holding => 0x32,
+            FinsArea::Auxiliary => 0x33,
+            FinsArea::Dm => 0x02,
+            FinsArea::Em(bank) => 0x20 + bank,
+        }
+    }
+}
+
+/// FINS 寄存器地址
+///
+/// 支持以下格式(字地址为十进制，位地址为 00~15)：
+/// * 按字：`DM100`、`D100`、`CIO100`、`W10`、`H10`、`A100`、`EM0_100`(存储体0)
+/// * 按位：`CIO100.05`、`W10.00`、`H10.15`、`A100.01`、`DM100.03`、`EM0_100.03`
+pub struct FinsAddress {
+    address_name: String,
+    data_type: DataType,
+    /// 寄存器头部，例如 `CIO`、`DM`、`EM0_`
+    inner_address_header: String,
+    /// 内存区域
+    inner_area: FinsArea,
+    /// 字地址
+    inner_address: u16,
+    /// 位地址 0~15
+    inner_bit: u8,
+}
+
+impl FinsAddress {
+    /// 实例化一个寄存器地址
+    ///
+    /// # Error
+    /// 无效的寄存器地址，或者地址与数据类型不一致
+    pub fn new(address_name: impl Into<String>, data_type: DataType) -> Result<Self, PlcError> {
+        let address_name = address_name.into().trim().to_uppercase();
+        let invalid = || PlcError::Addr(format!("FINS 无效的寄存器地址\t寄存器={}", &address_name));
+        let (area, header, number) = if let Some((head, number)) = address_name.split_once('_') {
+            // 扩展数据区：存储体编号(十六进制一位) + `_` + 字地址
+            let bank = head
+                .strip_prefix("EM")
+                .or_else(|| head.strip_prefix('E'))
+                .filter(|bank| bank.len() == 1)
+                .ok_or_else(invalid)?;
+            let bank = u8::from_str_radix(bank, 16).map_err(|_| invalid())?;
+            (FinsArea::Em(bank), &address_name[..head.len() + 1], number)
+        } else {
+            let split = address_name
+                .find(|c: char| c.is_ascii_digit())
+                .ok_or_else(invalid)?;
+            let (header, number) = address_name.split_at(split);
+            let area = match header {
+                "CIO" | "C" => FinsArea::Cio,
+                "W" => FinsArea::Work,
+                "H" => FinsArea::Holding,
+                "A" => FinsArea::Auxiliary,
+                "DM" | "D" => FinsArea::Dm,
+                _ => return Err(invalid()),
+            };
+            (area, header, number)
+        };
+        let (word, bit) = match number.split_once('.') {
+            Some((word, bit)) => {
+                let bit = bit.parse::<u8>().map_err(|_| invalid())?;
+                if bit > 15 {
+                    return Err(invalid());
+                }
+                (word, Some(bit))
+            }
+            None => (number, None),
+        };
+        if word.is_empty() || !word.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let word = word.parse::<u16>().map_err(|_| invalid())?;
+        match (bit.is_some(), &data_type) {
+            (true, DataType::Word) => Err(PlcError::Addr(format!(
+                "FINS 位地址只能按位读写\t寄存器={}",
+                &address_name
+            ))),
+            (false, DataType::Bit) => Err(PlcError::Addr(format!(
+                "FINS 字地址不能按位读写，请使用位地址，例如 CIO100.05\t寄存器={}",
+                &address_name
+            ))),
+            _ => Ok(Self {
+                inner_address_header: header.to_owned(),
+                address_name,
+                data_type,
+                inner_area: area,
+                inner_address: word,
+                inner_bit: bit.unwrap_or(0),
+            }),
+        }
+    }
+
+    /// 内存区域
+    pub fn area(&self) -> FinsArea {
+        self.inner_area
+    }
+
+    /// 字地址
+    pub fn word_address(&self) -> u16 {
+        self.inner_address
+    }
+
+    /// 位地址 0~15，字地址时为0
+    pub fn bit_offset(&self) -> u8 {
+        self.inner_bit
+    }
+}
+
+impl IAddress for FinsAddress {
+    fn get_address_name(&self) -> &str {
+        &self.address_name
+    }
+
+    fn get_address_header(&self) -> &str {
+        &self.inner_address_header
+    }
+
+    fn get_address(&self) -> u32 {
+        self.inner_address as u32
+    }
+
+    fn get_data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// 读取内存区域的命令参数：区域代码 + 字地址(2) + 位地址(1) + 数量(2)
+pub fn memory_area_param(area_code: u8, address: u16, bit: u8, count: u16) -> Vec<u8> {
+    let mut param = vec![area_code];
+    param.extend_from_slice(&address.to_be_bytes());
+    param.push(bit);
+    param.extend_from_slice(&count.to_be_bytes());
+    param
+}
+
+/// 创建 FINS 报文
+///
+/// # Param
+/// * `dest_node` - PLC 的节点地址
+/// * `src_node` - 本机的节点地址
+/// * `sid` - 服务编号，回复中原样返回
+/// * `command` - 命令代码(2) + 参数
+pub fn create_fins_frame(dest_node: u8, src_node: u8, sid: u8, command: &[u8]) -> Vec<u8> {
+    let mut frame = vec![
+        0x80, // ICF：命令，需要回复
+        0x00, // RSV
+        0x02, // GCT
+        0x00, dest_node, 0x00, // 目标网络、节点、单元
+        0x00, src_node, 0x00, // 源网络、节点、单元
+        sid,
+    ];
+    frame.extend_from_slice(command);
+    frame
+}
+
+/// 检查 FINS 回复，返回回复的数据(不含命令代码和结束码)
+///
+/// # Return
+/// * `Ok(Some(data))` => 请求的回复
+/// * `Ok(None)` => 服务编号不一致，为其他请求迟到的回复
+/// * `Err(err)` => 回复错误或者结束码不为0
+pub fn parse_fins_reply(
+    frame: &[u8],
+    sid: u8,
+    command: &[u8],
+) -> Result<Option<Vec<u8>>, PlcError> {
+    if frame.len() < FINS_HEADER_LEN + 4 || frame[0] & 0x40 == 0 {
+        return Err(PlcError::Comm(format!(
+            "FINS 回复报文错误\t数据={:02X?}",
+            frame
+        )));
+    }
+    if frame[9] != sid {
+        return Ok(None);
+    }
+    if frame[10..12] != command[..2] {
+        return Err(PlcError::Comm(format!(
+            "FINS 回复的命令代码错误\t数据={:02X?}",
+            frame
+        )));
+    }
+    end_code_result(frame[12], frame[13])?;
+    Ok(Some(frame[FINS_HEADER_LEN + 4..].to_vec()))
+}
+
+/// FINS/TCP 报文
+///
+/// # Param
+/// * `command` - 0：客户端发送节点地址；2：发送 FINS 报文
+pub fn create_tcp_frame(command: u32, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(TCP_HEADER_LEN + data.len());
+    frame.extend_from_slice(b"FINS");
+    frame.extend_from_slice(&(data.len() as u32 + 8).to_be_bytes());
+    frame.extend_from_slice(&command.to_be_bytes());
+    frame.extend_from_slice(&[0x00; 4]);
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// 检查 FINS/TCP 报文是否完整
+/// #Return
+/// * `Ok(OK(buf))` => 数据完整，返回完整的报文
+/// * `Ok(Err(err))` => 数据不完整，需要等待接收剩余部分
+/// * `Err(err)` => 接收数据错误，或者报文头中的错误码不为0
+pub fn check_tcp_frame(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    if buf.len() < TCP_HEADER_LEN {
+        return Ok(Err("数据未接收完成"));
+    }
+    if &buf[0..4] != b"FINS" {
+        return Err(PlcError::Comm(format!(
+            "FINS/TCP 报文头错误\t数据={:02X?}",
+            &buf[..TCP_HEADER_LEN]
+        )));
+    }
+    let error = u32::from_be_bytes([buf[12], buf[13], buf[14], buf[15]]);
+    if error != 0 {
+        return Err(tcp_error(error));
+    }
+    let len = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as usize + 8;
+    match buf.len() >= len {
+        true => Ok(Ok(&buf[..len])),
+        false => Ok(Err("数据未接收完成")),
+    }
+}
+
+/// FINS/TCP 报文头中的错误码转换为错误
+fn tcp_error(code: u32) -> PlcError {
+    let msg = match code {
+        0x01 => "报文头不是 FINS",
+        0x02 => "数据长度过长",
+        0x03 => "不支持的命令",
+        0x20 => "所有连接都在使用中",
+        0x21 => "指定的节点已经连接",
+        0x22 => "客户端 IP 地址未被允许访问",
+        0x23 => "客户端节点地址超出范围",
+        0x24 => "客户端与服务器节点地址相同",
+        0x25 => "没有可分配的节点地址",
+        _ => "未知错误",
+    };
+    PlcError::Comm(format!("FINS/TCP 错误码{:08X}：{}", code, msg))
+}
+
+/// 结束码转换为错误，忽略网络中继错误和 CPU 错误标志位
+pub fn end_code_result(mres: u8, sres: u8) -> PlcResult {
+    let (mres, sres) = (mres & 0x7F, sres & 0x3F);
+    let code = u16::from_be_bytes([mres, sres]);
+    let msg = format!("FINS 结束码{:04X}", code);
+    match (mres, sres) {
+        (0x00, 0x00) => Ok(()),
+        (0x00, 0x01) => Err(PlcError::Comm(format!("{}：服务已取消", msg))),
+        (0x01, _) => Err(PlcError::Comm(format!("{}：本地节点错误", msg))),
+        (0x02, 0x05) => Err(PlcError::Timeout),
+        (0x02, _) => Err(PlcError::Comm(format!("{}：目标节点错误", msg))),
+        (0x03, _) => Err(PlcError::Comm(format!("{}：控制器错误", msg))),
+        (0x04, _) => Err(PlcError::Param(format!("{}：不支持的服务", msg))),
+        (0x05, _) => Err(PlcError::Comm(format!("{}：路由表错误", msg))),
+        (0x10, _) => Err(PlcError::Param(format!("{}：命令格式错误", msg))),
+        (0x11, 0x01 | 0x03 | 0x04) => Err(PlcError::Addr(format!("{}：地址超出范围", msg))),
+        (0x11, _) => Err(PlcError::Param(format!("{}：参数错误", msg))),
+        (0x20, _) => Err(PlcError::Comm(format!("{}：不能读取", msg))),
+        (0x21, 0x01) => Err(PlcError::Addr(format!("{}：只读区域不能写入", msg))),
+        (0x21, _) => Err(PlcError::Comm(format!("{}：不能写入", msg))),
+        (0x22, _) => Err(PlcError::Comm(format!("{}：当前模式下不能执行", msg))),
+        (0x23, _) => Err(PlcError::Comm(format!("{}：没有此单元", msg))),
+        (0x24, _) => Err(PlcError::Comm(format!("{}：不能启动或停止", msg))),
+        (0x25, _) => Err(PlcError::Comm(format!("{}：单元错误", msg))),
+        (0x26, _) => Err(PlcError::Comm(format!("{}：命令错误", msg))),
+        (0x30, _) => Err(PlcError::Comm(format!("{}：没有访问权限", msg))),
+        (0x40, _) => Err(PlcError::Comm(format!("{}：服务已中止", msg))),
+        _ => Err(PlcError::Comm(format!("{}：未知错误", msg))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let cases = [
+            ("DM100", DataType::Word, FinsArea::Dm, 100, 0, "DM"),
+            ("d100", DataType::Word, FinsArea::Dm, 100, 0, "D"),
+            ("CIO100.05", DataType::Bit, FinsArea::Cio, 100, 5, "CIO"),
+            ("W10", DataType::Word, FinsArea::Work, 10, 0, "W"),
+            ("H10.15", DataType::Bit, FinsArea::Holding, 10, 15, "H"),
+            ("A100", DataType::Word, FinsArea::Auxiliary, 100, 0, "A"),
+            ("EM0_100", DataType::Word, FinsArea::Em(0), 100, 0, "EM0_"),
+            ("EMA_1.03", DataType::Bit, FinsArea::Em(10), 1, 3, "EMA_"),
+        ];
+        for (name, data_type, area, address, bit, header) in cases {
+            let r = FinsAddress::new(name, data_type).unwrap();
+            assert_eq!(
+                (
+                    r.area(),
+                    r.word_address(),
+                    r.bit_offset(),
+                    r.get_address_header()
+                ),
+                (area, address, bit, header),
+                "{}",
+                name
+            );
+        }
+        let invalid = [
+            ("CIO100.16", DataType::Bit),
+            ("CIO100", DataType::Bit),
+            ("CIO100.01", DataType::Word),
+            ("DM65536", DataType::Word),
+            ("EM100", DataType::Word),
+            ("EM10_1", DataType::Word),
+            ("X100", DataType::Word),
+            ("DM", DataType::Word),
+            ("DM1A", DataType::Word),
+        ];
+        for (name, data_type) in invalid {
+            assert!(FinsAddress::new(name, data_type).is_err(), "{}", name);
+        }
+        assert_eq!(FinsArea::Em(1).word_code(), 0xA1);
+        assert_eq!(FinsArea::Cio.bit_code(), 0x30);
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut command = MEMORY_AREA_READ.to_vec();
+        command.extend(memory_area_param(0x82, 100, 0, 2));
+        let frame = create_fins_frame(0x01, 0x0A, 0x05, &command);
+        assert_eq!(
+            frame,
+            [
+                0x80, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x0A, 0x00, 0x05, 0x01, 0x01, 0x82, 0x00,
+                0x64, 0x00, 0x00, 0x02
+            ]
+        );
+        let tcp = create_tcp_frame(2, &frame);
+        assert_eq!(&tcp[..8], b"FINS\x00\x00\x00\x1A");
+        assert!(check_tcp_frame(&tcp[..20]).unwrap().is_err());
+        assert_eq!(check_tcp_frame(&tcp).unwrap().unwrap(), tcp);
+        let mut error = tcp.clone();
+        error[15] = 0x20;
+        assert!(check_tcp_frame(&error).is_err());
+
+        let reply = [
+            0xC0, 0x00, 0x02, 0x00, 0x0A, 0x00, 0x00, 0x01, 0x00, 0x05, 0x01, 0x01, 0x00, 0x40,
+            0x12, 0x34,
+        ];
+        // 忽略 CPU 错误标志位
+        assert_eq!(
+            parse_fins_reply(&reply, 0x05, &command).unwrap(),
+            Some(vec![0x12, 0x34])
+        );
+        assert_eq!(parse_fins_reply(&reply, 0x06, &command).unwrap(), None);
+        let mut error = reply;
+        error[12..14].copy_from_slice(&[0x11, 0x03]);
+        assert!(matches!(
+            parse_fins_reply(&error, 0x05, &command),
+            Err(PlcError::Addr(_))
+        ));
+        assert!(matches!(
+            end_code_result(0x02, 0x05),
+            Err(PlcError::Timeout)
+        ));
+        assert!(matches!(
+            end_code_result(0x04, 0x01),
+            Err(PlcError::Param(_))
+        ));
+    }
+}
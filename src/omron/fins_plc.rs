@@ -0,0 +1,472 @@
+// ! 欧姆龙 FINS 协议(FINS/TCP、FINS/UDP)
+
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+use tracing::{event, Level};
+
+use super::fins::{self, FinsAddress};
+use crate::core::{receive, transfer, Channel, UdpTransport};
+use crate::prelude::*;
+
+/// FINS 传输方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinsTransport {
+    /// FINS/TCP，连接时通过握手获取节点地址
+    Tcp,
+    /// FINS/UDP，节点地址默认为 IP 地址的最后一个字节
+    Udp,
+}
+
+/// 节点地址，连接时确定，克隆的客户端共用
+#[derive(Default)]
+struct FinsNodes {
+    /// PLC 的节点地址
+    dest: AtomicU8,
+    /// 本机的节点地址
+    src: AtomicU8,
+}
+
+/// 欧姆龙 网口PLC FINS 协议
+///
+/// 默认使用 FINS/TCP，可以通过 [`FinsPlc::transport`] 设置为 FINS/UDP，端口一般为 9600
+pub struct FinsPlc {
+    /// 通讯通道
+    channel: Channel,
+    /// 传输方式
+    transport: FinsTransport,
+    /// 指定的节点地址(PLC, 本机)，FINS/UDP 时使用
+    nodes: Option<(u8, u8)>,
+    /// 连接后的节点地址
+    node: Arc<FinsNodes>,
+    /// 服务编号
+    sid: Arc<AtomicU8>,
+}
+
+impl Clone for FinsPlc {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            transport: self.transport,
+            nodes: self.nodes,
+            node: self.node.clone(),
+            sid: self.sid.clone(),
+        }
+    }
+}
+
+impl FinsPlc {
+    /// 设置传输方式，默认为 FINS/TCP，需要在连接前设置
+    pub fn transport(mut self, transport: FinsTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// 设置 FINS/UDP 的节点地址，需要在连接前设置
+    ///
+    /// 默认 PLC 的节点地址为 PLC IP 地址的最后一个字节，本机的节点地址为本机 IP 地址的最后一个字节。
+    /// FINS/TCP 的节点地址在连接时由握手确定，不使用此设置。
+    pub fn nodes(mut self, plc_node: u8, local_node: u8) -> Self {
+        self.nodes = Some((plc_node, local_node));
+        self
+    }
+
+    /// 发送 FINS 命令并返回回复的数据
+    async fn send_and_receive(&self, command: &[u8]) -> Result<Vec<u8>, PlcError> {
+        let sid = self.sid.fetch_add(1, Ordering::Relaxed);
+        let mut stream = self.channel.lock().await?;
+        let dest = self.node.dest.load(Ordering::Acquire);
+        let src = self.node.src.load(Ordering::Acquire);
+        let frame = fins::create_fins_frame(dest, src, sid, command);
+        // FINS/UDP 每个数据报为一个完整的报文
+        let (frame, header, check): (_, _, CheckFn) = match self.transport {
+            FinsTransport::Tcp => (
+                fins::create_tcp_frame(2, &frame),
+                fins::TCP_HEADER_LEN,
+                fins::check_tcp_frame,
+            ),
+            FinsTransport::Udp => (frame, 0, |buf| Ok(Ok(buf))),
+        };
+        let time = self.channel.timeout();
+        let mut reply = transfer(stream.as_mut(), &frame, check, time).await?;
+        loop {
+            if let Some(data) = fins::parse_fins_reply(&reply[header..], sid, command)? {
+                return Ok(data);
+            }
+            event!(Level::WARN, "丢弃其他请求的回复\t回复={:02X?}", reply);
+            reply = receive(stream.as_mut(), check, time).await?;
+        }
+    }
+
+    /// 连接 FINS/TCP 并通过握手获取节点地址
+    async fn connect_tcp(&mut self) -> Result<(u8, u8), PlcError> {
+        self.channel.connect().await?;
+        // 客户端节点地址为0时由 PLC 自动分配
+        let request = fins::create_tcp_frame(0, &[0x00; 4]);
+        let r = self
+            .channel
+            .send_and_receive(&request, fins::check_tcp_frame)
+            .await;
+        let r = r.and_then(|reply| match reply.get(fins::TCP_HEADER_LEN..) {
+            Some([.., client, _, _, _, server]) if reply[8..12] == [0, 0, 0, 1] => {
+                Ok((*server, *client))
+            }
+            _ => Err(PlcError::Comm(format!(
+                "FINS/TCP 节点地址握手失败\t数据={:02X?}",
+                reply
+            ))),
+        });
+        // 握手失败时关闭连接
+        if r.is_err() {
+            let _ = self.channel.close().await;
+        }
+        r
+    }
+
+    /// 连接 FINS/UDP，未指定节点地址时使用 IP 地址的最后一个字节
+    async fn connect_udp(&mut self, addr: &str) -> Result<(u8, u8), PlcError> {
+        let udp = timeout(self.channel.timeout(), UdpTransport::connect(addr)).await??;
+        let nodes = match self.nodes {
+            Some(nodes) => nodes,
+            None => {
+                let last = |ip: IpAddr| match ip {
+                    IpAddr::V4(ip) => ip.octets()[3],
+                    IpAddr::V6(ip) => ip.octets()[15],
+                };
+                let peer = udp.socket().peer_addr().map_err(PlcError::Io)?;
+                // 绑定在 0.0.0.0 上时本机地址为连接 PLC 使用的网卡地址
+                let local = udp.socket().local_addr().map_err(PlcError::Io)?;
+                (last(peer.ip()), last(local.ip()))
+            }
+        };
+        self.channel.attach(udp);
+        Ok(nodes)
+    }
+}
+
+/// 检查回复数据是否完整的函数
+type CheckFn = fn(&[u8]) -> Result<Result<&[u8], &str>, PlcError>;
+
+unsafe impl Send for FinsPlc {}
+
+unsafe impl Sync for FinsPlc {}
+
+impl IPlc for FinsPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        FinsPlc {
+            channel: Channel::new(conn, timeout),
+            transport: FinsTransport::Tcp,
+            nodes: None,
+            node: Arc::new(FinsNodes::default()),
+            sid: Arc::new(AtomicU8::new(0)),
+        }
+    }
+
+    async fn connect(&mut self) -> PlcResult {
+        let value = self.channel.conn().network()?;
+        let addr = format!("{}:{}", value.ip_address, value.ip_port);
+        let r = match self.transport {
+            FinsTransport::Tcp => self.connect_tcp().await,
+            FinsTransport::Udp => self.connect_udp(&addr).await,
+        };
+        match r {
+            Err(err) => {
+                event!(Level::ERROR, "\t连接错误\t{:?}", err);
+                Err(err)
+            }
+            Ok((dest, src)) => {
+                self.node.dest.store(dest, Ordering::Release);
+                self.node.src.store(src, Ordering::Release);
+                Ok(())
+            }
+        }
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    /// 读取数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `DM100`、`CIO100`、`W10`、`H10`、`A100`、`EM0_100`、`CIO100.05`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 从位地址开始读取，每个位一个数据
+    ///     * Word 从字地址开始读取
+    /// * `len` - 数据长度
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        if len == 0 {
+            return Err(PlcError::Param("读取长度不能为0".into()));
+        }
+        let address = FinsAddress::new(address_name, data_type.clone())?;
+        let area = address.area();
+        let mut datas = Vec::with_capacity(len as usize);
+        // 起始位置(按位读写时为位的序号)
+        let start = address.word_address() as u32 * 16 + address.bit_offset() as u32;
+        while datas.len() < len as usize {
+            let count = fins::MAX_COUNT.min(len - datas.len() as u16);
+            let offset = datas.len() as u32;
+            let param = match data_type {
+                DataType::Word => fins::memory_area_param(
+                    area.word_code(),
+                    check_word(start / 16 + offset)?,
+                    0,
+                    count,
+                ),
+                DataType::Bit => {
+                    let bit = start + offset;
+                    fins::memory_area_param(
+                        area.bit_code(),
+                        check_word(bit / 16)?,
+                        (bit % 16) as u8,
+                        count,
+                    )
+                }
+            };
+            let mut command = fins::MEMORY_AREA_READ.to_vec();
+            command.extend(param);
+            let data = self.send_and_receive(&command).await?;
+            let values: Vec<u16> = match data_type {
+                DataType::Word => data
+                    .chunks_exact(2)
+                    .map(|word| u16::from_be_bytes([word[0], word[1]]))
+                    .collect(),
+                DataType::Bit => data.iter().map(|bit| (*bit & 1) as u16).collect(),
+            };
+            if values.len() != count as usize {
+                return Err(PlcError::Comm(format!(
+                    "FINS 读取回复的数据长度错误\t长度={}\t需要={}",
+                    values.len(),
+                    count
+                )));
+            }
+            datas.extend(values);
+        }
+        Ok(datas)
+    }
+
+    /// 写入数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `DM100`、`CIO100`、`W10.00`、`CIO100.05`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 从位地址开始写入，0：关闭；1：打开
+    ///     * Word 从字地址开始写入
+    /// * `datas` - 需要写入的数据
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        if datas.is_empty() {
+            return Err(PlcError::Param("写入数据不能为空".into()));
+        }
+        let address = FinsAddress::new(address_name, data_type.clone())?;
+        if data_type == DataType::Bit && datas.iter().any(|state| *state > 1) {
+            return Err(PlcError::Param("位写入数据只能是 1 或者 0".into()));
+        }
+        let area = address.area();
+        let start = address.word_address() as u32 * 16 + address.bit_offset() as u32;
+        for (i, chunk) in datas.chunks(fins::MAX_COUNT as usize).enumerate() {
+            let offset = (i * fins::MAX_COUNT as usize) as u32;
+            let count = chunk.len() as u16;
+            let (param, data): (_, Vec<u8>) = match data_type {
+                DataType::Word => (
+                    fins::memory_area_param(
+                        area.word_code(),
+                        check_word(start / 16 + offset)?,
+                        0,
+                        count,
+                    ),
+                    chunk.iter().flat_map(|word| word.to_be_bytes()).collect(),
+                ),
+                DataType::Bit => {
+                    let bit = start + offset;
+                    (
+                        fins::memory_area_param(
+                            area.bit_code(),
+                            check_word(bit / 16)?,
+                            (bit % 16) as u8,
+                            count,
+                        ),
+                        chunk.iter().map(|state| *state as u8).collect(),
+                    )
+                }
+            };
+            let mut command = fins::MEMORY_AREA_WRITE.to_vec();
+            command.extend(param);
+            command.extend(data);
+            self.send_and_receive(&command).await?;
+        }
+        Ok(())
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+/// 检查字地址是否超出范围
+fn check_word(word: u32) -> Result<u16, PlcError> {
+    u16::try_from(word)
+        .map_err(|_| PlcError::Addr(format!("FINS 读写范围超出地址范围\t字地址={}", word)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::net::UdpSocket;
+
+    /// 模拟 PLC 处理 FINS 命令，每个区域的初始值为字地址
+    fn handle_fins(memory: &mut HashMap<u8, Vec<u16>>, request: &[u8], node: u8) -> Vec<u8> {
+        let mut reply = request[..12].to_vec();
+        reply[0] = 0xC0;
+        reply[4] = request[7];
+        reply[7] = node;
+        let code = request[12];
+        let word = u16::from_be_bytes([request[13], request[14]]) as usize;
+        let bit = request[15] as usize;
+        let count = u16::from_be_bytes([request[16], request[17]]) as usize;
+        let words = memory
+            .entry(code | 0x80)
+            .or_insert_with(|| (0..=u16::MAX).collect());
+        if word + count > 1000 {
+            reply.extend_from_slice(&[0x11, 0x03]);
+            return reply;
+        }
+        reply.extend_from_slice(&[0x00, 0x00]);
+        let bit_area = code & 0x80 == 0;
+        match (request[11], bit_area) {
+            (0x01, false) => {
+                for value in &words[word..word + count] {
+                    reply.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            (0x01, true) => {
+                for i in 0..count {
+                    let n = word * 16 + bit + i;
+                    reply.push(((words[n / 16] >> (n % 16)) & 1) as u8);
+                }
+            }
+            (_, false) => {
+                for (i, value) in request[18..].chunks(2).enumerate() {
+                    words[word + i] = u16::from_be_bytes([value[0], value[1]]);
+                }
+            }
+            (_, true) => {
+                for (i, state) in request[18..].iter().enumerate() {
+                    let n = word * 16 + bit + i;
+                    match state {
+                        0 => words[n / 16] &= !(1 << (n % 16)),
+                        _ => words[n / 16] |= 1 << (n % 16),
+                    }
+                }
+            }
+        }
+        reply
+    }
+
+    async fn check_read_write(plc: &FinsPlc) {
+        let r = plc.read("DM100", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [100, 101]);
+        // 5 = 0b0101
+        let r = plc.read("CIO5.00", DataType::Bit, 4).await.unwrap();
+        assert_eq!(r, [1, 0, 1, 0]);
+        plc.write("W10", DataType::Word, &[0x1234, 0x5678])
+            .await
+            .unwrap();
+        let r = plc.read("W10", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x1234, 0x5678]);
+        plc.write("H0.15", DataType::Bit, &[1, 1]).await.unwrap();
+        let r = plc.read("H0", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x8000, 0x0001]);
+        let r = plc.read("EM0_998", DataType::Word, 3).await;
+        assert!(matches!(r, Err(PlcError::Addr(_))));
+    }
+
+    #[tokio::test]
+    async fn test_fins_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut memory = HashMap::new();
+            loop {
+                let mut header = [0u8; 16];
+                if socket.read_exact(&mut header).await.is_err() {
+                    return;
+                }
+                let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+                let mut data = vec![0u8; len - 8];
+                socket.read_exact(&mut data).await.unwrap();
+                let reply = match header[11] {
+                    // 分配客户端节点 0x22，PLC 节点 0x01
+                    0 => fins::create_tcp_frame(1, &[0, 0, 0, 0x22, 0, 0, 0, 0x01]),
+                    _ => {
+                        assert_eq!((data[4], data[7]), (0x01, 0x22));
+                        fins::create_tcp_frame(2, &handle_fins(&mut memory, &data, 0x01))
+                    }
+                };
+                socket.write_all(&reply).await.unwrap();
+            }
+        });
+        let mut plc = FinsPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        plc.connect().await.unwrap();
+        check_read_write(&plc).await;
+        // 超过单次最大数量时分包读取
+        let r = plc.read("DM0", DataType::Word, 995).await.unwrap();
+        assert_eq!(r[994], 994);
+        // 断开只影响当前客户端，克隆的客户端继续使用连接
+        let other = plc.clone();
+        plc.disconnect().await.unwrap();
+        assert!(!plc.is_connect());
+        assert!(other.is_connect());
+        let r = other.read("DM100", DataType::Word, 1).await.unwrap();
+        assert_eq!(r, [100]);
+
+        let mut plc = FinsPlc::new(SerailPort::default().into(), Duration::from_millis(300));
+        assert!(matches!(plc.connect().await, Err(PlcError::Param(_))));
+    }
+
+    #[tokio::test]
+    async fn test_fins_udp() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut memory = HashMap::new();
+            let mut buf = [0u8; 2048];
+            loop {
+                let (n, peer) = server.recv_from(&mut buf).await.unwrap();
+                assert_eq!((buf[4], buf[7]), (0x0A, 0x0B));
+                let reply = handle_fins(&mut memory, &buf[..n], 0x0A);
+                // 先回复一个迟到的回复，应该被丢弃
+                let mut stale = reply.clone();
+                stale[9] = stale[9].wrapping_sub(1);
+                server.send_to(&stale, peer).await.unwrap();
+                server.send_to(&reply, peer).await.unwrap();
+            }
+        });
+        let mut plc = FinsPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        )
+        .transport(FinsTransport::Udp)
+        .nodes(0x0A, 0x0B);
+        plc.connect().await.unwrap();
+        check_read_write(&plc).await;
+    }
+}
@@ -0,0 +1,19 @@
+// ! 欧姆龙PLC
+
+mod fins;
+mod fins_plc;
+
+pub use self::fins::{FinsAddress, FinsArea};
+pub use self::fins_plc::{FinsPlc, FinsTransport};
+use crate::{core::PlcConnector, IPlc};
+use std::time::Duration;
+
+/// 创建一个 欧姆龙 网口PLC (FINS/TCP 协议，端口一般为9600)
+pub fn new_fins_tcp_plc(conn: PlcConnector, timeout: Duration) -> FinsPlc {
+    FinsPlc::new(conn, timeout)
+}
+
+/// 创建一个 欧姆龙 网口PLC (FINS/UDP 协议，端口一般为9600)
+pub fn new_fins_udp_plc(conn: PlcConnector, timeout: Duration) -> FinsPlc {
+    FinsPlc::new(conn, timeout).transport(FinsTransport::Udp)
+}
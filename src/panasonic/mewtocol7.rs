@@ -0,0 +1,273 @@
+use crate::{DataType, IAddress, PlcError};
+
+/// 松下 MEWTOCOL7-COM 协议(FP7)的寄存器地址
+///
+/// 与 [`super::newtocol::NewtocolAddress`] 一致，按寄存器头部区分线圈和字寄存器：
+/// * 线圈：`X`、`Y`、`R`、`L`、`T`、`C`、`SR`，地址为 十进制字地址 + 1位十六进制位地址，例如 `R100F`
+/// * 字：`DT`、`LD`、`WX`、`WY`、`WR`、`WL`、`SD`、`TS`、`TE`、`CS`、`CE`，地址为十进制，例如 `DT999423`
+pub struct Mewtocol7Address {
+    address_name: String,
+    data_type: DataType,
+    /// 寄存器头部
+    inner_address_header: String,
+    /// 寄存器当前的地址，线圈为字地址
+    inner_address: u32,
+    /// 线圈的位地址 0~F
+    inner_bit: u8,
+    /// 是否为线圈
+    inner_is_coil: bool,
+}
+
+/// FP7 线圈寄存器头部及最大字地址
+const HEAD_COIL: [(&str, u32); 7] = [
+    ("SR", 109),
+    ("X", 511),
+    ("Y", 511),
+    ("R", 2047),
+    ("L", 1023),
+    ("T", 4095),
+    ("C", 1023),
+];
+
+/// FP7 字寄存器头部及最大地址
+const HEAD_WORD: [(&str, u32); 11] = [
+    ("DT", 999423),
+    ("LD", 16383),
+    ("WX", 511),
+    ("WY", 511),
+    ("WR", 2047),
+    ("WL", 1023),
+    ("SD", 109),
+    ("TS", 4095),
+    ("TE", 4095),
+    ("CS", 1023),
+    ("CE", 1023),
+];
+
+impl Mewtocol7Address {
+    /// 实例化一个寄存器地址
+    ///
+    /// # Error
+    /// 无效的寄存器地址或者地址超出FP7的范围
+    pub fn new(address_name: impl Into<String>, data_type: DataType) -> Result<Self, PlcError> {
+        let address_name = address_name.into().to_uppercase();
+        if address_name.len() <= 1 || !address_name.is_ascii() {
+            return Err(PlcError::Param(format!(
+                "PLC 寄存器地址错误\t寄存器={}",
+                &address_name
+            )));
+        }
+        // 优先匹配两个字符的头部
+        let word = HEAD_WORD
+            .iter()
+            .find(|(head, _)| address_name.starts_with(head));
+        let coil = HEAD_COIL
+            .iter()
+            .filter(|(head, _)| address_name.starts_with(head))
+            .max_by_key(|(head, _)| head.len());
+        let (header, max, is_coil) = match (word, coil) {
+            (Some((head, max)), _) => (*head, *max, false),
+            (None, Some((head, max))) => (*head, *max, true),
+            _ => {
+                return Err(PlcError::Param(format!(
+                    "PLC 无效的寄存器地址\t寄存器={}",
+                    &address_name
+                )))
+            }
+        };
+        let address_str = &address_name[header.len()..];
+        let invalid = || PlcError::Addr(format!("PLC 无效的寄存器地址\t寄存器={}", &address_name));
+        let (address, bit) = if is_coil {
+            // 最后一位为十六进制位地址
+            if address_str.is_empty() {
+                return Err(invalid());
+            }
+            let (word_str, bit_str) = address_str.split_at(address_str.len() - 1);
+            let word = match word_str {
+                "" => 0,
+                _ => word_str.parse::<u32>().map_err(|_| invalid())?,
+            };
+            let bit = u8::from_str_radix(bit_str, 16).map_err(|_| invalid())?;
+            if data_type == DataType::Word && bit != 0 {
+                return Err(PlcError::Addr(
+                    "按字读取线圈的时候起始地址必须为16的整数倍".to_string(),
+                ));
+            }
+            (word, bit)
+        } else {
+            if data_type == DataType::Bit {
+                return Err(PlcError::Addr(format!(
+                    "字寄存器不支持按位读写\t寄存器={}",
+                    &address_name
+                )));
+            }
+            (address_str.parse::<u32>().map_err(|_| invalid())?, 0)
+        };
+        if address > max {
+            return Err(PlcError::Addr(format!(
+                "寄存器地址超出范围[{}0~{}{}]\t寄存器={}",
+                header, header, max, &address_name
+            )));
+        }
+        Ok(Self {
+            address_name,
+            data_type,
+            inner_address_header: header.to_owned(),
+            inner_address: address,
+            inner_bit: bit,
+            inner_is_coil: is_coil,
+        })
+    }
+
+    pub fn is_coil(&self) -> bool {
+        self.inner_is_coil
+    }
+
+    /// 指令中使用的寄存器代码(2位)
+    ///
+    /// 按字读写线圈的时候使用对应的字寄存器代码，例如 `R` -> `WR`
+    pub fn device_code(&self) -> Result<String, PlcError> {
+        let header = self.get_address_header();
+        match (self.is_coil(), &self.data_type) {
+            (false, _) => Ok(header.to_string()),
+            (true, DataType::Bit) => Ok(format!("{:<2}", header)),
+            (true, DataType::Word) => match header {
+                "X" | "Y" | "R" | "L" => Ok(format!("W{}", header)),
+                _ => Err(PlcError::Addr(format!(
+                    "{} 线圈不支持按字读写\t寄存器={}",
+                    header, self.address_name
+                ))),
+            },
+        }
+    }
+
+    /// 指令中使用的地址(7位)
+    pub fn device_address(&self) -> String {
+        match (self.is_coil(), &self.data_type) {
+            (true, DataType::Bit) => format!("{:0>6}{:X}", self.inner_address, self.inner_bit),
+            _ => format!("{:0>7}", self.inner_address),
+        }
+    }
+}
+
+impl IAddress for Mewtocol7Address {
+    fn get_address_name(&self) -> &str {
+        &self.address_name
+    }
+
+    fn get_address_header(&self) -> &str {
+        &self.inner_address_header
+    }
+
+    fn get_address(&self) -> u32 {
+        self.inner_address
+    }
+
+    fn get_data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// 松下 MEWTOCOL7-COM 协议命令字
+#[allow(clippy::upper_case_acronyms)]
+pub enum Cmd7 {
+    /// 批量读取
+    MMRD,
+    /// 批量写入
+    MMWT,
+}
+
+impl Cmd7 {
+    pub fn to_str(&self) -> &str {
+        match self {
+            Cmd7::MMRD => "MMRD",
+            Cmd7::MMWT => "MMWT",
+        }
+    }
+}
+
+/// 松下 MEWTOCOL7-COM 协议特殊字符
+pub mod cmd7_char {
+    /// 指令起始符
+    pub const START: &str = ">@";
+    /// 回复起始符
+    pub const REPLY_START: u8 = b'<';
+    /// 帧编号(固定)
+    pub const FRAME: &str = "00";
+    /// 指令固定符
+    pub const FIX: char = '#';
+    /// 指令版本(固定)
+    pub const VERSION: &str = "00";
+    /// 结束符
+    pub const END: char = 0x0D as char;
+    /// 成功
+    pub const OK: u8 = b'$';
+    /// 失败
+    pub const ERR: u8 = b'!';
+    /// 数据类型：线圈
+    pub const CONTACT: char = 'C';
+    /// 数据类型：字
+    pub const DATA: char = 'D';
+}
+
+/// MEWTOCOL7-COM 校验码 CRC-16-CCITT(多项式0x1021，初始值0x0000，低位在前)
+pub fn crc16(content: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in content {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            if crc & 0x0001 != 0 {
+                crc = (crc >> 1) ^ 0x8408;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let address = Mewtocol7Address::new("DT999423", DataType::Word).unwrap();
+        assert_eq!(address.get_address_header(), "DT");
+        assert_eq!(address.get_address(), 999423);
+        assert_eq!(address.device_code().unwrap(), "DT");
+        assert_eq!(address.device_address(), "0999423");
+        assert!(Mewtocol7Address::new("DT999424", DataType::Word).is_err());
+        assert!(Mewtocol7Address::new("DT0", DataType::Bit).is_err());
+
+        let address = Mewtocol7Address::new("R100F", DataType::Bit).unwrap();
+        assert!(address.is_coil());
+        assert_eq!(address.get_address(), 100);
+        assert_eq!(address.inner_bit, 0xF);
+        assert_eq!(address.device_code().unwrap(), "R ");
+        assert_eq!(address.device_address(), "000100F");
+
+        let address = Mewtocol7Address::new("SR12", DataType::Bit).unwrap();
+        assert_eq!(address.get_address_header(), "SR");
+        assert_eq!(address.get_address(), 1);
+
+        let address = Mewtocol7Address::new("R100", DataType::Word).unwrap();
+        assert_eq!(address.device_code().unwrap(), "WR");
+        assert_eq!(address.device_address(), "0000010");
+        assert!(Mewtocol7Address::new("R101", DataType::Word).is_err());
+        assert!(Mewtocol7Address::new("T0", DataType::Word)
+            .unwrap()
+            .device_code()
+            .is_err());
+        assert!(Mewtocol7Address::new("Q0", DataType::Word).is_err());
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x2189);
+    }
+}
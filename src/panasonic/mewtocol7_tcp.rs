@@ -0,0 +1,354 @@
+// ! 松下MEWTOCOL7-COM协议 网络PLC(FP7)
+
+use std::time::Duration;
+
+use crate::core::{Channel, Transport};
+use crate::prelude::*;
+
+use super::mewtocol7::{cmd7_char, crc16, Cmd7, Mewtocol7Address};
+
+/// 单次最多读写的数据数量
+const MAX_LEN: usize = 500;
+
+/// 松下 MEWTOCOL7-COM 协议 网络PLC(FP7)
+///
+/// 帧格式：
+/// * 指令：`>@` + 站号(3) + `00` + `#` + `00` + 指令(4) + 数据 + CRC(4) + CR
+/// * 成功：`<@` + 站号(3) + `00` + `$` + `00` + 指令(4) + 数据 + CRC(4) + CR
+/// * 失败：`<@` + 站号(3) + `00` + `!` + 错误代码(4) + CRC(4) + CR
+pub struct Mewtocol7TcpPlc {
+    /// 通讯通道
+    channel: Channel,
+    /// plc 站号 1~999
+    station: u16,
+}
+
+impl Clone for Mewtocol7TcpPlc {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            station: self.station,
+        }
+    }
+}
+
+impl Mewtocol7TcpPlc {
+    /// 设置站号(1~999)，默认为1
+    pub fn station(mut self, station: u16) -> Self {
+        self.station = station;
+        self
+    }
+
+    /// 使用已经打开的连接代替 `connect`
+    pub fn attach(&mut self, transport: impl Transport + 'static) {
+        self.channel.attach(transport);
+    }
+
+    /// 发送指令并等待PLC回复
+    ///
+    /// # Return
+    /// 回复中的数据部分，不包含指令和校验码
+    async fn send_and_receive(&self, buf: &[u8]) -> Result<Vec<u8>, PlcError> {
+        self.channel
+            .send_and_receive(buf, |buf| check_mewtocol7(buf, self.station))
+            .await
+    }
+}
+
+unsafe impl Send for Mewtocol7TcpPlc {}
+
+unsafe impl Sync for Mewtocol7TcpPlc {}
+
+impl IPlc for Mewtocol7TcpPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        Mewtocol7TcpPlc {
+            channel: Channel::new(conn, timeout),
+            station: 1,
+        }
+    }
+
+    async fn connect(&mut self) -> PlcResult {
+        self.channel.connect().await
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    /// 读取PLC数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `DT100`、`R100F`、`WR10`
+    /// * `data_type` - 数据类型：线圈可按位或按字读取，字寄存器只能按字读取
+    /// * `len` - 数据长度 1~500
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        if len == 0 || len as usize > MAX_LEN {
+            return Err(PlcError::Param(format!("超出读取长度范围[1~{}]", MAX_LEN)));
+        }
+        let address = Mewtocol7Address::new(address_name, data_type)?;
+        let buf = create_read_buf(&address, len, self.station)?;
+        let reply = self.send_and_receive(&buf).await?;
+        parse_reply_data(&reply, &address, len)
+    }
+
+    /// 写入PLC数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `DT100`、`R100F`、`WR10`
+    /// * `data_type` - 数据类型：线圈可按位或按字写入，字寄存器只能按字写入
+    /// * `datas` - 需要写入的数据，长度 1~500
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        if datas.is_empty() || datas.len() > MAX_LEN {
+            return Err(PlcError::Param(format!("超出写入长度范围[1~{}]", MAX_LEN)));
+        }
+        let address = Mewtocol7Address::new(address_name, data_type)?;
+        let buf = create_write_buf(&address, datas, self.station)?;
+        self.send_and_receive(&buf).await?;
+        Ok(())
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+/// 创建通用指令：起始符 + 站号 + 帧编号 + 固定符 + 版本 + 指令内容 + CRC + 结束符
+fn create_cmd_buf(cmd: &str, station: u16) -> Result<Vec<u8>, PlcError> {
+    if !(1..=999).contains(&station) {
+        return Err(PlcError::Param(format!(
+            "站号超出范围[1~999]\t站号={}",
+            station
+        )));
+    }
+    let mut buf = String::new();
+    buf.push_str(cmd7_char::START);
+    buf.push_str(&format!("{:0>3}", station));
+    buf.push_str(cmd7_char::FRAME);
+    buf.push(cmd7_char::FIX);
+    buf.push_str(cmd7_char::VERSION);
+    buf.push_str(cmd);
+    let crc = crc16(buf.as_bytes());
+    buf.push_str(&format!("{:0>4X}", crc));
+    buf.push(cmd7_char::END);
+    Ok(Vec::from(buf))
+}
+
+/// 指令中的寄存器部分：数据类型(1) + 寄存器代码(2) + 地址(7) + 数量(4)
+fn device_spec(address: &Mewtocol7Address, len: usize) -> Result<String, PlcError> {
+    let kind = match address.get_data_type() {
+        DataType::Bit => cmd7_char::CONTACT,
+        DataType::Word => cmd7_char::DATA,
+    };
+    Ok(format!(
+        "{}{}{}{:0>4}",
+        kind,
+        address.device_code()?,
+        address.device_address(),
+        len
+    ))
+}
+
+/// 创建读取数据指令
+fn create_read_buf(
+    address: &Mewtocol7Address,
+    len: u16,
+    station: u16,
+) -> Result<Vec<u8>, PlcError> {
+    let mut cmd = Cmd7::MMRD.to_str().to_string();
+    cmd.push_str(&device_spec(address, len as usize)?);
+    create_cmd_buf(&cmd, station)
+}
+
+/// 创建写入数据指令
+///
+/// 线圈数据每个1位(0/1)，字数据每个4位十六进制(低字节在前)
+fn create_write_buf(
+    address: &Mewtocol7Address,
+    datas: &[u16],
+    station: u16,
+) -> Result<Vec<u8>, PlcError> {
+    let mut cmd = Cmd7::MMWT.to_str().to_string();
+    cmd.push_str(&device_spec(address, datas.len())?);
+    for data in datas {
+        match address.get_data_type() {
+            DataType::Bit => cmd.push(if *data == 0 { '0' } else { '1' }),
+            DataType::Word => cmd.push_str(&format!("{:0>4X}", data.rotate_left(8))),
+        }
+    }
+    create_cmd_buf(&cmd, station)
+}
+
+/// 检查MEWTOCOL7返回数据是否完整
+/// #Return
+/// * `Ok(OK(buf))` => 数据完整，返回指令之后的数据部分
+/// * `Ok(Err(err))` => 数据不完整，需要等待接收剩余部分
+/// * `Err(err)` => 接收数据错误
+fn check_mewtocol7(buf: &[u8], station: u16) -> Result<Result<&[u8], &'static str>, PlcError> {
+    let Some(start) = buf.iter().position(|b| *b == cmd7_char::REPLY_START) else {
+        return Ok(Err("未找到起始符"));
+    };
+    let Some(end) = buf[start..]
+        .iter()
+        .position(|b| *b == cmd7_char::END as u8)
+        .map(|p| p + start)
+    else {
+        return Ok(Err("未找到结束符"));
+    };
+    let frame = &buf[start..end];
+    // <@ + 站号(3) + 帧编号(2) + $/! + CRC(4)
+    if frame.len() < 12 {
+        return Err(PlcError::Comm("数据长度错误".into()));
+    }
+    let (content, crc) = frame.split_at(frame.len() - 4);
+    let crc = std::str::from_utf8(crc)
+        .ok()
+        .and_then(|crc| u16::from_str_radix(crc, 16).ok());
+    if crc != Some(crc16(content)) {
+        return Err(PlcError::Comm("CRC校验错误".into()));
+    }
+    let reply_station = std::str::from_utf8(&content[2..5])
+        .ok()
+        .and_then(|s| s.parse::<u16>().ok());
+    if reply_station != Some(station) {
+        return Err(PlcError::Comm(format!(
+            "回复站号与请求不一致\t站号={}",
+            String::from_utf8_lossy(&content[2..5])
+        )));
+    }
+    match content[7] {
+        cmd7_char::OK => {
+            // $ + 版本(2) + 指令(4)
+            if content.len() < 14 {
+                return Err(PlcError::Comm("数据长度错误".into()));
+            }
+            Ok(Ok(&content[14..]))
+        }
+        cmd7_char::ERR => {
+            let code = std::str::from_utf8(&content[8..])
+                .ok()
+                .and_then(|code| code.parse::<u32>().ok())
+                .unwrap_or_default();
+            let err_str = match code {
+                40 => "CRC校验错误",
+                41 => "格式错误",
+                42 => "无效的指令",
+                43 => "处理步骤错误",
+                53 => "忙错误",
+                60 => "参数错误",
+                61 => "数据错误",
+                62 => "寄存器错误",
+                63 => "PLC模式错误",
+                65 => "保护错误",
+                66 => "地址错误",
+                67 => "丢失数据错误",
+                _ => "未知错误",
+            };
+            Err(PlcError::Comm(format!("{}\t错误代码={}", err_str, code)))
+        }
+        _ => Err(PlcError::Comm("数据校验错误".into())),
+    }
+}
+
+/// 解析plc回复数据（只有读取的时候才需要解析）
+fn parse_reply_data(
+    buf: &[u8],
+    address: &Mewtocol7Address,
+    len: u16,
+) -> Result<Vec<u16>, PlcError> {
+    let str = std::str::from_utf8(buf).map_err(|_| PlcError::Comm("数据解析失败".into()))?;
+    let len = len as usize;
+    let mut datas = Vec::with_capacity(len);
+    match address.get_data_type() {
+        DataType::Bit => {
+            if str.len() < len {
+                return Err(PlcError::Comm("数据不完整".into()));
+            }
+            for c in str[..len].chars() {
+                datas.push(match c {
+                    '0' => 0,
+                    '1' => 1,
+                    _ => return Err(PlcError::Comm("无效的读取结果".into())),
+                });
+            }
+        }
+        DataType::Word => {
+            if str.len() < len * 4 {
+                return Err(PlcError::Comm("数据不完整".into()));
+            }
+            for i in 0..len {
+                let value = u16::from_str_radix(&str[i * 4..i * 4 + 4], 16)
+                    .map_err(|_| PlcError::Comm("无效的读取结果".into()))?;
+                datas.push(value.rotate_left(8));
+            }
+        }
+    }
+    Ok(datas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(content: &str) -> Vec<u8> {
+        let mut buf = content.to_string();
+        buf.push_str(&format!("{:0>4X}", crc16(content.as_bytes())));
+        buf.push('\r');
+        buf.into_bytes()
+    }
+
+    #[test]
+    fn test_create_read_buf() {
+        let address = Mewtocol7Address::new("DT100", DataType::Word).unwrap();
+        let buf = create_read_buf(&address, 2, 1).unwrap();
+        assert_eq!(buf, frame(">@00100#00MMRDDDT00001000002"));
+
+        let address = Mewtocol7Address::new("R10F", DataType::Bit).unwrap();
+        let buf = create_read_buf(&address, 3, 12).unwrap();
+        assert_eq!(buf, frame(">@01200#00MMRDCR 000010F0003"));
+
+        assert!(create_read_buf(&address, 3, 1000).is_err());
+    }
+
+    #[test]
+    fn test_create_write_buf() {
+        let address = Mewtocol7Address::new("DT100", DataType::Word).unwrap();
+        let buf = create_write_buf(&address, &[1, 0x1234], 1).unwrap();
+        assert_eq!(buf, frame(">@00100#00MMWTDDT0000100000201003412"));
+
+        let address = Mewtocol7Address::new("Y0", DataType::Bit).unwrap();
+        let buf = create_write_buf(&address, &[1, 0, 5], 1).unwrap();
+        assert_eq!(buf, frame(">@00100#00MMWTCY 00000000003101"));
+    }
+
+    #[test]
+    fn test_check_and_parse() {
+        let reply = frame("<@00100$00MMRD0100E803");
+        // 数据不完整
+        assert!(check_mewtocol7(&reply[..10], 1).unwrap().is_err());
+        let data = check_mewtocol7(&reply, 1).unwrap().unwrap();
+        let address = Mewtocol7Address::new("DT0", DataType::Word).unwrap();
+        assert_eq!(parse_reply_data(data, &address, 2).unwrap(), [1, 1000]);
+        // 站号不一致
+        assert!(check_mewtocol7(&reply, 2).is_err());
+        // 校验错误
+        let mut bad = reply.clone();
+        bad[14] = b'1';
+        assert!(check_mewtocol7(&bad, 1).is_err());
+        // 错误回复
+        let reply = frame("<@00100!0061");
+        match check_mewtocol7(&reply, 1) {
+            Err(PlcError::Comm(err)) => assert!(err.contains("数据错误"), "{}", err),
+            _ => panic!("应当返回通讯错误"),
+        }
+    }
+}
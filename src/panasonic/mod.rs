@@ -1,9 +1,17 @@
 // ! 松下PLC
 
+mod mewtocol7;
+mod mewtocol7_tcp;
 mod newtocol;
+mod newtocol_manager;
+mod newtocol_monitor;
 mod newtocol_tcp;
 
-pub use self::newtocol_tcp::NewtocolTcpPlc;
+pub use self::mewtocol7_tcp::Mewtocol7TcpPlc;
+pub use self::newtocol::{NewtocolMode, NewtocolStatus};
+pub use self::newtocol_manager::NewtocolTcpManager;
+pub use self::newtocol_monitor::{NewtocolMonitor, NewtocolMonitorData};
+pub use self::newtocol_tcp::{NewtocolTcpPlc, BROADCAST_STATION};
 use crate::{core::PlcConnector, IPlc};
 use std::time::Duration;
 
@@ -11,3 +19,8 @@ use std::time::Duration;
 pub fn new_newtocol_tcp_plc(conn: PlcConnector, timeout: Duration) -> NewtocolTcpPlc {
     NewtocolTcpPlc::new(conn, timeout)
 }
+
+/// 创建一个松下 网口PLC (MEWTOCOL7-COM协议，FP7)
+pub fn new_mewtocol7_tcp_plc(conn: PlcConnector, timeout: Duration) -> Mewtocol7TcpPlc {
+    Mewtocol7TcpPlc::new(conn, timeout)
+}
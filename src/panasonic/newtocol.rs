@@ -12,10 +12,23 @@ pub struct NewtocolAddress {
     inner_address: u32,
     /// 是否为线圈
     inner_is_coil: bool,
+    /// 变址寄存器(IX/IY)，读写前需要先读取变址寄存器的值并加到地址上
+    inner_index: Option<&'static str>,
+    /// 32位数据类型，每个数据占用两个连续的字
+    inner_double: Option<DoubleWord>,
+}
+
+/// 32位数据寄存器的数据类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoubleWord {
+    /// 32位整数，地址写法 `DDT100`
+    Int,
+    /// 32位浮点数，地址写法 `DF100`
+    Float,
 }
 
 static HEAD_COIL: OnceCell<[&'static str; 6]> = OnceCell::const_new();
-static HEAD_WORD: OnceCell<[&'static str; 10]> = OnceCell::const_new();
+static HEAD_WORD: OnceCell<[&'static str; 12]> = OnceCell::const_new();
 
 impl NewtocolAddress {
     pub async fn new(
@@ -23,22 +36,36 @@ impl NewtocolAddress {
         data_type: DataType,
     ) -> Result<Self, PlcError> {
         let address_name = address_name.into();
-        if address_name.len() <= 1 {
+        // 变址修饰，例如 IXDT100、I0DT100
+        let (index, body) = split_index_prefix(&address_name);
+        // 32位数据寄存器，例如 DDT100、DF100
+        let (double, body) = split_double_prefix(body);
+        if body.len() <= 1 || !body.is_ascii() {
             return Err(PlcError::Param(format!(
                 "PLC 寄存器地址错误\t寄存器={}",
                 &address_name
             )));
         }
+        if double.is_some() && data_type == DataType::Bit {
+            return Err(PlcError::Param(format!(
+                "32位数据寄存器只能按字读写\t寄存器={}",
+                &address_name
+            )));
+        }
         let head_coil = HEAD_COIL
             .get_or_init(|| async { ["X", "Y", "R", "T", "C", "L"] })
             .await;
         let head_word = HEAD_WORD
-            .get_or_init(|| async { ["D", "L", "F", "S", "K", "IX", "IY", "WX", "WY", "WR"] })
+            .get_or_init(|| async {
+                [
+                    "D", "L", "F", "S", "K", "IX", "IY", "WX", "WY", "WR", "SV", "EV",
+                ]
+            })
             .await;
         // 检查寄存器head是否有效
-        let mut header = &address_name[0..2];
+        let mut header = &body[0..2];
         if !head_word.contains(&header) {
-            header = &address_name[0..1];
+            header = &body[0..1];
         }
         let is_coil = if head_word.contains(&header) {
             false
@@ -52,8 +79,8 @@ impl NewtocolAddress {
         };
         // 寄存器地址
         let address_str = match header.len() {
-            1 => &address_name[1..],
-            _ => &address_name[2..],
+            1 => &body[1..],
+            _ => &body[2..],
         };
         let address = match is_coil {
             true => {
@@ -78,19 +105,104 @@ impl NewtocolAddress {
                 }
             }
         };
+        if index.is_some() && is_coil {
+            return Err(PlcError::Addr(format!(
+                "线圈不支持变址修饰\t寄存器={}",
+                &address_name
+            )));
+        }
         let addr = Self {
             address_name: address_name.to_owned(),
             data_type,
             inner_address_header: header.to_owned(),
             inner_address: address,
             inner_is_coil: is_coil,
+            inner_index: index,
+            inner_double: double,
         };
         return Ok(addr);
     }
 
+    /// 变址寄存器(IX/IY)
+    pub fn index_register(&self) -> Option<&'static str> {
+        self.inner_index
+    }
+
+    /// 32位数据类型
+    pub fn double_word(&self) -> Option<DoubleWord> {
+        self.inner_double
+    }
+
+    /// 每个数据占用的字数
+    pub fn word_count(&self) -> u16 {
+        match self.inner_double {
+            Some(_) => 2,
+            None => 1,
+        }
+    }
+
+    /// 加上变址寄存器的值，得到实际读写的地址
+    pub fn with_offset(&self, offset: i32) -> Result<Self, PlcError> {
+        let address = self.inner_address as i64 + offset as i64;
+        if !(0..=99999).contains(&address) {
+            return Err(PlcError::Addr(format!(
+                "变址后的地址超出范围\t寄存器={}\t变址值={}",
+                self.address_name, offset
+            )));
+        }
+        Ok(Self {
+            address_name: self.address_name.clone(),
+            data_type: self.data_type.clone(),
+            inner_address_header: self.inner_address_header.clone(),
+            inner_address: address as u32,
+            inner_is_coil: self.inner_is_coil,
+            inner_index: None,
+            inner_double: self.inner_double,
+        })
+    }
+
     pub fn is_coil(&self) -> bool {
         self.inner_is_coil
     }
+
+    /// 是否为定时器/计数器的设定值(SV)或经过值(EV)
+    pub fn is_timer_value(&self) -> bool {
+        matches!(self.inner_address_header.as_str(), "SV" | "EV")
+    }
+}
+
+/// 拆分变址修饰前缀：`IX`/`I0` -> IX，`IY`/`I1` -> IY
+///
+/// 前缀后面必须是寄存器头部(字母)，`IX0` 这种写法表示读写变址寄存器本身
+fn split_index_prefix(address_name: &str) -> (Option<&'static str>, &str) {
+    let index = match address_name.get(0..2) {
+        Some("IX") | Some("I0") => "IX",
+        Some("IY") | Some("I1") => "IY",
+        _ => return (None, address_name),
+    };
+    let body = &address_name[2..];
+    match body.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => (Some(index), body),
+        _ => (None, address_name),
+    }
+}
+
+/// 拆分32位数据寄存器前缀：`DDT`/`DD` -> i32，`DF` -> f32；`DT` 为 `D` 的别名
+fn split_double_prefix(body: &str) -> (Option<DoubleWord>, String) {
+    let is_digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    for (prefix, double) in [
+        ("DDT", Some(DoubleWord::Int)),
+        ("DD", Some(DoubleWord::Int)),
+        ("DF", Some(DoubleWord::Float)),
+        ("DT", None),
+    ] {
+        if let Some(rest) = body.strip_prefix(prefix) {
+            if is_digits(rest) {
+                return (double, format!("D{}", rest));
+            }
+        }
+    }
+    (None, body.to_string())
 }
 
 impl IAddress for NewtocolAddress {
@@ -135,6 +247,24 @@ pub enum Cmd {
     RD,
     /// 写入寄存器
     WD,
+    /// 读取定时器/计数器设定值(SV)
+    RS,
+    /// 写入定时器/计数器设定值(SV)
+    WS,
+    /// 读取定时器/计数器经过值(EV)
+    RK,
+    /// 写入定时器/计数器经过值(EV)
+    WK,
+    /// 读取PLC状态
+    RT,
+    /// 远程切换运行模式
+    RM,
+    /// 登录/复位监控触点
+    MC,
+    /// 登录/复位监控数据
+    MD,
+    /// 开始监控，一次读取所有已登录的触点和数据
+    MG,
 }
 
 impl Cmd {
@@ -148,10 +278,110 @@ impl Cmd {
             Cmd::WCC => "WCC",
             Cmd::RD => "RD",
             Cmd::WD => "WD",
+            Cmd::RS => "RS",
+            Cmd::WS => "WS",
+            Cmd::RK => "RK",
+            Cmd::WK => "WK",
+            Cmd::RT => "RT",
+            Cmd::RM => "RM",
+            Cmd::MC => "MC",
+            Cmd::MD => "MD",
+            Cmd::MG => "MG",
         }
     }
 }
 
+/// 松下PLC运行模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewtocolMode {
+    /// 运行模式
+    Run,
+    /// 编程模式
+    Prog,
+}
+
+impl NewtocolMode {
+    pub fn to_char(&self) -> char {
+        match self {
+            NewtocolMode::Run => 'R',
+            NewtocolMode::Prog => 'P',
+        }
+    }
+}
+
+/// 松下PLC状态(RT指令读取结果)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewtocolStatus {
+    /// CPU型号代码
+    pub cpu_type: String,
+    /// CPU版本，例如 "12" 表示 V1.2
+    pub cpu_version: String,
+    /// 程序容量(K步)
+    pub program_capacity: u16,
+    /// 运行模式标志
+    /// * bit0: RUN模式
+    /// * bit1: TEST模式
+    /// * bit2: BRK/1步执行中
+    /// * bit3: BRK指令有效
+    /// * bit4: 输出有效
+    /// * bit5: 单步运行
+    /// * bit6: 信息显示标志
+    /// * bit7: 远程模式
+    pub operation_mode: u8,
+    /// 错误标志
+    /// * bit0: 自诊断错误
+    /// * bit2: 运算错误
+    pub error_flag: u8,
+    /// 自诊断错误代码，0 表示无错误
+    pub self_diagnostic_error: u16,
+}
+
+impl NewtocolStatus {
+    /// 解析RT指令的回复数据
+    ///
+    /// 格式：%01$RT + CPU型号(2) + 版本(2) + 程序容量(2) + 运行模式(2) + 未使用(2) + 错误标志(2) + 自诊断错误代码(4)
+    pub fn parse(buf: &[u8]) -> Result<Self, PlcError> {
+        let str =
+            std::str::from_utf8(buf).map_err(|_| PlcError::Comm("数据解析失败".to_string()))?;
+        if str.len() < 22 || &str[4..6] != "RT" {
+            return Err(PlcError::Comm(format!("无效的PLC状态数据\t数据={}", str)));
+        }
+        let hex = |s: &str| {
+            u8::from_str_radix(s, 16)
+                .map_err(|_| PlcError::Comm(format!("无效的PLC状态数据\t数据={}", str)))
+        };
+        let program_capacity = str[10..12]
+            .parse::<u16>()
+            .map_err(|_| PlcError::Comm(format!("无效的PLC状态数据\t数据={}", str)))?;
+        // 自诊断错误代码为低字节在前
+        let error_low = hex(&str[18..20])? as u16;
+        let error_high = hex(&str[20..22])? as u16;
+        Ok(Self {
+            cpu_type: str[6..8].to_string(),
+            cpu_version: str[8..10].to_string(),
+            program_capacity,
+            operation_mode: hex(&str[12..14])?,
+            error_flag: hex(&str[16..18])?,
+            self_diagnostic_error: error_low + (error_high << 8),
+        })
+    }
+
+    /// 是否处于RUN模式
+    pub fn is_run(&self) -> bool {
+        self.operation_mode & 0x01 != 0
+    }
+
+    /// 是否处于远程模式
+    pub fn is_remote(&self) -> bool {
+        self.operation_mode & 0x80 != 0
+    }
+
+    /// 是否存在自诊断错误
+    pub fn has_error(&self) -> bool {
+        self.error_flag & 0x01 != 0 || self.self_diagnostic_error != 0
+    }
+}
+
 /// 松下 Newtocol 协议特殊字符
 pub mod cmd_char {
     /// 起始符
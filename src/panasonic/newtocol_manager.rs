@@ -0,0 +1,158 @@
+// ! 松下Newtocol协议 多站号共享连接
+
+use std::time::Duration;
+
+use crate::prelude::*;
+
+use super::newtocol_tcp::BROADCAST_STATION;
+
+/// 松下 Newtocol 协议多站号管理器
+///
+/// 串口转网口网关后面连接多个PLC时，所有站号共用一个TCP连接。
+/// 通过 [`NewtocolTcpManager::station`] 获取每个站号的PLC实例，所有实例的请求在同一个连接上依次发送。
+/// 重新连接后已经获取的实例会自动使用新的连接，不需要重新获取。
+pub struct NewtocolTcpManager {
+    /// 共享连接的PLC实例
+    plc: NewtocolTcpPlc,
+}
+
+impl NewtocolTcpManager {
+    /// 创建多站号管理器
+    /// * `conn`连接参数
+    /// * `timeout` 通讯超时时间，网关后面的串口PLC推荐 500ms 以上的超时时间
+    pub fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        Self {
+            plc: NewtocolTcpPlc::new(conn, timeout),
+        }
+    }
+
+    /// 连接网关，已连接时会替换为新的连接
+    pub async fn connect(&mut self) -> PlcResult {
+        self.plc.reconnect_shared().await
+    }
+
+    /// 断开网关连接，所有站号的实例都会断开，重新连接后可以继续使用
+    pub async fn disconnect(&mut self) -> PlcResult {
+        self.plc.close_shared().await
+    }
+
+    /// 获取网关连接状态
+    pub fn is_connect(&self) -> bool {
+        self.plc.is_connect()
+    }
+
+    /// 获取指定站号的PLC实例
+    ///
+    /// # Param
+    /// * `station` - PLC站号 1~99
+    ///
+    /// # Error
+    /// 未连接网关或者站号超出范围
+    pub fn station(&self, station: u8) -> Result<NewtocolTcpPlc, PlcError> {
+        if !(1..=99).contains(&station) {
+            return Err(PlcError::Param(format!(
+                "站号超出范围[1~99]\t站号={}",
+                station
+            )));
+        }
+        if !self.plc.is_connect() {
+            return Err(PlcError::NotConnect);
+        }
+        Ok(self.plc.clone().station(station))
+    }
+
+    /// 向所有站号广播写入数据(站号FF)，PLC不回复，发送后立即返回
+    pub async fn broadcast_write(
+        &self,
+        address_name: impl Into<String> + Send,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        self.plc
+            .clone()
+            .station(BROADCAST_STATION)
+            .write(address_name, data_type, datas)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// 读取一帧指令(以CR结尾)
+    async fn read_frame(socket: &mut tokio::net::TcpStream) -> Vec<u8> {
+        let mut frame = Vec::new();
+        loop {
+            let b = socket.read_u8().await.unwrap();
+            frame.push(b);
+            if b == b'\r' {
+                return frame;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_stations() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // 广播写入不回复
+            assert_eq!(read_frame(&mut socket).await, b"%FF#WDD0000000000010050\r");
+            // 站号12：先回复一个其他站号的迟到数据，然后回复正确的数据
+            assert_eq!(read_frame(&mut socket).await, b"%0C#RDD000000000027\r");
+            socket.write_all(b"%03$RD0200").await.unwrap();
+            socket.write_all(b"16\r%0C$RD0100").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            socket.write_all(b"65\r").await.unwrap();
+        });
+        let mut manager = NewtocolTcpManager::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        assert!(manager.station(12).is_err());
+        manager.connect().await.unwrap();
+        assert!(manager.station(0).is_err());
+        assert!(manager.station(100).is_err());
+        let plc = manager.station(12).unwrap();
+        manager
+            .broadcast_write("D0", DataType::Word, &[1])
+            .await
+            .unwrap();
+        let r = plc.read("D0", DataType::Word, 1).await.unwrap();
+        assert_eq!(r, [1]);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_keeps_stations() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            for reply in [b"%05$RD010013\r", b"%05$RD020010\r"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                assert_eq!(read_frame(&mut socket).await, b"%05#RDD000000000051\r");
+                socket.write_all(reply).await.unwrap();
+                // 等待客户端断开
+                let _ = socket.read_u8().await;
+            }
+        });
+        let mut manager = NewtocolTcpManager::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        manager.connect().await.unwrap();
+        let plc = manager.station(5).unwrap();
+        assert_eq!(plc.read("D0", DataType::Word, 1).await.unwrap(), [1]);
+        manager.disconnect().await.unwrap();
+        assert!(!manager.is_connect());
+        assert!(!plc.is_connect());
+        let r = plc.read("D0", DataType::Word, 1).await;
+        assert!(matches!(r, Err(PlcError::NotConnect)));
+        manager.connect().await.unwrap();
+        // 断开前获取的实例使用新的连接
+        assert!(plc.is_connect());
+        assert_eq!(plc.read("D0", DataType::Word, 1).await.unwrap(), [2]);
+    }
+}
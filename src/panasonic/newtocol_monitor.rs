@@ -0,0 +1,331 @@
+// ! 松下Newtocol协议 监控登录(MC/MD/MG)
+
+use tracing::{event, Level};
+
+use crate::prelude::*;
+
+use super::newtocol::{Cmd, NewtocolAddress};
+use super::newtocol_tcp::{create_cmd_buf, to_dcba};
+
+/// 单条指令最多登录的触点/数据数量
+const MAX_REGISTER: usize = 16;
+
+/// 监控读取结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewtocolMonitorData {
+    /// 触点状态，顺序与登录顺序一致，0：OFF；1：ON
+    pub contacts: Vec<u16>,
+    /// 数据寄存器的值，顺序与登录顺序一致
+    pub datas: Vec<u16>,
+}
+
+/// 松下 Newtocol 协议监控会话
+///
+/// 触点通过MC指令、数据通过MD指令一次性登录到PLC，之后每次轮询只需要发送一条MG指令即可读取全部数据。
+/// PLC断电或者连接断开后登录信息会丢失，会话会在重新连接后自动重新登录。
+pub struct NewtocolMonitor {
+    /// 会话使用的PLC连接，重新连接后与创建会话的PLC实例不再共享连接
+    plc: NewtocolTcpPlc,
+    /// 已登录的触点
+    contacts: Vec<NewtocolAddress>,
+    /// 已登录的数据
+    datas: Vec<NewtocolAddress>,
+    /// 是否已在PLC中登录
+    registered: bool,
+    /// 上次连接失败(IO 错误、超时或者未连接)，下次轮询前需要重新连接
+    need_reconnect: bool,
+}
+
+impl NewtocolTcpPlc {
+    /// 创建监控会话
+    ///
+    /// # Param
+    /// * `contacts` - 需要监控的触点，例如 `X0`、`R10`，最多16个
+    /// * `datas` - 需要监控的数据寄存器，例如 `D100`，最多16个
+    pub async fn monitor(
+        &self,
+        contacts: &[&str],
+        datas: &[&str],
+    ) -> Result<NewtocolMonitor, PlcError> {
+        NewtocolMonitor::new(self.clone(), contacts, datas).await
+    }
+}
+
+impl NewtocolMonitor {
+    /// 创建监控会话，需要在调用[`NewtocolMonitor::poll`]之前连接PLC
+    pub async fn new(
+        plc: NewtocolTcpPlc,
+        contacts: &[&str],
+        datas: &[&str],
+    ) -> Result<Self, PlcError> {
+        if contacts.is_empty() && datas.is_empty() {
+            return Err(PlcError::Param("监控的触点和数据不能同时为空".into()));
+        }
+        if contacts.len() > MAX_REGISTER || datas.len() > MAX_REGISTER {
+            return Err(PlcError::Param(format!(
+                "监控的触点和数据数量不能大于{}",
+                MAX_REGISTER
+            )));
+        }
+        let mut contact_addresses = Vec::with_capacity(contacts.len());
+        for contact in contacts {
+            let address = NewtocolAddress::new(*contact, DataType::Bit).await?;
+            if !address.is_coil() {
+                return Err(PlcError::Addr(format!(
+                    "监控触点必须是线圈\t寄存器={}",
+                    contact
+                )));
+            }
+            contact_addresses.push(address);
+        }
+        let mut data_addresses = Vec::with_capacity(datas.len());
+        for data in datas {
+            let address = NewtocolAddress::new(*data, DataType::Word).await?;
+            if address.is_coil()
+                || address.is_timer_value()
+                || address.index_register().is_some()
+                || address.double_word().is_some()
+                || address.get_address_header().len() != 1
+            {
+                return Err(PlcError::Addr(format!(
+                    "监控数据只支持 D、L、F、S、K 寄存器\t寄存器={}",
+                    data
+                )));
+            }
+            data_addresses.push(address);
+        }
+        Ok(Self {
+            plc,
+            contacts: contact_addresses,
+            datas: data_addresses,
+            registered: false,
+            need_reconnect: false,
+        })
+    }
+
+    /// 连接PLC，连接成功后会在下次轮询时重新登录；连接失败时下次轮询前继续重新连接
+    pub async fn connect(&mut self) -> PlcResult {
+        self.plc.connect().await?;
+        self.registered = false;
+        self.need_reconnect = false;
+        Ok(())
+    }
+
+    /// 复位PLC中的监控登录并断开会话的连接，共用此连接的PLC实例不受影响
+    pub async fn disconnect(&mut self) -> PlcResult {
+        if self.registered {
+            let _ = self.reset().await;
+        }
+        self.plc.disconnect().await
+    }
+
+    /// 读取所有已登录的触点和数据
+    ///
+    /// 如果上次连接失败，会先重新连接；如果PLC中的登录信息丢失，会重新登录后再读取。
+    /// PLC回复的错误代码(例如地址错误)不会导致重新连接。
+    pub async fn poll(&mut self) -> Result<NewtocolMonitorData, PlcError> {
+        if self.need_reconnect {
+            event!(Level::INFO, "监控会话重新连接PLC");
+            self.connect().await?;
+        }
+        let r = self.poll_once().await;
+        match r {
+            Err(PlcError::Io(_)) | Err(PlcError::Timeout) | Err(PlcError::NotConnect) => {
+                self.registered = false;
+                self.need_reconnect = true;
+            }
+            _ => {}
+        }
+        r
+    }
+
+    /// 复位PLC中已登录的触点和数据
+    pub async fn reset(&mut self) -> PlcResult {
+        self.registered = false;
+        let buf = create_cmd_buf(&format!("{}FFFFF", Cmd::MC.to_str()), self.plc.station)?;
+        self.plc.send_and_receive(&buf).await?;
+        let buf = create_cmd_buf(&format!("{}FFFFFF", Cmd::MD.to_str()), self.plc.station)?;
+        self.plc.send_and_receive(&buf).await?;
+        Ok(())
+    }
+
+    async fn poll_once(&mut self) -> Result<NewtocolMonitorData, PlcError> {
+        if !self.registered {
+            self.register().await?;
+        }
+        let buf = create_cmd_buf(Cmd::MG.to_str(), self.plc.station)?;
+        let reply = self.plc.send_and_receive(&buf).await?;
+        match parse_monitor_reply(&reply, self.contacts.len(), self.datas.len())? {
+            Some(data) => Ok(data),
+            None => {
+                // PLC中的登录数量与会话不一致(例如PLC重新上电)，重新登录后再读取一次
+                event!(Level::WARN, "PLC监控登录信息丢失，重新登录");
+                self.register().await?;
+                let reply = self.plc.send_and_receive(&buf).await?;
+                parse_monitor_reply(&reply, self.contacts.len(), self.datas.len())?
+                    .ok_or_else(|| PlcError::Comm("PLC监控登录失败".into()))
+            }
+        }
+    }
+
+    /// 复位后重新登录全部触点和数据
+    async fn register(&mut self) -> PlcResult {
+        self.reset().await?;
+        for cmd in create_register_cmds(&self.contacts, &self.datas) {
+            let buf = create_cmd_buf(&cmd, self.plc.station)?;
+            self.plc.send_and_receive(&buf).await?;
+        }
+        self.registered = true;
+        Ok(())
+    }
+}
+
+/// 创建登录指令(不含起始符、站号和校验码)
+///
+/// * 触点：MC + 触点代码(1) + 地址(4)
+/// * 数据：MD + 数据代码(1) + 地址(5)
+fn create_register_cmds(contacts: &[NewtocolAddress], datas: &[NewtocolAddress]) -> Vec<String> {
+    let mut cmds = Vec::new();
+    if !contacts.is_empty() {
+        let mut cmd = Cmd::MC.to_str().to_string();
+        for contact in contacts {
+            cmd.push_str(contact.get_address_header());
+            cmd.push_str(&format!("{:0>4X}", contact.get_address()));
+        }
+        cmds.push(cmd);
+    }
+    if !datas.is_empty() {
+        let mut cmd = Cmd::MD.to_str().to_string();
+        for data in datas {
+            cmd.push_str(data.get_address_header());
+            cmd.push_str(&format!("{:0>5}", data.get_address()));
+        }
+        cmds.push(cmd);
+    }
+    cmds
+}
+
+/// 解析MG指令的回复数据
+///
+/// 格式：%01$MG + 触点登录数(2) + 数据登录数(2) + 跟踪数据(2) + 触点状态(每个1位) + 数据(每个4位，低字节在前)
+///
+/// # Return
+/// * `Ok(Some(data))` => 解析成功
+/// * `Ok(None)` => PLC中的登录数量与会话不一致，需要重新登录
+/// * `Err(err)` => 数据格式错误
+fn parse_monitor_reply(
+    buf: &[u8],
+    contact_count: usize,
+    data_count: usize,
+) -> Result<Option<NewtocolMonitorData>, PlcError> {
+    let str = std::str::from_utf8(buf).map_err(|_| PlcError::Comm("数据解析失败".into()))?;
+    let invalid = || PlcError::Comm(format!("无效的监控数据\t数据={}", str));
+    if str.len() < 12 || &str[4..6] != "MG" {
+        return Err(invalid());
+    }
+    let registered_contacts = usize::from_str_radix(&str[6..8], 16).map_err(|_| invalid())?;
+    let registered_datas = usize::from_str_radix(&str[8..10], 16).map_err(|_| invalid())?;
+    if registered_contacts != contact_count || registered_datas != data_count {
+        return Ok(None);
+    }
+    let contact_start = 12;
+    let data_start = contact_start + contact_count;
+    if str.len() < data_start + data_count * 4 {
+        return Err(invalid());
+    }
+    let mut contacts = Vec::with_capacity(contact_count);
+    for c in str[contact_start..data_start].chars() {
+        contacts.push(match c {
+            '0' => 0,
+            '1' => 1,
+            _ => return Err(invalid()),
+        });
+    }
+    let mut datas = Vec::with_capacity(data_count);
+    for i in 0..data_count {
+        let base = data_start + i * 4;
+        let value = u16::from_str_radix(&str[base..base + 4], 16).map_err(|_| invalid())?;
+        datas.push(to_dcba(&value));
+    }
+    Ok(Some(NewtocolMonitorData { contacts, datas }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_create_register_cmds() {
+        let contacts = [
+            NewtocolAddress::new("X0", DataType::Bit).await.unwrap(),
+            NewtocolAddress::new("R1A", DataType::Bit).await.unwrap(),
+        ];
+        let datas = [NewtocolAddress::new("D100", DataType::Word).await.unwrap()];
+        let cmds = create_register_cmds(&contacts, &datas);
+        assert_eq!(cmds, ["MCX0000R001A", "MDD00100"]);
+        let cmds = create_register_cmds(&[], &datas);
+        assert_eq!(cmds, ["MDD00100"]);
+    }
+
+    #[test]
+    fn test_parse_monitor_reply() {
+        let data = parse_monitor_reply(b"%01$MG020200100100E803", 2, 2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.contacts, [1, 0]);
+        assert_eq!(data.datas, [1, 1000]);
+        // PLC重新上电后登录数量为0
+        let data = parse_monitor_reply(b"%01$MG000000", 2, 2).unwrap();
+        assert!(data.is_none());
+        assert!(parse_monitor_reply(b"%01$MG020100100", 2, 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_error_reply_no_reconnect() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (local, mut remote) = tokio::io::duplex(256);
+        tokio::spawn(async move {
+            let mut buf = [0u8; 256];
+            // 所有指令都回复地址错误
+            while let Ok(n) = remote.read(&mut buf).await {
+                if n == 0 {
+                    break;
+                }
+                remote.write_all(b"%01!6600\r").await.unwrap();
+            }
+        });
+        // 未监听的端口，重新连接时会失败
+        let mut plc = NewtocolTcpPlc::new(
+            Network::new("127.0.0.1", 1).into(),
+            std::time::Duration::from_millis(300),
+        );
+        plc.attach(local);
+        let mut monitor = plc.monitor(&["X0"], &[]).await.unwrap();
+        for _ in 0..2 {
+            let r = monitor.poll().await;
+            assert!(matches!(r, Err(PlcError::Comm(ref err)) if err.contains("地址错误")));
+            assert!(!monitor.need_reconnect);
+        }
+        // 重新连接失败时保留重新连接标志
+        monitor.need_reconnect = true;
+        assert!(monitor.poll().await.is_err());
+        assert!(monitor.need_reconnect);
+        // 断开会话只断开会话自己的连接
+        monitor.disconnect().await.unwrap();
+        assert!(plc.is_connect());
+    }
+
+    #[tokio::test]
+    async fn test_monitor_data_address() {
+        let plc = NewtocolTcpPlc::new(
+            Network::new("127.0.0.1", 9094).into(),
+            std::time::Duration::from_millis(300),
+        );
+        assert!(plc.monitor(&["D0"], &[]).await.is_err());
+        assert!(plc.monitor(&["X0"], &["WX0"]).await.is_err());
+        assert!(plc.monitor(&[], &[]).await.is_err());
+        assert!(plc.monitor(&[], &["IXD0"]).await.is_err());
+        assert!(plc.monitor(&["X0", "Y1"], &["D0", "F2"]).await.is_ok());
+    }
+}
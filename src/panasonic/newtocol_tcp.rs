@@ -1,40 +1,28 @@
 // ! 松下Newtocol协议 网络PLC
 
-use std::sync::Arc;
 use std::time::Duration;
-use tokio::io::AsyncReadExt;
-use tokio::sync::Mutex;
-use tokio::time::timeout;
-use tokio::{io::AsyncWriteExt, net::TcpStream};
 use tracing::{event, Level};
 
+use crate::core::{Channel, Transport};
 use crate::prelude::*;
 
-use super::newtocol::{cmd_char, Cmd, NewtocolAddress};
+use super::newtocol::{cmd_char, Cmd, NewtocolAddress, NewtocolMode, NewtocolStatus};
+
+/// 广播站号，广播指令PLC不回复
+pub const BROADCAST_STATION: u8 = 0xFF;
 
 /// 松下 Newtocol 协议 网络PLC
 pub struct NewtocolTcpPlc {
-    /// 连接参数
-    conn: PlcConnector,
-    /// 客户端连接
-    client: Option<Arc<Mutex<TcpStream>>>,
-    /// 超时时间
-    timeout: Duration,
+    /// 通讯通道
+    channel: Channel,
     /// plc 站号
-    station: u8,
+    pub(super) station: u8,
 }
 
 impl Clone for NewtocolTcpPlc {
     fn clone(&self) -> Self {
         Self {
-            conn: self.conn.clone(),
-            client: if self.client.is_none() {
-                None
-            } else {
-                let client = self.client.as_ref().unwrap();
-                Some(client.clone())
-            },
-            timeout: self.timeout.clone(),
+            channel: self.channel.clone(),
             station: self.station,
         }
     }
@@ -42,69 +30,99 @@ impl Clone for NewtocolTcpPlc {
 
 #[allow(unused)]
 impl NewtocolTcpPlc {
-    /// 设置站号，默认为1
+    /// 设置站号(1~99)，默认为1
+    ///
+    /// 站号为 [`BROADCAST_STATION`] 时只能写入，写入后不等待PLC回复
     pub fn station(mut self, station: u8) -> Self {
         self.station = station;
         self
     }
-}
 
-unsafe impl Send for NewtocolTcpPlc {}
+    /// 读取PLC状态(RT)：CPU型号、版本、程序容量、运行模式及错误标志
+    pub async fn read_status(&self) -> Result<NewtocolStatus, PlcError> {
+        let buf = create_cmd_buf(Cmd::RT.to_str(), self.station)?;
+        let reply = self.send_and_receive(&buf).await?;
+        NewtocolStatus::parse(&reply)
+    }
 
-unsafe impl Sync for NewtocolTcpPlc {}
+    /// 远程切换PLC运行模式(RM)
+    ///
+    /// 注意：PLC的模式开关必须处于 RUN 位置才能远程切换
+    pub async fn set_mode(&self, mode: NewtocolMode) -> PlcResult {
+        let cmd = format!("{}{}", Cmd::RM.to_str(), mode.to_char());
+        let buf = create_cmd_buf(&cmd, self.station)?;
+        self.send_and_receive(&buf).await?;
+        Ok(())
+    }
 
-impl IPlc for NewtocolTcpPlc {
-    fn new(conn: PlcConnector, timeout: std::time::Duration) -> Self {
-        NewtocolTcpPlc {
-            conn,
-            client: None,
-            timeout,
-            station: 1,
-        }
+    /// 读取PLC自诊断错误代码，0 表示无错误
+    pub async fn read_error_code(&self) -> Result<u16, PlcError> {
+        let status = self.read_status().await?;
+        Ok(status.self_diagnostic_error)
     }
 
-    async fn connect(&mut self) -> PlcResult {
-        match &self.conn {
-            PlcConnector::SerialPort(value) => {
-                let err = format!("连接参数错误,此处需要Network参数\t{:?}", value);
-                event!(Level::ERROR, "\t{}", &err);
-                Err(PlcError::Param(err))
-            }
-            PlcConnector::Network(value) => {
-                let addr = format!("{}:{}", value.ip_address, value.ip_port);
-                // let r = TcpStream::connect(addr).await;
-                let r = timeout(self.timeout, TcpStream::connect(addr)).await?;
-                if let Err(err) = r {
-                    let err = format!("连接错误\t{}", err);
-                    event!(Level::ERROR, "\t{}", &err);
-                    Err(PlcError::Comm(err))
-                } else {
-                    let client = r.unwrap();
-                    let _ = client.set_nodelay(true); // ! 解决网络物理断开后重连需要50秒的问题
-                    self.client = Some(Arc::new(Mutex::new(client)));
-                    Ok(())
-                }
-            }
-        }
+    /// 读取32位整数，地址例如 `DDT100`、`DT100`、`IXDDT100`，低位字在前
+    ///
+    /// * `len` - 读取的32位数据数量
+    pub async fn read_i32(
+        &self,
+        address_name: impl Into<String>,
+        len: u16,
+    ) -> Result<Vec<i32>, PlcError> {
+        let words = self.read_double(address_name, len).await?;
+        Ok(words
+            .chunks_exact(2)
+            .map(|w| ((w[0] as u32) | ((w[1] as u32) << 16)) as i32)
+            .collect())
     }
 
-    async fn disconnect(&mut self) -> PlcResult {
-        if let Some(tcp) = &self.client {
-            let tcp = Arc::clone(&tcp);
-            let mut tcp = tcp.lock().await;
-            let _ = tcp.shutdown();
-            drop(tcp);
-        }
-        self.client = None;
-        Ok(())
+    /// 读取32位浮点数，地址例如 `DF100`、`DT100`、`IXDF100`，低位字在前
+    ///
+    /// * `len` - 读取的32位数据数量
+    pub async fn read_f32(
+        &self,
+        address_name: impl Into<String>,
+        len: u16,
+    ) -> Result<Vec<f32>, PlcError> {
+        let words = self.read_double(address_name, len).await?;
+        Ok(words
+            .chunks_exact(2)
+            .map(|w| f32::from_bits((w[0] as u32) | ((w[1] as u32) << 16)))
+            .collect())
     }
 
-    async fn read(
+    /// 写入32位整数，地址例如 `DDT100`、`DT100`、`IXDDT100`，低位字在前
+    pub async fn write_i32(&self, address_name: impl Into<String>, datas: &[i32]) -> PlcResult {
+        let words: Vec<u16> = datas
+            .iter()
+            .flat_map(|v| [*v as u16, ((*v as u32) >> 16) as u16])
+            .collect();
+        let address = double_address(address_name).await?;
+        self.write_address(address, &words).await
+    }
+
+    /// 写入32位浮点数，地址例如 `DF100`、`DT100`、`IXDF100`，低位字在前
+    pub async fn write_f32(&self, address_name: impl Into<String>, datas: &[f32]) -> PlcResult {
+        let words: Vec<u16> = datas
+            .iter()
+            .flat_map(|v| [v.to_bits() as u16, (v.to_bits() >> 16) as u16])
+            .collect();
+        let address = double_address(address_name).await?;
+        self.write_address(address, &words).await
+    }
+
+    /// 按32位读取原始字数据，`len` 为32位数据的数量
+    async fn read_double(
         &self,
         address_name: impl Into<String>,
-        data_type: DataType,
         len: u16,
     ) -> Result<Vec<u16>, PlcError> {
+        let address = double_address(address_name).await?;
+        self.read_address(address, len.saturating_mul(2)).await
+    }
+
+    /// 读取已解析的寄存器地址，`len` 为字数
+    async fn read_address(&self, address: NewtocolAddress, len: u16) -> Result<Vec<u16>, PlcError> {
         if len == 0 {
             return Err(PlcError::Param("读取长度不能为0".into()));
         }
@@ -112,52 +130,131 @@ impl IPlc for NewtocolTcpPlc {
             // 实测结果
             return Err(PlcError::Param("读取长度不能大于960".into()));
         }
-        // 解析寄存器地址
-        let address = NewtocolAddress::new(address_name, data_type).await?;
+        let address = self.resolve_index(address).await?;
         // 创建读取PLC数据buffer
         let buf = create_read_buf(&address, len, self.station)?;
-        // 获取发送客户端
-        if let None = self.client {
-            return Err(PlcError::NotConnect);
-        }
-        let client = self.client.as_ref().unwrap();
-        let mut client = client.lock().await;
-        let r = client.writable().await;
-        if let Err(err) = r {
-            return Err(PlcError::Comm(err.to_string()));
+        let reply = self.send_and_receive(&buf).await?;
+        parse_reply_data(&reply, len, &address)
+    }
+
+    /// 写入已解析的寄存器地址
+    async fn write_address(&self, address: NewtocolAddress, datas: &[u16]) -> PlcResult {
+        let address = self.resolve_index(address).await?;
+        // 创建写入PLC数据buffer
+        let buf = create_write_buf(&address, datas, self.station)?;
+        if self.station == BROADCAST_STATION {
+            return self.send_only(&buf).await;
         }
-        // 写入数据
-        let r = timeout(self.timeout, client.write(buf.as_slice())).await?;
-        if let Err(err) = r {
-            return Err(PlcError::Comm(err.to_string()));
+        self.send_and_receive(&buf).await?;
+        Ok(())
+    }
+
+    /// 变址修饰的地址：先读取变址寄存器(IX/IY)的值，再加到地址上
+    async fn resolve_index(&self, address: NewtocolAddress) -> Result<NewtocolAddress, PlcError> {
+        let Some(index) = address.index_register() else {
+            return Ok(address);
+        };
+        let index_address = NewtocolAddress::new(format!("{}0", index), DataType::Word).await?;
+        let buf = create_read_buf(&index_address, 1, self.station)?;
+        let reply = self.send_and_receive(&buf).await?;
+        let value = parse_reply_data(&reply, 1, &index_address)?;
+        let Some(value) = value.first() else {
+            return Err(PlcError::Comm(format!("读取变址寄存器{}失败", index)));
+        };
+        address.with_offset(*value as i16 as i32)
+    }
+
+    /// 发送指令并等待PLC回复
+    ///
+    /// # Return
+    /// 校验通过后的回复数据，从起始符`%`开始，不包含校验码和结束符
+    pub(super) async fn send_and_receive(&self, buf: &[u8]) -> Result<Vec<u8>, PlcError> {
+        // 请求中的站号，用于丢弃其他站号的回复(例如上一次超时后迟到的回复)
+        let station = buf.get(1..3).unwrap_or_default().to_vec();
+        if station == b"FF" {
+            return Err(PlcError::Param("广播指令PLC不回复，只能写入".into()));
         }
-        // 读取返回数据,有可能接收数据不完整，所以需要循环读取
-        let mut buf = [0u8; 1024 * 4];
-        let mut index = 0;
-        loop {
-            let r = timeout(self.timeout, client.read(&mut buf[index..])).await?;
-            match r {
-                Ok(n) if n == 0 => return Err(PlcError::Comm("读取数据为空".into())),
-                Ok(n) => {
-                    index += n;
-                    let r = quick_check(&buf[0..index])?;
-                    match r {
-                        Ok(arr) => {
-                            let arr = slow_check(&arr)?;
-                            return parse_reply_data(arr, len, &address);
-                        }
-                        Err(err) => {
-                            println!("数据不完整,继续等待...\terr={}", err);
-                            index += n;
-                            continue;
-                        }
+        self.channel
+            .send_and_receive(buf, |mut buf| {
+                // 缓冲区中可能包含多帧数据，依次处理
+                while let Ok(arr) = quick_check(buf)? {
+                    let arr = slow_check(arr)?;
+                    if arr.get(1..3) == Some(station.as_slice()) {
+                        return Ok(Ok(arr));
                     }
+                    event!(
+                        Level::WARN,
+                        "丢弃其他站号的回复\t回复={}",
+                        String::from_utf8_lossy(arr)
+                    );
+                    // 只丢弃这一帧，保留之后接收到的数据
+                    let end = buf
+                        .iter()
+                        .position(|b| *b == cmd_char::END as u8)
+                        .map_or(buf.len(), |p| p + 1);
+                    buf = &buf[end..];
                 }
-                Err(err) => return Err(PlcError::Comm(err.to_string())),
-            };
+                Ok(Err("数据不完整"))
+            })
+            .await
+    }
+}
+
+impl NewtocolTcpPlc {
+    /// 使用已经打开的连接代替 `connect`
+    pub fn attach(&mut self, transport: impl Transport + 'static) {
+        self.channel.attach(transport);
+    }
+
+    /// 只发送指令，不等待PLC回复(广播)
+    async fn send_only(&self, buf: &[u8]) -> PlcResult {
+        self.channel.send(buf).await
+    }
+
+    /// 重新连接，所有共享此连接的实例都会使用新的连接
+    pub(super) async fn reconnect_shared(&mut self) -> PlcResult {
+        self.channel.reconnect().await
+    }
+
+    /// 断开连接，所有共享此连接的实例在 `reconnect_shared` 后继续使用新的连接
+    pub(super) async fn close_shared(&self) -> PlcResult {
+        self.channel.close().await
+    }
+}
+
+unsafe impl Send for NewtocolTcpPlc {}
+
+unsafe impl Sync for NewtocolTcpPlc {}
+
+impl IPlc for NewtocolTcpPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        NewtocolTcpPlc {
+            channel: Channel::new(conn, timeout),
+            station: 1,
         }
     }
 
+    async fn connect(&mut self) -> PlcResult {
+        self.channel.connect().await
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        // 解析寄存器地址
+        let address = NewtocolAddress::new(address_name, data_type).await?;
+        // 32位数据寄存器每个数据占用两个字
+        let len = len.saturating_mul(address.word_count());
+        self.read_address(address, len).await
+    }
+
     async fn write(
         &self,
         address_name: impl Into<String>,
@@ -166,52 +263,52 @@ impl IPlc for NewtocolTcpPlc {
     ) -> PlcResult {
         // 解析寄存器地址
         let address = NewtocolAddress::new(address_name, data_type).await?;
-        // 创建读取PLC数据buffer
-        let buf = create_write_buf(&address, datas, self.station)?;
-        // 获取发送客户端
-        if let None = self.client {
-            return Err(PlcError::NotConnect);
-        }
-        let client = self.client.as_ref().unwrap();
-        let mut client = client.lock().await;
-        let r = client.writable().await;
-        if let Err(err) = r {
-            return Err(PlcError::Comm(err.to_string()));
-        }
-        // 写入数据
-        let r = timeout(self.timeout, client.write(buf.as_slice())).await?;
-        if let Err(err) = r {
-            return Err(PlcError::Comm(err.to_string()));
-        }
-        // 读取返回数据,有可能接收数据不完整，所以需要循环读取
-        let mut buf = [0u8; 1024 * 4];
-        let mut index = 0;
-        loop {
-            let r = timeout(self.timeout, client.read(&mut buf[index..])).await?;
-            match r {
-                Ok(n) if n == 0 => return Err(PlcError::Comm("读取数据为空".into())),
-                Ok(n) => {
-                    index += n;
-                    let r = quick_check(&buf[0..index])?;
-                    match r {
-                        Ok(arr) => {
-                            let _ = slow_check(&arr)?;
-                            return Ok(());
-                        }
-                        Err(err) => {
-                            println!("数据不完整,继续等待...\terr={}", err);
-                            index += n;
-                            continue;
-                        }
-                    }
-                }
-                Err(err) => return Err(PlcError::Comm(err.to_string())),
-            };
+        if address.double_word().is_some() && !datas.len().is_multiple_of(2) {
+            return Err(PlcError::Param(
+                "32位数据寄存器的写入数据长度必须为2的整数倍".into(),
+            ));
         }
+        self.write_address(address, datas).await
     }
 
     fn is_connect(&self) -> bool {
-        self.client.is_some()
+        self.channel.is_connect()
+    }
+}
+
+/// 创建通用指令：起始符 + 站号 + 固定符 + 指令内容 + BCC + 结束符
+pub(super) fn create_cmd_buf(cmd: &str, station: u8) -> Result<Vec<u8>, PlcError> {
+    let mut buf = String::new();
+    buf.push(cmd_char::START);
+    buf.push_str(&station_str(station)?);
+    buf.push(cmd_char::FIX);
+    buf.push_str(cmd);
+    let bcc = bcc(buf.as_bytes());
+    buf.push_str(&bcc);
+    buf.push(cmd_char::END);
+    Ok(Vec::from(buf))
+}
+
+/// 解析32位数据的地址，只能是数据寄存器
+async fn double_address(address_name: impl Into<String>) -> Result<NewtocolAddress, PlcError> {
+    let address = NewtocolAddress::new(address_name, DataType::Word).await?;
+    if address.is_coil() || address.is_timer_value() {
+        return Err(PlcError::Addr(format!(
+            "32位数据只能读写数据寄存器\t寄存器={}",
+            address.get_address_name()
+        )));
+    }
+    Ok(address)
+}
+
+/// 站号：01~99 为两位十六进制，FF 为广播
+fn station_str(station: u8) -> Result<String, PlcError> {
+    match station {
+        1..=99 | BROADCAST_STATION => Ok(format!("{:0>2X}", station)),
+        _ => Err(PlcError::Param(format!(
+            "站号超出范围[1~99]\t站号={}",
+            station
+        ))),
     }
 }
 
@@ -224,8 +321,19 @@ where
     let data_type = address.get_data_type();
     let mut buf = String::new();
     buf.push(cmd_char::START);
-    buf.push_str(&format!("{:0>2X}", station));
+    buf.push_str(&station_str(station)?);
     buf.push(cmd_char::FIX);
+    if address.is_timer_value() {
+        let cmd = match address.get_address_header() {
+            "SV" => Cmd::RS,
+            _ => Cmd::RK,
+        };
+        push_timer_value_cmd(&mut buf, cmd, address, len as usize)?;
+        let bcc = bcc(buf.as_bytes());
+        buf.push_str(&bcc);
+        buf.push(cmd_char::END);
+        return Ok(Vec::from(buf));
+    }
     match data_type {
         DataType::Bit => {
             let offset = ((address.get_address() as u8) << 4) >> 4;
@@ -268,7 +376,7 @@ where
                 buf.push_str(Cmd::RD.to_str());
                 buf.push_str(address.get_address_header());
                 buf.push_str(&format!("{:0>5}", address.get_address()));
-                buf.push_str(&format!("{:0>5}", address.get_address() + (len as u32)));
+                buf.push_str(&format!("{:0>5}", address.get_address() + (len as u32) - 1));
             }
         },
     }
@@ -288,9 +396,23 @@ where
     let data_type = address.get_data_type();
     let mut buf = String::new();
     buf.push(cmd_char::START);
-    buf.push_str(&format!("{:0>2X}", station));
+    buf.push_str(&station_str(station)?);
     buf.push(cmd_char::FIX);
     let len = datas.len();
+    if address.is_timer_value() {
+        let cmd = match address.get_address_header() {
+            "SV" => Cmd::WS,
+            _ => Cmd::WK,
+        };
+        push_timer_value_cmd(&mut buf, cmd, address, len)?;
+        for data in datas {
+            buf.push_str(&format!("{:0>4X}", to_dcba(data)));
+        }
+        let bcc = bcc(buf.as_bytes());
+        buf.push_str(&bcc);
+        buf.push(cmd_char::END);
+        return Ok(Vec::from(buf));
+    }
     match data_type {
         DataType::Bit => {
             if len == 1 {
@@ -349,6 +471,37 @@ where
     Ok(v)
 }
 
+/// 添加定时器/计数器设定值(SV)、经过值(EV)的读写指令及地址范围
+///
+/// 格式：指令 + 起始编号(4位) + 结束编号(4位)
+fn push_timer_value_cmd(
+    buf: &mut String,
+    cmd: Cmd,
+    address: &NewtocolAddress,
+    len: usize,
+) -> Result<(), PlcError> {
+    if *address.get_data_type() == DataType::Bit {
+        return Err(PlcError::Param(
+            "定时器/计数器的设定值和经过值只能按字读写".into(),
+        ));
+    }
+    if len == 0 {
+        return Err(PlcError::Param("读写长度不能为0".into()));
+    }
+    let start = address.get_address();
+    let end = start + (len as u32) - 1;
+    if end > 9999 {
+        return Err(PlcError::Addr(format!(
+            "定时器/计数器编号超出范围[0~9999]\t寄存器={}",
+            address.get_address_name()
+        )));
+    }
+    buf.push_str(cmd.to_str());
+    buf.push_str(&format!("{:0>4}", start));
+    buf.push_str(&format!("{:0>4}", end));
+    Ok(())
+}
+
 fn bcc(content: &[u8]) -> String {
     let mut bcc = 0;
     for i in 0..content.len() {
@@ -358,7 +511,7 @@ fn bcc(content: &[u8]) -> String {
 }
 
 /// 16位高低字节交换
-fn to_dcba(value: &u16) -> u16 {
+pub(super) fn to_dcba(value: &u16) -> u16 {
     // let high = (value >> 8) as u8;
     // let low = *value as u8;
     // let value = ((low as u16) << 8) + (high as u16);
@@ -401,12 +554,14 @@ fn quick_check(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
     // 快速校验数据
     if idx_start != usize::MAX && idx_end != usize::MAX {
         if idx_ok != usize::MAX {
-            return Ok(Ok(&buf[idx_start..(idx_end - idx_start + 1)]));
+            return Ok(Ok(&buf[idx_start..=idx_end]));
         } else if idx_err != usize::MAX {
-            let e = (buf[idx_err] as u32)
-                << 24 + (buf[idx_err + 1] as u32)
-                << 16 + (buf[idx_err + 2] as u32)
-                << 8 + (buf[idx_err + 3] as u32);
+            // 错误回复：%01!42**CR，错误代码为2位十进制
+            let e = buf
+                .get(idx_err + 1..idx_err + 3)
+                .and_then(|code| std::str::from_utf8(code).ok())
+                .and_then(|code| code.parse::<u32>().ok())
+                .unwrap_or_default();
             let err_str = match e {
                 20 => "未定义错误",
                 21 => "NACK 错误",
@@ -436,7 +591,7 @@ fn quick_check(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
                 67 => "丢失数据错误",
                 _ => "未知错误",
             };
-            return Err(PlcError::Comm(err_str.to_string()));
+            return Err(PlcError::Comm(format!("{}\t错误代码={}", err_str, e)));
         } else {
             return Err(PlcError::Comm("数据校验错误".to_string()));
         }
@@ -469,32 +624,32 @@ fn slow_check(buf: &[u8]) -> Result<&[u8], PlcError> {
 /// 注意，需要调用slow_check获取有效数据后再调用此方法
 ///
 /// TODO: 按字读取Coil 的解析
-fn parse_reply_data<'a, T>(buf: &'a [u8], len: u16, address: &T) -> Result<Vec<u16>, PlcError>
+fn parse_reply_data<T>(buf: &[u8], len: u16, address: &T) -> Result<Vec<u16>, PlcError>
 where
     T: IAddress,
 {
+    let invalid = || PlcError::Comm(format!("数据解析失败\t数据={:02X?}", buf));
     let data_type = address.get_data_type();
     // 检查数据是否是按字读取: bit模式 长度 大于 8
     let a = address.get_address() as u8;
     let offset = a << 4 >> 4;
     let is_bit_read_as_word = ((offset as u16) + len) > 8;
-    let str = std::str::from_utf8(&buf);
-    if str.is_err() {
-        return Err(PlcError::Comm("数据解析失败".to_string()));
+    // 按字节位置切分，非 ASCII 字符会导致切分到字符中间
+    if !buf.is_ascii() {
+        return Err(invalid());
     }
-    let str = str.unwrap();
-    let cmd = &str[4..6];
+    let str = std::str::from_utf8(buf).map_err(|_| invalid())?;
+    let cmd = str.get(4..6).ok_or_else(invalid)?;
     let mut datas: Vec<u16> = vec![];
     let len = len as usize;
-    if cmd == "RC" || cmd == "RD" {
+    if cmd == "RC" || cmd == "RD" || cmd == "RS" || cmd == "RK" {
         match data_type {
             DataType::Bit => {
                 // %01#RCSR001214CR -> %01$RC120**CR
                 // 按字读取的需要特殊转换，要考虑初始偏移量
                 if is_bit_read_as_word {
-                    for i in 6..6 + len {
-                        datas.push(*&buf[i] as u16);
-                    }
+                    let bits = buf.get(6..6 + len).ok_or_else(invalid)?;
+                    datas.extend(bits.iter().map(|bit| *bit as u16));
                 } else {
                     // 每16bit计算一次，避免每次重复计算
                     // for i in 0..len {}
@@ -503,11 +658,10 @@ where
             DataType::Word => {
                 for i in 0..len {
                     let base = 6 + i * 4;
-                    let low = &str[base..base + 2];
-                    let high = &str[base + 2..base + 4];
-                    let value = u16::from_str_radix(low, 16).unwrap()
-                        + (u16::from_str_radix(high, 16).unwrap() << 8);
-                    datas.push(value);
+                    let word = str.get(base..base + 4).ok_or_else(invalid)?;
+                    let low = u16::from_str_radix(&word[..2], 16).map_err(|_| invalid())?;
+                    let high = u16::from_str_radix(&word[2..], 16).map_err(|_| invalid())?;
+                    datas.push(low + (high << 8));
                 }
             }
         }
@@ -517,16 +671,23 @@ where
 
 #[cfg(test)]
 mod tests {
+    use super::super::newtocol::DoubleWord;
     use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     #[tokio::test]
     async fn test_create_read_buf() {
         let address = NewtocolAddress::new("D0", DataType::Word).await.unwrap();
         let buf = create_read_buf(&address, 10, 1).unwrap();
-        assert_eq!(
-            buf,
-            [37, 48, 49, 35, 82, 68, 68, 48, 48, 48, 48, 48, 48, 48, 48, 49, 48, 53, 52, 13]
-        );
+        // 结束地址为 D9
+        assert_eq!(buf, b"%01#RDD00000000095C\r");
+        // 站号为十六进制
+        let address = NewtocolAddress::new("D0", DataType::Word).await.unwrap();
+        let buf = create_read_buf(&address, 1, 10).unwrap();
+        assert_eq!(buf, b"%0A#RDD000000000025\r");
+        let buf = create_read_buf(&address, 1, 99).unwrap();
+        assert_eq!(buf, b"%63#RDD000000000051\r");
+        assert!(create_read_buf(&address, 1, 100).is_err());
     }
 
     #[tokio::test]
@@ -542,6 +703,186 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_create_timer_value_buf() {
+        let address = NewtocolAddress::new("SV10", DataType::Word).await.unwrap();
+        let buf = create_read_buf(&address, 3, 1).unwrap();
+        assert_eq!(buf, b"%01#RS0010001204\r");
+
+        let address = NewtocolAddress::new("EV0", DataType::Word).await.unwrap();
+        let buf = create_read_buf(&address, 1, 1).unwrap();
+        assert_eq!(buf, b"%01#RK000000001E\r");
+
+        let address = NewtocolAddress::new("SV5", DataType::Word).await.unwrap();
+        let buf = create_write_buf(&address, &[0x10], 1).unwrap();
+        assert_eq!(buf, b"%01#WS00050005100002\r");
+
+        let address = NewtocolAddress::new("EV1", DataType::Word).await.unwrap();
+        let buf = create_write_buf(&address, &[0x01, 1000], 1).unwrap();
+        assert_eq!(buf, b"%01#WK000100020100E80367\r");
+
+        let address = NewtocolAddress::new("SV9999", DataType::Word)
+            .await
+            .unwrap();
+        assert!(create_read_buf(&address, 2, 1).is_err());
+        let address = NewtocolAddress::new("EV1", DataType::Bit).await.unwrap();
+        assert!(create_read_buf(&address, 1, 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_timer_value_reply() {
+        let address = NewtocolAddress::new("SV10", DataType::Word).await.unwrap();
+        let reply = b"%01$RS0100E803**\r";
+        let datas = parse_reply_data(reply, 2, &address).unwrap();
+        assert_eq!(datas, [1, 1000]);
+        // 数据不足、非十六进制字符、非 ASCII 字符时返回错误
+        for reply in [
+            &b"%01$RS0100E8"[..],
+            b"%01$RS01G0E803**\r",
+            "%01$RS0100\u{4E2D}3**\r".as_bytes(),
+            b"%01",
+        ] {
+            let r = parse_reply_data(reply, 2, &address);
+            assert!(matches!(r, Err(PlcError::Comm(_))));
+        }
+    }
+
+    #[test]
+    fn test_create_cmd_buf() {
+        let cmd = format!("{}{}", Cmd::RM.to_str(), NewtocolMode::Run.to_char());
+        assert_eq!(create_cmd_buf(&cmd, 1).unwrap(), b"%01#RMR4A\r");
+        let cmd = format!("{}{}", Cmd::RM.to_str(), NewtocolMode::Prog.to_char());
+        assert_eq!(create_cmd_buf(&cmd, 1).unwrap(), b"%01#RMP48\r");
+        assert_eq!(create_cmd_buf(Cmd::RT.to_str(), 1).unwrap(), b"%01#RT01\r");
+        // 站号为十六进制，FF为广播
+        assert_eq!(create_cmd_buf(Cmd::RT.to_str(), 12).unwrap(), b"%0C#RT73\r");
+        assert_eq!(
+            create_cmd_buf(Cmd::RT.to_str(), 0xFF).unwrap(),
+            b"%FF#RT00\r"
+        );
+        assert!(create_cmd_buf(Cmd::RT.to_str(), 0).is_err());
+        assert!(create_cmd_buf(Cmd::RT.to_str(), 100).is_err());
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let reply = quick_check(b"%01$RT0310328100040A0079\r").unwrap().unwrap();
+        let reply = slow_check(reply).unwrap();
+        let status = NewtocolStatus::parse(reply).unwrap();
+        assert_eq!(status.cpu_type, "03");
+        assert_eq!(status.cpu_version, "10");
+        assert_eq!(status.program_capacity, 32);
+        assert!(status.is_run());
+        assert!(status.is_remote());
+        assert_eq!(status.error_flag, 0x04);
+        assert_eq!(status.self_diagnostic_error, 10);
+        assert!(status.has_error());
+    }
+
+    #[test]
+    fn test_error_reply() {
+        let r = quick_check(b"%01!6300\r");
+        match r {
+            Err(PlcError::Comm(err)) => assert!(err.contains("PLC模式错误"), "{}", err),
+            _ => panic!("应当返回通讯错误"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_status() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 64];
+            let n = socket.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"%01#RT01\r");
+            // 分两次回复，检查数据不完整时能够继续等待
+            socket.write_all(b"%01$RT031032").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            socket.write_all(b"8100040A0079\r").await.unwrap();
+        });
+        let mut plc = NewtocolTcpPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        plc.connect().await.unwrap();
+        let code = plc.read_error_code().await.unwrap();
+        assert_eq!(code, 10);
+    }
+
+    #[tokio::test]
+    async fn test_double_and_index_address() {
+        let address = NewtocolAddress::new("DT100", DataType::Word).await.unwrap();
+        assert_eq!(address.get_address_header(), "D");
+        assert_eq!(address.get_address(), 100);
+        assert_eq!(address.word_count(), 1);
+
+        let address = NewtocolAddress::new("DDT100", DataType::Word)
+            .await
+            .unwrap();
+        assert_eq!(address.double_word(), Some(DoubleWord::Int));
+        assert_eq!(address.word_count(), 2);
+        let address = NewtocolAddress::new("DF20", DataType::Word).await.unwrap();
+        assert_eq!(address.double_word(), Some(DoubleWord::Float));
+        assert_eq!(address.get_address(), 20);
+        assert!(NewtocolAddress::new("DDT100", DataType::Bit).await.is_err());
+
+        let address = NewtocolAddress::new("I0DT100", DataType::Word)
+            .await
+            .unwrap();
+        assert_eq!(address.index_register(), Some("IX"));
+        assert_eq!(address.with_offset(-10).unwrap().get_address(), 90);
+        assert!(address.with_offset(-101).is_err());
+        let address = NewtocolAddress::new("IYDDT100", DataType::Word)
+            .await
+            .unwrap();
+        assert_eq!(address.index_register(), Some("IY"));
+        assert_eq!(address.double_word(), Some(DoubleWord::Int));
+        // 读写变址寄存器本身
+        let address = NewtocolAddress::new("IX0", DataType::Word).await.unwrap();
+        assert_eq!(address.index_register(), None);
+        assert_eq!(address.get_address_header(), "IX");
+        assert!(NewtocolAddress::new("IXR10", DataType::Bit).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_i32_with_index() {
+        fn frame(content: &str) -> Vec<u8> {
+            format!("{}{}\r", content, bcc(content.as_bytes())).into_bytes()
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 64];
+            // 先读取变址寄存器 IX = 5
+            let n = socket.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], frame("%01#RDIX0000000000"));
+            socket.write_all(&frame("%01$RD0500")).await.unwrap();
+            // 再读取 D105~D106
+            let n = socket.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], frame("%01#RDD0010500106"));
+            socket.write_all(&frame("%01$RDFEFFFFFF")).await.unwrap();
+        });
+        let mut plc = NewtocolTcpPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        );
+        plc.connect().await.unwrap();
+        let r = plc.read_i32("IXDDT100", 1).await.unwrap();
+        assert_eq!(r, [-2]);
+        // 32位数据不能读写线圈、定时器和计数器
+        for address in ["R10", "Y0", "SV0", "EV0"] {
+            let r = plc.write_i32(address, &[1]).await;
+            assert!(matches!(r, Err(PlcError::Addr(_))), "{}", address);
+            let r = plc.write_f32(address, &[1.0]).await;
+            assert!(matches!(r, Err(PlcError::Addr(_))), "{}", address);
+            let r = plc.read_i32(address, 1).await;
+            assert!(matches!(r, Err(PlcError::Addr(_))), "{}", address);
+        }
+    }
+
     #[test]
     fn test_to_dcba() {
         let value = 0x1234u16;
@@ -1,6 +1,22 @@
-pub use crate::core::{IAddress, Network, PlcConnector, SerailPort};
+pub use crate::allen_bradley::EipTcpPlc;
+pub use crate::beckhoff::AdsTcpPlc;
+#[cfg(unix)]
+pub use crate::core::SerialStream;
+pub use crate::core::{
+    FlowControl, IAddress, Network, Parity, PlcConnector, Rs485, SerailPort, StopBits, Transport,
+    UdpTransport,
+};
 pub use crate::error::{PlcError, PlcResult};
+#[cfg(unix)]
+pub use crate::fatek::FatekPlc;
 pub use crate::ipcsun::{IpcsunEio1010G, IpcsunEio1608I};
+pub use crate::keyence::KvTcpPlc;
+pub use crate::ls::XgtFenetPlc;
 pub use crate::mitsubishi::Mc3eBinaryTcpPlc;
-pub use crate::panasonic::NewtocolTcpPlc;
+#[cfg(unix)]
+pub use crate::modbus::ModbusRtuPlc;
+pub use crate::modbus::ModbusTcpPlc;
+pub use crate::omron::FinsPlc;
+pub use crate::panasonic::{Mewtocol7TcpPlc, NewtocolTcpPlc};
+pub use crate::siemens::S7TcpPlc;
 pub use crate::{DataType, IPlc};
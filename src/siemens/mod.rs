@@ -0,0 +1,14 @@
+// ! 西门子PLC
+
+mod s7;
+mod s7_tcp;
+
+pub use self::s7::{S7Address, S7Area};
+pub use self::s7_tcp::S7TcpPlc;
+use crate::{core::PlcConnector, IPlc};
+use std::time::Duration;
+
+/// 创建一个 西门子 网口PLC (S7 协议，端口一般为102)
+pub fn new_s7_tcp_plc(conn: PlcConnector, timeout: Duration) -> S7TcpPlc {
+    S7TcpPlc::new(conn, timeout)
+}
@@ -0,0 +1,579 @@
+// ! S7 协议：TPKT(RFC1006) + COTP(ISO 8073) + S7comm
+
+use crate::prelude::*;
+
+/// TPKT 报文头长度：版本(1) + 保留(1) + 长度(2)
+pub const TPKT_LEN: usize = 4;
+
+/// COTP 数据报文头：长度(1) + 类型 DT(1) + 最后一个数据单元(1)
+const COTP_DT: [u8; 3] = [0x02, 0xF0, 0x80];
+
+/// 请求报文 S7 头部长度
+const JOB_HEADER_LEN: usize = 10;
+
+/// 回复报文 S7 头部长度，比请求多了错误类型(1) + 错误码(1)
+const ACK_HEADER_LEN: usize = 12;
+
+/// 读取请求中报文头和参数的长度，回复的数据不能超过 PDU 长度减去此值
+pub const READ_OVERHEAD: u16 = 18;
+
+/// 写入请求中报文头、参数和数据头部的长度，写入的数据不能超过 PDU 长度减去此值
+pub const WRITE_OVERHEAD: u16 = 28;
+
+/// 一次最多写入的位数量(每个位一个数据项)
+pub const MAX_WRITE_BITS: usize = 20;
+
+/// S7 数据区
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum S7Area {
+    /// 输入(I)
+    Input,
+    /// 输出(Q)
+    Output,
+    /// 中间继电器(M)
+    Merker,
+    /// 数据块(DB)，参数为数据块编号
+    DataBlock(u16),
+}
+
+impl S7Area {
+    /// 数据区代码
+    pub fn code(&self) -> u8 {
+        match self {
+            S7Area::Input => 0x81,
+            S7Area::Output => 0x82,
+            S7Area::Merker => 0x83,
+            S7Area::DataBlock(_) => 0x84,
+        }
+    }
+
+    /// 数据块编号，其他数据区为0
+    pub fn db_number(&self) -> u16 {
+        match self {
+            S7Area::DataBlock(number) => *number,
+            _ => 0,
+        }
+    }
+}
+
+/// S7 寄存器地址
+///
+/// 支持以下格式：
+/// * 数据块：`DB1.DBX0.3`(位)、`DB1.DBB10`、`DB1.DBW10`、`DB1.DBD10`(字节地址)
+/// * 输入、输出、中间继电器：`I0.1`、`Q4.0`、`M10.0`、`MX10.0`(位)、`IB0`、`QW4`、`MW20`、`MD20`、`M20`(字节地址)
+///
+/// 位地址只能按位读写；字节地址按字读写，每个字为从字节地址开始的2个字节(高字节在前)
+pub struct S7Address {
+    address_name: String,
+    data_type: DataType,
+    /// 寄存器头部，例如 `DB1.DBW`、`MW`
+    inner_address_header: String,
+    /// 数据区
+    inner_area: S7Area,
+    /// 字节地址
+    inner_byte: u32,
+    /// 位地址 0~7
+    inner_bit: u8,
+}
+
+impl S7Address {
+    /// 实例化一个寄存器地址
+    ///
+    /// # Error
+    /// 无效的寄存器地址，或者地址与数据类型不一致
+    pub fn new(address_name: impl Into<String>, data_type: DataType) -> Result<Self, PlcError> {
+        let address_name = address_name.into().trim().to_uppercase();
+        let invalid = || PlcError::Addr(format!("S7 无效的寄存器地址\t寄存器={}", &address_name));
+        let (area, rest, header_len) = if let Some(db) = address_name.strip_prefix("DB") {
+            let (number, rest) = db.split_once(".DB").ok_or_else(invalid)?;
+            let number = number.parse::<u16>().map_err(|_| invalid())?;
+            if number == 0 {
+                return Err(invalid());
+            }
+            (
+                S7Area::DataBlock(number),
+                rest,
+                address_name.len() - rest.len(),
+            )
+        } else {
+            let area = match address_name.as_bytes().first() {
+                Some(b'I') => S7Area::Input,
+                Some(b'Q') => S7Area::Output,
+                Some(b'M') => S7Area::Merker,
+                _ => return Err(invalid()),
+            };
+            (area, &address_name[1..], 1)
+        };
+        // 数据宽度：X 位；B/W/D 字节、字、双字；数据块必须指定，其他数据区可以省略
+        let (size, number) = match rest.as_bytes().first() {
+            Some(size @ (b'X' | b'B' | b'W' | b'D')) => (Some(*size), &rest[1..]),
+            _ if matches!(area, S7Area::DataBlock(_)) => return Err(invalid()),
+            _ => (None, rest),
+        };
+        let header = &address_name[..header_len + size.is_some() as usize];
+        let (byte, bit) = match number.split_once('.') {
+            Some((byte, bit)) if matches!(size, None | Some(b'X')) => {
+                let bit = bit.parse::<u8>().map_err(|_| invalid())?;
+                if bit > 7 {
+                    return Err(invalid());
+                }
+                (byte, Some(bit))
+            }
+            None if size != Some(b'X') => (number, None),
+            _ => return Err(invalid()),
+        };
+        if byte.is_empty() || !byte.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let byte = byte.parse::<u32>().map_err(|_| invalid())?;
+        // 协议中的地址为3个字节的位地址
+        if byte > 0x1F_FFFF {
+            return Err(invalid());
+        }
+        match (bit.is_some(), &data_type) {
+            (true, DataType::Word) => Err(PlcError::Addr(format!(
+                "S7 位地址只能按位读写\t寄存器={}",
+                &address_name
+            ))),
+            (false, DataType::Bit) => Err(PlcError::Addr(format!(
+                "S7 字节地址不能按位读写\t寄存器={}",
+                &address_name
+            ))),
+            _ => Ok(Self {
+                inner_address_header: header.to_owned(),
+                address_name,
+                data_type,
+                inner_area: area,
+                inner_byte: byte,
+                inner_bit: bit.unwrap_or(0),
+            }),
+        }
+    }
+
+    /// 数据区
+    pub fn area(&self) -> S7Area {
+        self.inner_area
+    }
+
+    /// 字节地址
+    pub fn byte_offset(&self) -> u32 {
+        self.inner_byte
+    }
+
+    /// 位地址 0~7，字节地址时为0
+    pub fn bit_offset(&self) -> u8 {
+        self.inner_bit
+    }
+}
+
+impl IAddress for S7Address {
+    fn get_address_name(&self) -> &str {
+        &self.address_name
+    }
+
+    fn get_address_header(&self) -> &str {
+        &self.inner_address_header
+    }
+
+    fn get_address(&self) -> u32 {
+        self.inner_byte
+    }
+
+    fn get_data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// 读写数据项的传输单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum S7Transport {
+    /// 按位，每个数据项一个位
+    Bit,
+    /// 按字节
+    Byte,
+}
+
+/// 读写数据项
+#[derive(Debug, Clone, Copy)]
+pub struct S7Item {
+    pub area: S7Area,
+    /// 起始位地址：字节地址 * 8 + 位地址
+    pub start: u32,
+    pub transport: S7Transport,
+    /// 数量，按位时为1
+    pub count: u16,
+}
+
+impl S7Item {
+    /// 数据项参数
+    fn param(&self) -> [u8; 12] {
+        let [count_hi, count_lo] = self.count.to_be_bytes();
+        let [db_hi, db_lo] = self.area.db_number().to_be_bytes();
+        let [_, start_hi, start_mid, start_lo] = self.start.to_be_bytes();
+        [
+            0x12,
+            0x0A,
+            0x10,
+            match self.transport {
+                S7Transport::Bit => 0x01,
+                S7Transport::Byte => 0x02,
+            },
+            count_hi,
+            count_lo,
+            db_hi,
+            db_lo,
+            self.area.code(),
+            start_hi,
+            start_mid,
+            start_lo,
+        ]
+    }
+}
+
+/// COTP 连接请求
+///
+/// # Param
+/// * `local_tsap` - 本地 TSAP
+/// * `remote_tsap` - PLC 的 TSAP
+pub fn create_connect_request(local_tsap: u16, remote_tsap: u16) -> Vec<u8> {
+    let mut cotp = vec![
+        0x11, // 长度
+        0xE0, // 连接请求 CR
+        0x00, 0x00, // 目标引用
+        0x00, 0x01, // 源引用
+        0x00, // 类别
+        0xC0, 0x01, 0x0A, // TPDU 长度 1024
+        0xC1, 0x02, // 本地 TSAP
+    ];
+    cotp.extend_from_slice(&local_tsap.to_be_bytes());
+    cotp.extend_from_slice(&[0xC2, 0x02]); // PLC 的 TSAP
+    cotp.extend_from_slice(&remote_tsap.to_be_bytes());
+    tpkt(&cotp)
+}
+
+/// 检查 COTP 连接确认
+pub fn check_connect_reply(frame: &[u8]) -> PlcResult {
+    match frame.get(TPKT_LEN + 1) {
+        Some(pdu_type) if pdu_type & 0xF0 == 0xD0 => Ok(()),
+        _ => Err(PlcError::Comm(format!(
+            "S7 COTP 连接被拒绝，请检查机架号、槽号或者 TSAP\t数据={:02X?}",
+            frame
+        ))),
+    }
+}
+
+/// S7 通讯设置请求，协商 PDU 长度
+pub fn create_setup_request(pdu_ref: u16, pdu_size: u16) -> Vec<u8> {
+    let mut param = vec![0xF0, 0x00, 0x00, 0x01, 0x00, 0x01];
+    param.extend_from_slice(&pdu_size.to_be_bytes());
+    s7_job(pdu_ref, &param, &[])
+}
+
+/// 解析 S7 通讯设置回复，返回协商后的 PDU 长度
+pub fn parse_setup_reply(frame: &[u8], pdu_ref: u16) -> Result<u16, PlcError> {
+    let (param, _) = s7_reply(frame, pdu_ref)?;
+    match param {
+        [0xF0, _, _, _, _, _, hi, lo] => Ok(u16::from_be_bytes([*hi, *lo])),
+        _ => Err(PlcError::Comm(format!(
+            "S7 通讯设置回复错误\t数据={:02X?}",
+            frame
+        ))),
+    }
+}
+
+/// 读取请求，每次读取一个数据项
+pub fn create_read_request(pdu_ref: u16, item: &S7Item) -> Vec<u8> {
+    let mut param = vec![0x04, 0x01];
+    param.extend_from_slice(&item.param());
+    s7_job(pdu_ref, &param, &[])
+}
+
+/// 解析读取回复，返回读取的字节
+pub fn parse_read_reply(frame: &[u8], pdu_ref: u16) -> Result<Vec<u8>, PlcError> {
+    let (param, data) = s7_reply(frame, pdu_ref)?;
+    if param.first() != Some(&0x04) {
+        return Err(PlcError::Comm(format!(
+            "S7 读取回复的功能码错误\t数据={:02X?}",
+            frame
+        )));
+    }
+    match data {
+        [0xFF, transport, hi, lo, data @ ..] => {
+            let len = u16::from_be_bytes([*hi, *lo]) as usize;
+            // 0x03 位、0x09 字节串、0x07 实数的长度单位为字节，其他为位
+            let len = match transport {
+                0x03 | 0x07 | 0x09 => len,
+                _ => len / 8,
+            };
+            match data.get(..len) {
+                Some(data) => Ok(data.to_vec()),
+                None => Err(PlcError::Comm(format!(
+                    "S7 读取回复的数据长度错误\t数据={:02X?}",
+                    frame
+                ))),
+            }
+        }
+        [code, ..] => Err(return_code_error(*code)),
+        _ => Err(PlcError::Comm(format!(
+            "S7 读取回复的数据错误\t数据={:02X?}",
+            frame
+        ))),
+    }
+}
+
+/// 写入请求
+///
+/// # Param
+/// * `items` - 数据项和写入的数据，按位写入时数据为 0 或者 1
+pub fn create_write_request(pdu_ref: u16, items: &[(S7Item, &[u8])]) -> Vec<u8> {
+    let mut param = vec![0x05, items.len() as u8];
+    let mut data = Vec::new();
+    for (i, (item, values)) in items.iter().enumerate() {
+        param.extend_from_slice(&item.param());
+        let (transport, bits) = match item.transport {
+            S7Transport::Bit => (0x03, values.len()),
+            S7Transport::Byte => (0x04, values.len() * 8),
+        };
+        data.extend_from_slice(&[0x00, transport]);
+        data.extend_from_slice(&(bits as u16).to_be_bytes());
+        data.extend_from_slice(values);
+        // 除最后一项外，数据长度为奇数时补齐
+        if values.len() % 2 == 1 && i + 1 < items.len() {
+            data.push(0x00);
+        }
+    }
+    s7_job(pdu_ref, &param, &data)
+}
+
+/// 检查写入回复，每个数据项的返回码都必须为成功
+pub fn check_write_reply(frame: &[u8], pdu_ref: u16, count: usize) -> PlcResult {
+    let (param, data) = s7_reply(frame, pdu_ref)?;
+    if param.first() != Some(&0x05) || data.len() != count {
+        return Err(PlcError::Comm(format!(
+            "S7 写入回复错误\t数据={:02X?}",
+            frame
+        )));
+    }
+    match data.iter().find(|code| **code != 0xFF) {
+        Some(code) => Err(return_code_error(*code)),
+        None => Ok(()),
+    }
+}
+
+/// 检查 TPKT 报文是否完整
+/// #Return
+/// * `Ok(OK(buf))` => 数据完整，返回完整的报文
+/// * `Ok(Err(err))` => 数据不完整，需要等待接收剩余部分
+/// * `Err(err)` => 接收数据错误
+pub fn check_tpkt(buf: &[u8]) -> Result<Result<&[u8], &str>, PlcError> {
+    if buf.len() < TPKT_LEN {
+        return Ok(Err("数据未接收完成"));
+    }
+    let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+    if buf[0] != 0x03 || len < TPKT_LEN + 3 {
+        return Err(PlcError::Comm(format!(
+            "S7 TPKT 报文头错误\t数据={:02X?}",
+            &buf[..TPKT_LEN]
+        )));
+    }
+    match buf.len() >= len {
+        true => Ok(Ok(&buf[..len])),
+        false => Ok(Err("数据未接收完成")),
+    }
+}
+
+/// TPKT 报文
+fn tpkt(payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x03, 0x00];
+    frame.extend_from_slice(&((payload.len() + TPKT_LEN) as u16).to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// S7 请求报文：TPKT + COTP DT + S7 头部 + 参数 + 数据
+fn s7_job(pdu_ref: u16, param: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(COTP_DT.len() + JOB_HEADER_LEN + param.len() + data.len());
+    payload.extend_from_slice(&COTP_DT);
+    payload.extend_from_slice(&[0x32, 0x01, 0x00, 0x00]);
+    payload.extend_from_slice(&pdu_ref.to_be_bytes());
+    payload.extend_from_slice(&(param.len() as u16).to_be_bytes());
+    payload.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    payload.extend_from_slice(param);
+    payload.extend_from_slice(data);
+    tpkt(&payload)
+}
+
+/// 检查 S7 回复报文，返回参数和数据
+fn s7_reply(frame: &[u8], pdu_ref: u16) -> Result<(&[u8], &[u8]), PlcError> {
+    let invalid = || PlcError::Comm(format!("S7 回复报文错误\t数据={:02X?}", frame));
+    let cotp_len = *frame.get(TPKT_LEN).ok_or_else(invalid)? as usize + 1;
+    let s7 = frame.get(TPKT_LEN + cotp_len..).ok_or_else(invalid)?;
+    if s7.len() < ACK_HEADER_LEN || s7[0] != 0x32 || s7[1] != 0x03 {
+        return Err(invalid());
+    }
+    if s7[4..6] != pdu_ref.to_be_bytes() {
+        return Err(PlcError::Comm(format!(
+            "S7 回复与请求不一致\t请求编号={}\t数据={:02X?}",
+            pdu_ref, frame
+        )));
+    }
+    if s7[10..12] != [0x00, 0x00] {
+        return Err(PlcError::Comm(format!(
+            "S7 PLC 返回错误\t错误类型={:#04X}\t错误码={:#04X}",
+            s7[10], s7[11]
+        )));
+    }
+    let param_len = u16::from_be_bytes([s7[6], s7[7]]) as usize;
+    let data_len = u16::from_be_bytes([s7[8], s7[9]]) as usize;
+    let param = s7
+        .get(ACK_HEADER_LEN..ACK_HEADER_LEN + param_len)
+        .ok_or_else(invalid)?;
+    let data = s7
+        .get(ACK_HEADER_LEN + param_len..ACK_HEADER_LEN + param_len + data_len)
+        .ok_or_else(invalid)?;
+    Ok((param, data))
+}
+
+/// 数据项返回码转换为错误
+fn return_code_error(code: u8) -> PlcError {
+    match code {
+        0x01 => PlcError::Comm("S7 返回码01：硬件错误".into()),
+        0x03 => PlcError::Param("S7 返回码03：禁止访问".into()),
+        0x05 => PlcError::Addr("S7 返回码05：地址超出范围".into()),
+        0x06 => PlcError::Param("S7 返回码06：不支持的数据类型".into()),
+        0x07 => PlcError::Param("S7 返回码07：数据类型不一致".into()),
+        0x0A => PlcError::Addr("S7 返回码0A：对象不存在".into()),
+        _ => PlcError::Comm(format!("S7 未知的返回码{:02X}", code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let cases = [
+            (
+                "DB1.DBX0.3",
+                DataType::Bit,
+                S7Area::DataBlock(1),
+                0,
+                3,
+                "DB1.DBX",
+            ),
+            (
+                "db10.dbw10",
+                DataType::Word,
+                S7Area::DataBlock(10),
+                10,
+                0,
+                "DB10.DBW",
+            ),
+            (
+                "DB1.DBD4",
+                DataType::Word,
+                S7Area::DataBlock(1),
+                4,
+                0,
+                "DB1.DBD",
+            ),
+            ("M10.0", DataType::Bit, S7Area::Merker, 10, 0, "M"),
+            ("MX10.7", DataType::Bit, S7Area::Merker, 10, 7, "MX"),
+            ("I0.1", DataType::Bit, S7Area::Input, 0, 1, "I"),
+            ("Q4.0", DataType::Bit, S7Area::Output, 4, 0, "Q"),
+            ("MW20", DataType::Word, S7Area::Merker, 20, 0, "MW"),
+            ("IB3", DataType::Word, S7Area::Input, 3, 0, "IB"),
+            ("M20", DataType::Word, S7Area::Merker, 20, 0, "M"),
+        ];
+        for (name, data_type, area, byte, bit, header) in cases {
+            let r = S7Address::new(name, data_type).unwrap();
+            assert_eq!(
+                (
+                    r.area(),
+                    r.byte_offset(),
+                    r.bit_offset(),
+                    r.get_address_header()
+                ),
+                (area, byte, bit, header),
+                "{}",
+                name
+            );
+        }
+        let invalid = [
+            ("DB1.DBX0", DataType::Bit),
+            ("DB1.DBW0.1", DataType::Bit),
+            ("DB0.DBW0", DataType::Word),
+            ("DB1.W0", DataType::Word),
+            ("DB1.DB0", DataType::Word),
+            ("M10.8", DataType::Bit),
+            ("MX10", DataType::Bit),
+            ("MW20", DataType::Bit),
+            ("M10.0", DataType::Word),
+            ("V10", DataType::Word),
+            ("MW", DataType::Word),
+            ("MW-1", DataType::Word),
+            ("MW2097152", DataType::Word),
+        ];
+        for (name, data_type) in invalid {
+            assert!(S7Address::new(name, data_type).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_frame() {
+        let request = create_connect_request(0x0100, 0x0102);
+        assert_eq!(request.len(), 22);
+        assert_eq!(&request[..4], [0x03, 0x00, 0x00, 0x16]);
+        assert!(check_connect_reply(&[0x03, 0x00, 0x00, 0x0B, 0x06, 0xD0, 0, 0, 0, 1, 0]).is_ok());
+        assert!(check_connect_reply(&[0x03, 0x00, 0x00, 0x0B, 0x06, 0x80, 0, 0, 0, 1, 0]).is_err());
+
+        let item = S7Item {
+            area: S7Area::DataBlock(1),
+            start: 10 * 8,
+            transport: S7Transport::Byte,
+            count: 4,
+        };
+        let request = create_read_request(0x0102, &item);
+        assert_eq!(
+            request,
+            [
+                0x03, 0x00, 0x00, 0x1F, 0x02, 0xF0, 0x80, 0x32, 0x01, 0x00, 0x00, 0x01, 0x02, 0x00,
+                0x0E, 0x00, 0x00, 0x04, 0x01, 0x12, 0x0A, 0x10, 0x02, 0x00, 0x04, 0x00, 0x01, 0x84,
+                0x00, 0x00, 0x50,
+            ]
+        );
+        let reply = [
+            0x03, 0x00, 0x00, 0x1D, 0x02, 0xF0, 0x80, 0x32, 0x03, 0x00, 0x00, 0x01, 0x02, 0x00,
+            0x02, 0x00, 0x08, 0x00, 0x00, 0x04, 0x01, 0xFF, 0x04, 0x00, 0x20, 0x01, 0x02, 0x03,
+            0x04,
+        ];
+        assert_eq!(check_tpkt(&reply[..10]).unwrap(), Err("数据未接收完成"));
+        assert_eq!(check_tpkt(&reply).unwrap().unwrap().len(), reply.len());
+        assert_eq!(parse_read_reply(&reply, 0x0102).unwrap(), [1, 2, 3, 4]);
+        assert!(parse_read_reply(&reply, 0x0103).is_err());
+        let mut invalid = reply;
+        invalid[21] = 0x05;
+        assert!(matches!(
+            parse_read_reply(&invalid, 0x0102),
+            Err(PlcError::Addr(_))
+        ));
+
+        let bit = S7Item {
+            area: S7Area::Merker,
+            start: 10 * 8 + 1,
+            transport: S7Transport::Bit,
+            count: 1,
+        };
+        let request = create_write_request(0x0001, &[(bit, &[1]), (bit, &[0])]);
+        // 第一个数据项补齐为偶数长度
+        assert_eq!(
+            &request[request.len() - 11..],
+            [0x00, 0x03, 0x00, 0x01, 0x01, 0x00, 0x00, 0x03, 0x00, 0x01, 0x00]
+        );
+    }
+}
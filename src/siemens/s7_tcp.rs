@@ -0,0 +1,479 @@
+// ! 西门子 S7 协议(ISO-on-TCP)
+
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{event, Level};
+
+use super::s7::{self, S7Address, S7Area, S7Item, S7Transport};
+use crate::core::{Channel, Transport};
+use crate::prelude::*;
+
+/// 请求的 PDU 长度，PLC 会回复实际支持的长度(S7-300/1200 一般为 240，S7-1500 为 960)
+const REQUEST_PDU_SIZE: u16 = 960;
+
+/// 西门子 S7 网口PLC
+///
+/// 默认机架号0、槽号1(S7-1200/1500)，S7-300 一般为槽号2，可以通过 [`S7TcpPlc::rack_slot`]
+/// 或者 [`S7TcpPlc::tsap`] 设置。读写数据按协商后的 PDU 长度自动分包。
+pub struct S7TcpPlc {
+    /// 通讯通道
+    channel: Channel,
+    /// 本地 TSAP
+    local_tsap: u16,
+    /// PLC 的 TSAP
+    remote_tsap: u16,
+    /// 协商后的 PDU 长度，克隆的客户端共用
+    pdu_size: Arc<AtomicU16>,
+    /// 请求编号
+    pdu_ref: Arc<AtomicU16>,
+}
+
+impl Clone for S7TcpPlc {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            local_tsap: self.local_tsap,
+            remote_tsap: self.remote_tsap,
+            pdu_size: self.pdu_size.clone(),
+            pdu_ref: self.pdu_ref.clone(),
+        }
+    }
+}
+
+impl S7TcpPlc {
+    /// 按机架号和槽号设置 PLC 的 TSAP(PG 连接)，需要在连接前设置
+    pub fn rack_slot(mut self, rack: u8, slot: u8) -> Self {
+        self.remote_tsap = 0x0100 | ((rack as u16 & 0x07) << 5) | (slot as u16 & 0x1F);
+        self
+    }
+
+    /// 直接设置本地和 PLC 的 TSAP，需要在连接前设置
+    ///
+    /// 例如 LOGO! 或者通过 NetPro 组态的连接
+    pub fn tsap(mut self, local_tsap: u16, remote_tsap: u16) -> Self {
+        self.local_tsap = local_tsap;
+        self.remote_tsap = remote_tsap;
+        self
+    }
+
+    /// 协商后的 PDU 长度，未连接时为0
+    pub fn pdu_size(&self) -> u16 {
+        match self.channel.is_connect() {
+            true => self.pdu_size.load(Ordering::Acquire),
+            false => 0,
+        }
+    }
+
+    /// 按字节读取数据，按 PDU 长度分包
+    async fn read_bytes(&self, area: S7Area, byte: u32, count: usize) -> Result<Vec<u8>, PlcError> {
+        check_range(byte, count)?;
+        let max = self.max_data(s7::READ_OVERHEAD)?;
+        let mut bytes = Vec::with_capacity(count);
+        while bytes.len() < count {
+            let n = max.min(count - bytes.len());
+            let item = S7Item {
+                area,
+                start: (byte + bytes.len() as u32) * 8,
+                transport: S7Transport::Byte,
+                count: n as u16,
+            };
+            let pdu_ref = self.next_ref();
+            let reply = self
+                .send_and_receive(&s7::create_read_request(pdu_ref, &item))
+                .await?;
+            let data = s7::parse_read_reply(&reply, pdu_ref)?;
+            if data.len() != n {
+                return Err(PlcError::Comm(format!(
+                    "S7 读取回复的数据长度错误\t长度={}\t需要={}",
+                    data.len(),
+                    n
+                )));
+            }
+            bytes.extend_from_slice(&data);
+        }
+        Ok(bytes)
+    }
+
+    /// 按字节写入数据，按 PDU 长度分包
+    async fn write_bytes(&self, area: S7Area, byte: u32, datas: &[u8]) -> PlcResult {
+        check_range(byte, datas.len())?;
+        let max = self.max_data(s7::WRITE_OVERHEAD)?;
+        for (i, chunk) in datas.chunks(max).enumerate() {
+            let item = S7Item {
+                area,
+                start: (byte + (i * max) as u32) * 8,
+                transport: S7Transport::Byte,
+                count: chunk.len() as u16,
+            };
+            let pdu_ref = self.next_ref();
+            let reply = self
+                .send_and_receive(&s7::create_write_request(pdu_ref, &[(item, chunk)]))
+                .await?;
+            s7::check_write_reply(&reply, pdu_ref, 1)?;
+        }
+        Ok(())
+    }
+
+    /// 按位写入数据，每个位一个数据项
+    async fn write_bits(&self, area: S7Area, start: u32, states: &[u8]) -> PlcResult {
+        check_range(start / 8, (start as usize % 8 + states.len()).div_ceil(8))?;
+        // 每个数据项：参数(12) + 数据头部(4) + 数据(1) + 补齐(1)
+        let max = ((self.pdu_size().saturating_sub(11)) / 18) as usize;
+        let max = max.min(s7::MAX_WRITE_BITS);
+        if max == 0 {
+            return Err(PlcError::NotConnect);
+        }
+        for (i, chunk) in states.chunks(max).enumerate() {
+            let items: Vec<(S7Item, &[u8])> = chunk
+                .iter()
+                .enumerate()
+                .map(|(j, state)| {
+                    let item = S7Item {
+                        area,
+                        start: start + (i * max + j) as u32,
+                        transport: S7Transport::Bit,
+                        count: 1,
+                    };
+                    (item, std::slice::from_ref(state))
+                })
+                .collect();
+            let pdu_ref = self.next_ref();
+            let reply = self
+                .send_and_receive(&s7::create_write_request(pdu_ref, &items))
+                .await?;
+            s7::check_write_reply(&reply, pdu_ref, items.len())?;
+        }
+        Ok(())
+    }
+
+    /// 每个请求最多读写的字节数(偶数)
+    fn max_data(&self, overhead: u16) -> Result<usize, PlcError> {
+        match self.pdu_size().saturating_sub(overhead) & !1 {
+            0 => Err(PlcError::NotConnect),
+            max => Ok(max as usize),
+        }
+    }
+
+    fn next_ref(&self) -> u16 {
+        self.pdu_ref.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// 发送请求并返回完整的回复报文
+    async fn send_and_receive(&self, buf: &[u8]) -> Result<Vec<u8>, PlcError> {
+        self.channel.send_and_receive(buf, s7::check_tpkt).await
+    }
+
+    /// 使用已经打开的连接代替 `connect`，同样进行 COTP 连接和 S7 通讯设置
+    pub async fn attach(&mut self, transport: impl Transport + 'static) -> PlcResult {
+        self.channel.attach(transport);
+        self.setup().await
+    }
+
+    /// 依次进行 COTP 连接和 S7 通讯设置，失败时断开连接
+    async fn setup(&mut self) -> PlcResult {
+        let r = self.negotiate().await;
+        match r {
+            Ok(pdu_size) => {
+                event!(Level::DEBUG, "S7 协商后的 PDU 长度\t{}", pdu_size);
+                self.pdu_size.store(pdu_size, Ordering::Release);
+                Ok(())
+            }
+            Err(err) => {
+                let _ = self.channel.close().await;
+                Err(err)
+            }
+        }
+    }
+
+    /// COTP 连接和 S7 通讯设置，返回协商后的 PDU 长度
+    async fn negotiate(&self) -> Result<u16, PlcError> {
+        let request = s7::create_connect_request(self.local_tsap, self.remote_tsap);
+        let reply = self.send_and_receive(&request).await?;
+        s7::check_connect_reply(&reply)?;
+        let pdu_ref = self.next_ref();
+        let request = s7::create_setup_request(pdu_ref, REQUEST_PDU_SIZE);
+        let reply = self.send_and_receive(&request).await?;
+        s7::parse_setup_reply(&reply, pdu_ref)
+    }
+}
+
+/// 检查读写范围是否超出 S7 地址范围
+fn check_range(byte: u32, count: usize) -> PlcResult {
+    if byte as usize + count > 0x20_0000 {
+        return Err(PlcError::Addr(format!(
+            "S7 读写范围超出地址范围\t字节地址={}\t长度={}",
+            byte, count
+        )));
+    }
+    Ok(())
+}
+
+unsafe impl Send for S7TcpPlc {}
+
+unsafe impl Sync for S7TcpPlc {}
+
+impl IPlc for S7TcpPlc {
+    fn new(conn: PlcConnector, timeout: Duration) -> Self {
+        S7TcpPlc {
+            channel: Channel::new(conn, timeout),
+            local_tsap: 0x0100,
+            remote_tsap: 0x0101,
+            pdu_size: Arc::new(AtomicU16::new(0)),
+            pdu_ref: Arc::new(AtomicU16::new(1)),
+        }
+    }
+
+    /// 连接PLC：建立 TCP 连接后依次进行 COTP 连接和 S7 通讯设置
+    async fn connect(&mut self) -> PlcResult {
+        self.channel.connect().await?;
+        self.setup().await
+    }
+
+    async fn disconnect(&mut self) -> PlcResult {
+        self.channel.disconnect().await
+    }
+
+    /// 读取数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `DB1.DBW10`、`DB1.DBX0.3`、`M10.0`、`I0.1`、`Q4.0`、`MW20`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 从位地址开始读取，每个位一个数据
+    ///     * Word 从字节地址开始读取，每个字为2个字节(高字节在前)
+    /// * `len` - 数据长度
+    async fn read(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        len: u16,
+    ) -> Result<Vec<u16>, PlcError> {
+        if len == 0 {
+            return Err(PlcError::Param("读取长度不能为0".into()));
+        }
+        let address = S7Address::new(address_name, data_type.clone())?;
+        let byte = address.byte_offset();
+        match data_type {
+            DataType::Word => {
+                let bytes = self
+                    .read_bytes(address.area(), byte, len as usize * 2)
+                    .await?;
+                Ok(bytes
+                    .chunks(2)
+                    .map(|word| u16::from_be_bytes([word[0], word[1]]))
+                    .collect())
+            }
+            DataType::Bit => {
+                // 读取包含所有位的字节
+                let bit = address.bit_offset() as usize;
+                let count = (bit + len as usize).div_ceil(8);
+                let bytes = self.read_bytes(address.area(), byte, count).await?;
+                Ok((bit..bit + len as usize)
+                    .map(|i| ((bytes[i / 8] >> (i % 8)) & 1) as u16)
+                    .collect())
+            }
+        }
+    }
+
+    /// 写入数据
+    ///
+    /// # Param
+    /// * `address_name` - 寄存器地址，例如 `DB1.DBW10`、`DB1.DBX0.3`、`M10.0`、`Q4.0`、`MW20`
+    /// * `data_type` - 数据类型：
+    ///     * Bit 从位地址开始写入，0：关闭；1：打开
+    ///     * Word 从字节地址开始写入，每个字为2个字节(高字节在前)
+    /// * `datas` - 需要写入的数据
+    async fn write(
+        &self,
+        address_name: impl Into<String>,
+        data_type: DataType,
+        datas: &[u16],
+    ) -> PlcResult {
+        if datas.is_empty() {
+            return Err(PlcError::Param("写入数据不能为空".into()));
+        }
+        let address = S7Address::new(address_name, data_type.clone())?;
+        let byte = address.byte_offset();
+        match data_type {
+            DataType::Word => {
+                let bytes: Vec<u8> = datas.iter().flat_map(|word| word.to_be_bytes()).collect();
+                self.write_bytes(address.area(), byte, &bytes).await
+            }
+            DataType::Bit => {
+                if datas.iter().any(|state| *state > 1) {
+                    return Err(PlcError::Param("位写入数据只能是 1 或者 0".into()));
+                }
+                let states: Vec<u8> = datas.iter().map(|state| *state as u8).collect();
+                let start = byte * 8 + address.bit_offset() as u32;
+                self.write_bits(address.area(), start, &states).await
+            }
+        }
+    }
+
+    fn is_connect(&self) -> bool {
+        self.channel.is_connect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 模拟 PLC：PDU 长度 240，每个数据区 1024 字节，初始值为字节地址的低8位
+    async fn mock_plc(requests: Arc<AtomicU16>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut memory: HashMap<(u8, u16), Vec<u8>> = HashMap::new();
+            loop {
+                let mut header = [0u8; 4];
+                if socket.read_exact(&mut header).await.is_err() {
+                    return;
+                }
+                let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+                let mut payload = vec![0u8; len - 4];
+                socket.read_exact(&mut payload).await.unwrap();
+                if payload[1] == 0xE0 {
+                    // COTP 连接确认
+                    let mut cc = payload.clone();
+                    cc[1] = 0xD0;
+                    let mut frame = vec![0x03, 0x00];
+                    frame.extend_from_slice(&((cc.len() + 4) as u16).to_be_bytes());
+                    frame.extend_from_slice(&cc);
+                    socket.write_all(&frame).await.unwrap();
+                    continue;
+                }
+                requests.fetch_add(1, Ordering::Relaxed);
+                let s7 = &payload[3..];
+                let pdu_ref = [s7[4], s7[5]];
+                let param_len = u16::from_be_bytes([s7[6], s7[7]]) as usize;
+                let param = &s7[10..10 + param_len];
+                let mut data = &s7[10 + param_len..];
+                let (reply_param, reply_data) = match param[0] {
+                    0xF0 => (vec![0xF0, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0xF0], vec![]),
+                    fc @ (0x04 | 0x05) => {
+                        let mut reply_data = vec![];
+                        for item in param[2..].chunks(12) {
+                            let count = u16::from_be_bytes([item[4], item[5]]) as usize;
+                            let db = u16::from_be_bytes([item[6], item[7]]);
+                            let start = u32::from_be_bytes([0, item[9], item[10], item[11]]);
+                            let area = memory
+                                .entry((item[8], db))
+                                .or_insert_with(|| (0..1024).map(|i| i as u8).collect::<Vec<u8>>());
+                            let byte = (start / 8) as usize;
+                            if fc == 0x04 {
+                                reply_data.extend_from_slice(&[0xFF, 0x04]);
+                                reply_data.extend_from_slice(&((count * 8) as u16).to_be_bytes());
+                                reply_data.extend_from_slice(&area[byte..byte + count]);
+                                continue;
+                            }
+                            let bits = u16::from_be_bytes([data[2], data[3]]) as usize;
+                            let values = match data[1] {
+                                0x03 => &data[4..4 + bits],
+                                _ => &data[4..4 + bits / 8],
+                            };
+                            if item[3] == 0x01 {
+                                let mask = 1 << (start % 8);
+                                match values[0] {
+                                    0 => area[byte] &= !mask,
+                                    _ => area[byte] |= mask,
+                                }
+                            } else {
+                                area[byte..byte + values.len()].copy_from_slice(values);
+                            }
+                            data = &data[(4 + values.len()).min(data.len())..];
+                            if values.len() % 2 == 1 && !data.is_empty() {
+                                data = &data[1..];
+                            }
+                            reply_data.push(0xFF);
+                        }
+                        (vec![fc, param[1]], reply_data)
+                    }
+                    _ => unreachable!(),
+                };
+                let mut s7_reply = vec![0x32, 0x03, 0x00, 0x00, pdu_ref[0], pdu_ref[1]];
+                s7_reply.extend_from_slice(&(reply_param.len() as u16).to_be_bytes());
+                s7_reply.extend_from_slice(&(reply_data.len() as u16).to_be_bytes());
+                s7_reply.extend_from_slice(&[0x00, 0x00]);
+                s7_reply.extend_from_slice(&reply_param);
+                s7_reply.extend_from_slice(&reply_data);
+                let mut frame = vec![0x03, 0x00];
+                frame.extend_from_slice(&((s7_reply.len() + 7) as u16).to_be_bytes());
+                frame.extend_from_slice(&[0x02, 0xF0, 0x80]);
+                frame.extend_from_slice(&s7_reply);
+                socket.write_all(&frame).await.unwrap();
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let requests = Arc::new(AtomicU16::new(0));
+        let port = mock_plc(requests.clone()).await;
+        let mut plc = S7TcpPlc::new(
+            Network::new("127.0.0.1", port).into(),
+            Duration::from_millis(300),
+        )
+        .rack_slot(0, 2);
+        assert_eq!(plc.remote_tsap, 0x0102);
+        assert!(plc.read("DB1.DBW0", DataType::Word, 1).await.is_err());
+        plc.connect().await.unwrap();
+        assert_eq!(plc.pdu_size(), 240);
+
+        let r = plc.read("DB1.DBW10", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x0A0B, 0x0C0D]);
+        // 0x01 = 0b0000_0001，从 bit0 开始
+        let r = plc.read("DB1.DBX1.0", DataType::Bit, 3).await.unwrap();
+        assert_eq!(r, [1, 0, 0]);
+        // 跨字节读取位：0xFF 的 bit6、bit7 和 0x00 的 bit0
+        let r = plc.read("M255.6", DataType::Bit, 3).await.unwrap();
+        assert_eq!(r, [1, 1, 0]);
+
+        // 按 PDU 长度分包：每次最多读取 222 字节
+        requests.store(0, Ordering::Relaxed);
+        let r = plc.read("MW0", DataType::Word, 150).await.unwrap();
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+        assert_eq!(r[111], 0xDEDF);
+        assert_eq!(r[149], 0x2A2B);
+
+        plc.write("DB2.DBW4", DataType::Word, &[0x1234, 0x5678])
+            .await
+            .unwrap();
+        let r = plc.read("DB2.DBB4", DataType::Word, 2).await.unwrap();
+        assert_eq!(r, [0x1234, 0x5678]);
+        // 每次最多写入 212 字节
+        requests.store(0, Ordering::Relaxed);
+        let datas: Vec<u16> = (0..120).collect();
+        plc.write("QW0", DataType::Word, &datas).await.unwrap();
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+        let r = plc.read("QW0", DataType::Word, 120).await.unwrap();
+        assert_eq!(r, datas);
+
+        requests.store(0, Ordering::Relaxed);
+        let states = [1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1];
+        plc.write("Q300.1", DataType::Bit, &states).await.unwrap();
+        // 每次最多写入 12 个位
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+        let r = plc.read("Q300.1", DataType::Bit, 14).await.unwrap();
+        assert_eq!(r, states);
+        let r = plc.read("QB300", DataType::Word, 1).await.unwrap();
+        // 0x2C 的 bit0 和 0x2D 的 bit7 不变
+        assert_eq!(r, [0xFA7F]);
+
+        assert!(plc.read("M10.0", DataType::Word, 1).await.is_err());
+        assert!(plc.write("M10.0", DataType::Bit, &[2]).await.is_err());
+        assert!(plc.read("MW2097150", DataType::Word, 2).await.is_err());
+        // 克隆的客户端共用协商后的 PDU 长度
+        let other = plc.clone();
+        plc.disconnect().await.unwrap();
+        assert_eq!(plc.pdu_size(), 0);
+        assert_eq!(other.pdu_size(), 240);
+        let r = other.read("QB300", DataType::Word, 1).await.unwrap();
+        assert_eq!(r, [0xFA7F]);
+    }
+}